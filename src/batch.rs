@@ -0,0 +1,479 @@
+//! Fluent accumulation of messages for batch sends.
+
+use std::{borrow::Borrow, collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    GraphiteError, GraphiteErrorKind, GraphiteMessage, MetricSink, ValidationRule, hook::Hook,
+    unix_now,
+};
+
+/// Signature of the callback receiving the messages a [`Batch`] dropped as too old.
+pub(crate) type ExpiredHook = dyn Fn(&[GraphiteMessage]) + Send + Sync;
+
+/// Signature of the callback choosing how a [`Batch`] coalesces each metric path.
+type CoalesceHook = dyn Fn(&str) -> Option<DedupePolicy> + Send + Sync;
+
+/// How a batch was transmitted by [`GraphiteClient::send_batch`] or
+/// [`GraphiteClient::send_batch_atomic`].
+///
+/// [`GraphiteClient::send_batch`]: crate::GraphiteClient::send_batch
+/// [`GraphiteClient::send_batch_atomic`]: crate::GraphiteClient::send_batch_atomic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchResult {
+    /// Number of messages in the batch, after any `batch_dedupe` collapsing and without
+    /// those skipped under [`NonFinitePolicy::Skip`](crate::NonFinitePolicy::Skip).
+    pub messages: usize,
+
+    /// Number of bytes in the encoded batch.
+    pub bytes: usize,
+
+    /// Number of separate buffers the encoded batch was written as.
+    pub chunks: usize,
+
+    /// Connections replaced while sending the batch, whether after a failed write or
+    /// because the connection had reached `max_connection_lifetime` or was found closed.
+    pub reconnects: usize,
+
+    /// Whether a write failed and the batch was resent after reconnecting.
+    ///
+    /// The failed write may have delivered part of the batch before breaking, so some
+    /// messages may have been received twice. This is set whenever anything was
+    /// written again, even if `resent` is zero.
+    pub possibly_duplicated: bool,
+
+    /// Number of messages written to the socket exactly once.
+    pub sent_once: usize,
+
+    /// Number of messages that were at least partly written before a failed write and
+    /// then written again, so may have been received twice.
+    pub resent: usize,
+
+    /// Number of messages cut off by a failed write and not written again, so may or may
+    /// not have been received. Only non-zero when `failure` is set.
+    pub maybe_sent: usize,
+
+    /// Number of messages never written. Only non-zero when `failure` is set.
+    pub unsent: usize,
+
+    /// Why the batch was only partly written, under
+    /// [`Delivery::AtMostOnce`](crate::Delivery::AtMostOnce) or when a chunk of a batch
+    /// split by `max_write_size` failed.
+    ///
+    /// Messages are written in order, so the first `sent_once` messages were written,
+    /// the next `maybe_sent` possibly were, and the remaining `unsent` were not.
+    pub failure: Option<GraphiteErrorKind>,
+}
+
+/// What happened to one message of a batch sent with
+/// [`GraphiteClient::send_batch_detailed`].
+///
+/// As with [`BatchResult`], a message counts as delivered once it was completely written
+/// to the socket, even though it may still have been in the kernel's send buffer when
+/// the connection broke.
+///
+/// [`GraphiteClient::send_batch_detailed`]: crate::GraphiteClient::send_batch_detailed
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum MessageOutcome {
+    /// The message was written exactly once.
+    Delivered,
+
+    /// The message was written, but a failure cut into it and it was written again after
+    /// reconnecting, so the server may have received it twice.
+    PossiblyDuplicated,
+
+    /// The message was not completely written, either because it failed validation or
+    /// because the batch write failed before reaching it. It can be sent again safely.
+    Failed(GraphiteError),
+
+    /// The message was left out under
+    /// [`NonFinitePolicy::Skip`](crate::NonFinitePolicy::Skip).
+    Skipped,
+}
+
+/// How points in a batch sharing a metric path and timestamp are collapsed into one.
+///
+/// The collapsed point takes the position of the first occurrence in the batch.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{DedupePolicy, GraphiteClient};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .batch_dedupe(DedupePolicy::Sum)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupePolicy {
+    /// Keep the value that appears first in the batch.
+    First,
+
+    /// Keep the value that appears last in the batch.
+    Last,
+
+    /// Send the sum of the values. Fails with
+    /// [`GraphiteErrorKind::InvalidMessage`] if a duplicated value isn't numeric.
+    Sum,
+
+    /// Send the largest value. Fails with [`GraphiteErrorKind::InvalidMessage`] if a
+    /// duplicated value isn't numeric.
+    Max,
+}
+
+/// Collapses messages in `msgs` sharing a metric path and the timestamp given by
+/// `timestamp_of`, according to `policy`.
+///
+/// Also returns, for each message in `msgs`, the index of the collapsed message it went
+/// into.
+pub(crate) fn dedupe<M: Borrow<GraphiteMessage>>(
+    msgs: &[M],
+    policy: DedupePolicy,
+    timestamp_of: impl Fn(&GraphiteMessage) -> u64,
+) -> Result<(Vec<GraphiteMessage>, Vec<usize>), GraphiteError> {
+    let mut out: Vec<GraphiteMessage> = Vec::with_capacity(msgs.len());
+    let mut merged_into = Vec::with_capacity(msgs.len());
+    let mut seen = HashMap::with_capacity(msgs.len());
+    for msg in msgs {
+        let msg = msg.borrow();
+        let timestamp = timestamp_of(msg);
+        let Some(&index) = seen.get(&(&*msg.metric_path, timestamp)) else {
+            seen.insert((&*msg.metric_path, timestamp), out.len());
+            merged_into.push(out.len());
+            out.push(msg.clone());
+            continue;
+        };
+        merged_into.push(index);
+        combine(policy, &mut out[index], msg)?;
+    }
+    Ok((out, merged_into))
+}
+
+/// Folds the value of `msg` into `kept` according to `policy`.
+fn combine(
+    policy: DedupePolicy,
+    kept: &mut GraphiteMessage,
+    msg: &GraphiteMessage,
+) -> Result<(), GraphiteError> {
+    match policy {
+        DedupePolicy::First => {}
+        DedupePolicy::Last => kept.value.clone_from(&msg.value),
+        DedupePolicy::Sum => kept.value = (numeric(kept)? + numeric(msg)?).to_string(),
+        DedupePolicy::Max => {
+            if numeric(msg)? > numeric(kept)? {
+                kept.value.clone_from(&msg.value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses the value of `msg` as a number for combining duplicates.
+fn numeric(msg: &GraphiteMessage) -> Result<f64, GraphiteError> {
+    msg.value.parse().map_err(|_| {
+        GraphiteError::invalid_message(
+            &*msg.metric_path,
+            ValidationRule::NonNumericValue,
+            format!("can't combine non-numeric value {:?}", msg.value),
+        )
+    })
+}
+
+/// A reusable buffer of metrics sent together with a single write.
+///
+/// `Batch` accumulates messages via [`push`](Batch::push) and
+/// [`push_message`](Batch::push_message), then sends them all through
+/// [`GraphiteClient::send_batch_message`], or any other [`MetricSink`], with
+/// [`send`](Batch::send). After a successful send the batch is emptied so it can be
+/// reused, which suits periodic flush loops.
+///
+/// Because a failed send keeps the messages, a batch also buffers points through an
+/// outage. Set [`max_metric_age`](Batch::max_metric_age) to drop points the retention
+/// schema has already aggregated past instead of replaying them after recovery.
+///
+/// Whisper keeps one value per path and second, so a gauge updated many times a second
+/// only needs its last value sent. Set [`coalesce`](Batch::coalesce) or
+/// [`coalesce_by`](Batch::coalesce_by) to combine such points as they are pushed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{Batch, GraphiteClient};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .build()?;
+///
+/// let mut batch = Batch::new();
+/// batch
+///     .push("server1.cpu", "45")
+///     .push("server1.memory", "80");
+/// batch.send(&mut client)?;
+/// assert!(batch.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`GraphiteClient::send_batch_message`]: crate::GraphiteClient::send_batch_message
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    /// Messages waiting to be sent, in insertion order.
+    messages: Vec<GraphiteMessage>,
+
+    /// Total size in bytes of the messages in plaintext protocol format.
+    size_bytes: usize,
+
+    /// Age beyond which messages are dropped instead of sent.
+    max_metric_age: Option<Duration>,
+
+    /// Messages dropped for exceeding `max_metric_age`.
+    expired: u64,
+
+    /// Callback receiving the messages dropped for exceeding `max_metric_age`.
+    on_expired: Option<Hook<ExpiredHook>>,
+
+    /// Callback choosing how points for each path are coalesced, if at all.
+    coalesce: Option<Hook<CoalesceHook>>,
+
+    /// Index into `messages` of the point kept for each coalesced path and timestamp.
+    coalesce_index: HashMap<(Arc<str>, u64), usize>,
+
+    /// Points folded into an earlier point by coalescing.
+    coalesced: u64,
+}
+
+impl PartialEq for Batch {
+    /// Compares everything but the callbacks and the coalescing index derived from the
+    /// messages.
+    fn eq(&self, other: &Self) -> bool {
+        self.messages == other.messages
+            && self.size_bytes == other.size_bytes
+            && self.max_metric_age == other.max_metric_age
+            && self.expired == other.expired
+            && self.coalesced == other.coalesced
+    }
+}
+
+impl Batch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops messages older than `max_age` before each send.
+    ///
+    /// Age is judged by each message's own timestamp rather than when it was added, so a
+    /// message whose timestamp is before `now - max_age` is dropped. Leave this unset for
+    /// batches carrying deliberately backfilled data.
+    pub fn max_metric_age(mut self, max_age: Duration) -> Self {
+        self.max_metric_age = Some(max_age);
+        self
+    }
+
+    /// Sets a callback receiving the messages dropped by
+    /// [`max_metric_age`](Batch::max_metric_age), e.g. to log or count them by path.
+    pub fn on_expired(mut self, f: impl Fn(&[GraphiteMessage]) + Send + Sync + 'static) -> Self {
+        self.on_expired = Some(Hook::new(Arc::new(f)));
+        self
+    }
+
+    /// Coalesces points sharing a metric path and timestamp as they are pushed,
+    /// combining them according to `policy`.
+    ///
+    /// Use [`DedupePolicy::Last`] for gauges. Counters need [`DedupePolicy::Sum`]
+    /// instead, so when a batch mixes both, choose per path with
+    /// [`coalesce_by`](Batch::coalesce_by).
+    pub fn coalesce(self, policy: DedupePolicy) -> Self {
+        self.coalesce_by(move |_| Some(policy))
+    }
+
+    /// Coalesces points sharing a metric path and timestamp as they are pushed, with the
+    /// policy `f` returns for the path. Points for a path `f` returns `None` for are all
+    /// kept.
+    ///
+    /// A point that [`DedupePolicy::Sum`] or [`DedupePolicy::Max`] can't combine because
+    /// a value isn't numeric is kept as a separate point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::{Batch, DedupePolicy, GraphiteMessage};
+    ///
+    /// let mut batch = Batch::new().coalesce_by(|path| {
+    ///     if path.ends_with(".count") {
+    ///         Some(DedupePolicy::Sum)
+    ///     } else {
+    ///         Some(DedupePolicy::Last)
+    ///     }
+    /// });
+    /// for line in ["queue.depth 3 1600000000", "queue.depth 5 1600000000"] {
+    ///     batch.push_message(line.parse().unwrap());
+    /// }
+    /// assert_eq!(batch.len(), 1);
+    /// assert_eq!(batch.coalesced(), 1);
+    /// ```
+    pub fn coalesce_by(
+        mut self,
+        f: impl Fn(&str) -> Option<DedupePolicy> + Send + Sync + 'static,
+    ) -> Self {
+        self.coalesce = Some(Hook::new(Arc::new(f)));
+        self
+    }
+
+    /// Adds a metric stamped with the current time.
+    pub fn push(&mut self, metric_path: &str, value: &str) -> &mut Self {
+        self.push_message(GraphiteMessage::new(metric_path, value))
+    }
+
+    /// Adds an existing message, or folds it into an earlier one when coalescing.
+    pub fn push_message(&mut self, msg: GraphiteMessage) -> &mut Self {
+        if let Some(policy) = self.coalesce.as_ref().and_then(|f| f(&msg.metric_path)) {
+            let key = (Arc::clone(&msg.metric_path), msg.timestamp);
+            if let Some(&index) = self.coalesce_index.get(&key) {
+                let kept = &mut self.messages[index];
+                let before = kept.encoded_len();
+                if combine(policy, kept, &msg).is_ok() {
+                    self.size_bytes = self.size_bytes - before + kept.encoded_len();
+                    self.coalesced += 1;
+                    return self;
+                }
+            } else {
+                self.coalesce_index.insert(key, self.messages.len());
+            }
+        }
+        self.size_bytes += msg.encoded_len();
+        self.messages.push(msg);
+        self
+    }
+
+    /// Returns the number of messages in the batch.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if the batch contains no messages.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Returns the total size in bytes of the batched messages in plaintext protocol format.
+    ///
+    /// This does not account for client-level transformations such as a metric prefix.
+    pub fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    /// Returns the batched messages.
+    pub fn messages(&self) -> &[GraphiteMessage] {
+        &self.messages
+    }
+
+    /// Returns how many messages have been dropped for exceeding
+    /// [`max_metric_age`](Batch::max_metric_age).
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    /// Returns how many pushed points have been folded into an earlier point by
+    /// [`coalesce`](Batch::coalesce) or [`coalesce_by`](Batch::coalesce_by), and so
+    /// won't be sent on their own.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+
+    /// Removes all messages from the batch.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.coalesce_index.clear();
+        self.size_bytes = 0;
+    }
+
+    /// Sends every batched message through `client`, which may be any [`MetricSink`].
+    ///
+    /// Messages older than [`max_metric_age`](Batch::max_metric_age) are dropped first.
+    /// On success the batch is cleared and the number of bytes written is returned. On
+    /// failure the messages are kept so the send can be retried, except that a batch
+    /// written in chunks, such as under `max_write_size` or `max_batch_messages`, drops
+    /// the messages written before the failing chunk so a retry doesn't send them again.
+    /// That is only done when every message was sent as its own line; if the client
+    /// collapsed or skipped some, the written ones can't be told apart and all are kept.
+    pub fn send(
+        &mut self,
+        client: &mut (impl MetricSink + ?Sized),
+    ) -> Result<usize, GraphiteError> {
+        self.drop_expired();
+        match client.send_batch_message(&self.messages) {
+            Ok(written) => {
+                self.clear();
+                Ok(written)
+            }
+            Err(err) => {
+                if let GraphiteError::BatchIncomplete { sent, messages, .. } = err
+                    && messages == self.messages.len()
+                {
+                    self.drop_sent(sent);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Removes the first `sent` messages, which have been written.
+    fn drop_sent(&mut self, sent: usize) {
+        if sent == 0 {
+            return;
+        }
+        self.size_bytes -= self
+            .messages
+            .drain(..sent)
+            .map(|msg| msg.encoded_len())
+            .sum::<usize>();
+        self.reindex();
+    }
+
+    /// Rebuilds the coalescing index after messages were removed.
+    fn reindex(&mut self) {
+        self.coalesce_index.clear();
+        let Some(coalesce) = &self.coalesce else {
+            return;
+        };
+        for (index, msg) in self.messages.iter().enumerate() {
+            if coalesce(&msg.metric_path).is_some() {
+                let key = (Arc::clone(&msg.metric_path), msg.timestamp);
+                self.coalesce_index.entry(key).or_insert(index);
+            }
+        }
+    }
+
+    /// Removes messages older than `max_metric_age`, handing them to `on_expired`.
+    fn drop_expired(&mut self) {
+        let Some(max_age) = self.max_metric_age else {
+            return;
+        };
+        let cutoff = unix_now().saturating_sub(max_age.as_secs());
+        let (kept, expired): (Vec<_>, Vec<_>) = std::mem::take(&mut self.messages)
+            .into_iter()
+            .partition(|msg| msg.timestamp >= cutoff);
+        self.messages = kept;
+        if expired.is_empty() {
+            return;
+        }
+        self.reindex();
+        self.size_bytes -= expired
+            .iter()
+            .map(GraphiteMessage::encoded_len)
+            .sum::<usize>();
+        self.expired += expired.len() as u64;
+        if let Some(on_expired) = &self.on_expired {
+            on_expired(&expired);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Circuit breaker that lets sends fail fast during prolonged outages.
+
+use std::time::{Duration, Instant};
+
+use crate::hook::Hook;
+
+/// The state of a client's circuit breaker.
+///
+/// See the `circuit_breaker_threshold` builder option on
+/// [`GraphiteClient`](crate::GraphiteClient).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Sends go through normally.
+    Closed,
+
+    /// Too many consecutive sends failed; sends fail immediately without touching the
+    /// network until the cooldown elapses.
+    Open,
+
+    /// The cooldown has elapsed and a single probe send is deciding whether to close the
+    /// breaker again or reopen it.
+    HalfOpen,
+}
+
+/// Signature of the callback fired on breaker state transitions, receiving the old and
+/// new states.
+pub(crate) type TransitionHook = dyn Fn(BreakerState, BreakerState) + Send + Sync;
+
+/// Tracks consecutive send failures and decides whether sends may proceed.
+#[derive(Debug, Clone)]
+pub(crate) struct CircuitBreaker {
+    /// Consecutive failures that open the breaker, or `None` if it is disabled.
+    threshold: Option<u32>,
+
+    /// How long the breaker stays open before allowing a probe.
+    cooldown: Duration,
+
+    /// Current state.
+    state: BreakerState,
+
+    /// Failed sends since the last success.
+    consecutive_failures: u32,
+
+    /// When the breaker last opened.
+    opened_at: Option<Instant>,
+
+    /// Callback fired on every state transition.
+    on_transition: Option<Hook<TransitionHook>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(
+        threshold: Option<u32>,
+        cooldown: Duration,
+        on_transition: Option<Hook<TransitionHook>>,
+    ) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            on_transition,
+        }
+    }
+
+    /// Returns the current state.
+    pub(crate) fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    /// Returns whether a send may proceed, moving to half-open once the cooldown elapses.
+    pub(crate) fn allow(&mut self) -> bool {
+        match self.state {
+            BreakerState::Closed | BreakerState::HalfOpen => true,
+            BreakerState::Open => {
+                if self
+                    .opened_at
+                    .is_some_and(|at| at.elapsed() < self.cooldown)
+                {
+                    return false;
+                }
+                self.transition(BreakerState::HalfOpen);
+                true
+            }
+        }
+    }
+
+    /// Records the outcome of a send that `allow` let through.
+    pub(crate) fn record(&mut self, success: bool) {
+        let Some(threshold) = self.threshold else {
+            return;
+        };
+        if success {
+            self.consecutive_failures = 0;
+            if self.state != BreakerState::Closed {
+                self.transition(BreakerState::Closed);
+            }
+            return;
+        }
+
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.state == BreakerState::HalfOpen || self.consecutive_failures >= threshold {
+            self.opened_at = Some(Instant::now());
+            if self.state != BreakerState::Open {
+                self.transition(BreakerState::Open);
+            }
+        }
+    }
+
+    fn transition(&mut self, to: BreakerState) {
+        let from = std::mem::replace(&mut self.state, to);
+        if let Some(on_transition) = &self.on_transition {
+            on_transition(from, to);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! The stream a client writes to, over TCP, UDP or a Unix domain socket.
+
+use std::{
+    fmt,
+    io::{self, ErrorKind, Write},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream,
+        ToSocketAddrs, UdpSocket,
+    },
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+/// How a client reaches the Graphite server.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(unix)]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use graphyne::{GraphiteClient, Transport};
+///
+/// let client = GraphiteClient::builder()
+///     .transport(Transport::UnixSocket("/run/carbon-relay.sock".into()))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(unix))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transport {
+    /// TCP to the configured `address` and `port`.
+    #[default]
+    Tcp,
+
+    /// UDP datagrams to the configured `address` and `port`, e.g. for carbon's UDP
+    /// listener or a StatsD aggregator; see
+    /// [`GraphiteClient::send_statsd`](crate::GraphiteClient::send_statsd).
+    ///
+    /// Each write is sent as one datagram, so a batch must fit in one: keep
+    /// `max_write_size` below the path MTU to avoid fragmentation. Delivery is never
+    /// confirmed, so a send only fails when the local socket does. `fallbacks` and the
+    /// TCP-specific options are ignored.
+    Udp,
+
+    /// A Unix domain socket at the given path, such as a local carbon-relay's.
+    ///
+    /// Avoids the TCP stack entirely for on-host collection, which lowers latency and
+    /// can't exhaust ephemeral ports. `address`, `port`, `fallbacks` and the TCP-specific
+    /// options are ignored; retries, reconnects and every other option work as for TCP.
+    #[cfg(unix)]
+    UnixSocket(PathBuf),
+
+    /// No connection at all: every write is recorded in memory instead of being sent.
+    ///
+    /// Meant for tests, through [`GraphiteClient::null`] and
+    /// [`GraphiteClient::sent_messages`]. Sends always succeed, and `address`, `port` and
+    /// the socket options are ignored.
+    ///
+    /// [`GraphiteClient::null`]: crate::GraphiteClient::null
+    /// [`GraphiteClient::sent_messages`]: crate::GraphiteClient::sent_messages
+    Null,
+}
+
+/// An address [`GraphiteClient::update_target`] can point a client at.
+///
+/// Implemented for the same types as [`ToSocketAddrs`], such as `"host:port"` strings,
+/// `(host, port)` tuples, socket addresses and slices of them. A target naming a host
+/// keeps that name, so later reconnects resolve it again after `dns_cache_ttl`.
+///
+/// [`GraphiteClient::update_target`]: crate::GraphiteClient::update_target
+pub trait TargetAddr: ToSocketAddrs {
+    /// Returns the hostname and port as given, or `None` for a fixed IP address.
+    #[doc(hidden)]
+    fn host(&self) -> Option<(String, u16)>;
+}
+
+/// Returns `host` and `port` unless `host` is an IP address, possibly in brackets.
+fn named_host(host: &str, port: u16) -> Option<(String, u16)> {
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>()
+        .is_err()
+        .then(|| (host.to_owned(), port))
+}
+
+impl TargetAddr for str {
+    fn host(&self) -> Option<(String, u16)> {
+        let (host, port) = self.rsplit_once(':')?;
+        named_host(host, port.parse().ok()?)
+    }
+}
+
+impl TargetAddr for String {
+    fn host(&self) -> Option<(String, u16)> {
+        self.as_str().host()
+    }
+}
+
+impl TargetAddr for (&str, u16) {
+    fn host(&self) -> Option<(String, u16)> {
+        named_host(self.0, self.1)
+    }
+}
+
+impl TargetAddr for (String, u16) {
+    fn host(&self) -> Option<(String, u16)> {
+        named_host(&self.0, self.1)
+    }
+}
+
+/// Implements [`TargetAddr`] for address types that never name a host.
+macro_rules! fixed_target_addr {
+    ($($ty:ty),*) => {
+        $(
+            impl TargetAddr for $ty {
+                fn host(&self) -> Option<(String, u16)> {
+                    None
+                }
+            }
+        )*
+    };
+}
+
+fixed_target_addr!(
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+    (IpAddr, u16),
+    (Ipv4Addr, u16),
+    (Ipv6Addr, u16),
+    &[SocketAddr]
+);
+
+impl<T: TargetAddr + ?Sized> TargetAddr for &T {
+    fn host(&self) -> Option<(String, u16)> {
+        (**self).host()
+    }
+}
+
+/// An open connection to the Graphite server.
+pub(crate) enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    /// Everything written so far, for [`Transport::Null`].
+    Null(Vec<u8>),
+}
+
+impl Connection {
+    /// Opens a UDP socket sending to `peer`, bound to an ephemeral local port.
+    pub(crate) fn udp(peer: SocketAddr) -> io::Result<Self> {
+        let local = match peer {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        Ok(Connection::Udp(socket))
+    }
+
+    /// Returns the TCP stream, if this is a TCP connection.
+    pub(crate) fn tcp(&self) -> Option<&TcpStream> {
+        match self {
+            Connection::Tcp(stream) => Some(stream),
+            Connection::Udp(_) => None,
+            #[cfg(unix)]
+            Connection::Unix(_) => None,
+            Connection::Null(_) => None,
+        }
+    }
+
+    /// Returns a second handle to the same socket, or a new, empty null connection.
+    pub(crate) fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Connection::Tcp(stream) => Connection::Tcp(stream.try_clone()?),
+            Connection::Udp(socket) => Connection::Udp(socket.try_clone()?),
+            #[cfg(unix)]
+            Connection::Unix(stream) => Connection::Unix(stream.try_clone()?),
+            Connection::Null(_) => Connection::Null(Vec::new()),
+        })
+    }
+
+    /// Returns everything written so far, if this is a null connection.
+    pub(crate) fn recorded(&self) -> Option<&[u8]> {
+        match self {
+            Connection::Null(recorded) => Some(recorded),
+            _ => None,
+        }
+    }
+
+    /// Returns the timeout for blocking writes.
+    pub(crate) fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        match self {
+            Connection::Tcp(stream) => stream.write_timeout(),
+            Connection::Udp(socket) => socket.write_timeout(),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write_timeout(),
+            Connection::Null(_) => Ok(None),
+        }
+    }
+
+    /// Sets the timeout for blocking writes.
+    pub(crate) fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_write_timeout(timeout),
+            Connection::Udp(socket) => socket.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_write_timeout(timeout),
+            Connection::Null(_) => Ok(()),
+        }
+    }
+
+    /// Shuts down the read, write, or both halves of the connection.
+    pub(crate) fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.shutdown(how),
+            // There is no connection to tear down; dropping the socket closes it.
+            Connection::Udp(_) => Ok(()),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.shutdown(how),
+            Connection::Null(_) => Ok(()),
+        }
+    }
+
+    /// Returns whether the server has closed or reset the connection.
+    ///
+    /// Peeks without blocking: a clean EOF or a socket error means the connection is dead,
+    /// while no pending data means it is still open as far as the kernel knows. The
+    /// connection is left in non-blocking mode if `nonblocking` is set.
+    pub(crate) fn peer_closed(&self, nonblocking: bool) -> bool {
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let closed = match self.peek_byte() {
+            Ok(0) => true,
+            Ok(_) => false,
+            Err(err) => err.kind() != ErrorKind::WouldBlock,
+        };
+        let _ = self.set_nonblocking(nonblocking);
+        closed
+    }
+
+    /// Moves the connection into or out of non-blocking mode.
+    pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            Connection::Udp(socket) => socket.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_nonblocking(nonblocking),
+            Connection::Null(_) => Ok(()),
+        }
+    }
+
+    /// Peeks at the next received byte without consuming it.
+    fn peek_byte(&self) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.peek(&mut [0; 1]),
+            // Nothing is expected back, and there is no connection for a peer to close.
+            Connection::Udp(_) => Err(ErrorKind::WouldBlock.into()),
+            // `UnixStream::peek` isn't stable yet, so go through socket2 where it's available.
+            #[cfg(target_os = "linux")]
+            Connection::Unix(stream) => {
+                socket2::SockRef::from(stream).peek(&mut [std::mem::MaybeUninit::uninit(); 1])
+            }
+            // Elsewhere a Unix socket can't be peeked, so it is always reported as open.
+            #[cfg(all(unix, not(target_os = "linux")))]
+            Connection::Unix(_) => Err(ErrorKind::WouldBlock.into()),
+            // Nothing is ever received, and the connection can't be closed by a peer.
+            Connection::Null(_) => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            Connection::Udp(socket) => socket.send(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write(buf),
+            Connection::Null(recorded) => recorded.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            Connection::Udp(_) => Ok(()),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.flush(),
+            Connection::Null(_) => Ok(()),
+        }
+    }
+}
+
+impl From<TcpStream> for Connection {
+    fn from(stream: TcpStream) -> Self {
+        Connection::Tcp(stream)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixStream> for Connection {
+    fn from(stream: UnixStream) -> Self {
+        Connection::Unix(stream)
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Connection::Tcp(stream) => stream.fmt(f),
+            Connection::Udp(socket) => socket.fmt(f),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.fmt(f),
+            Connection::Null(recorded) => write!(f, "Null({} bytes)", recorded.len()),
+        }
+    }
+}
+
+/// The connection a client shares with its clones from
+/// [`GraphiteClient::try_clone`](crate::GraphiteClient::try_clone).
+///
+/// Whichever of them replaces its connection publishes the new one here, and the others
+/// move to it instead of reconnecting on their own.
+#[derive(Default)]
+pub(crate) struct SharedConnection {
+    /// How many times a connection has been published, readable without locking.
+    generation: AtomicU64,
+
+    /// The connection published last, unless it couldn't be duplicated.
+    latest: Mutex<Option<Latest>>,
+
+    /// Held by the client that is reconnecting, so that the others wait for it.
+    reconnecting: Mutex<()>,
+}
+
+/// A connection published to a [`SharedConnection`], with what came with it.
+pub(crate) struct Latest {
+    pub(crate) connection: Connection,
+    pub(crate) sock_addr: SocketAddr,
+    pub(crate) expires_at: Option<Instant>,
+}
+
+impl SharedConnection {
+    /// Publishes a handle to `connection` for the other clients, returning its
+    /// generation.
+    pub(crate) fn publish(
+        &self,
+        connection: &Connection,
+        sock_addr: SocketAddr,
+        expires_at: Option<Instant>,
+    ) -> u64 {
+        let mut latest = lock(&self.latest);
+        // If the socket can't be duplicated, the others reconnect on their own.
+        *latest = connection.try_clone().ok().map(|connection| Latest {
+            connection,
+            sock_addr,
+            expires_at,
+        });
+        self.generation.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    /// Returns a handle to the connection published last, and its generation, if there
+    /// is a newer one than `generation`.
+    pub(crate) fn newer_than(&self, generation: u64) -> Option<(u64, Latest)> {
+        if self.generation.load(Ordering::Acquire) == generation {
+            return None;
+        }
+        let latest = lock(&self.latest);
+        let current = self.generation.load(Ordering::Acquire);
+        let latest = latest.as_ref()?;
+        let connection = latest.connection.try_clone().ok()?;
+        Some((
+            current,
+            Latest {
+                connection,
+                sock_addr: latest.sock_addr,
+                expires_at: latest.expires_at,
+            },
+        ))
+    }
+
+    /// Waits until no other client is reconnecting, and holds the others off until the
+    /// guard is dropped.
+    pub(crate) fn reconnecting(&self) -> MutexGuard<'_, ()> {
+        lock(&self.reconnecting)
+    }
+}
+
+impl fmt::Debug for SharedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedConnection")
+            .field("generation", &self.generation.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Locks `mutex`, ignoring poisoning: a panic elsewhere leaves nothing half-updated.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
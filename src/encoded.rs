@@ -0,0 +1,104 @@
+//! Messages kept alongside their encoded line for repeated sends.
+
+use crate::{GraphiteMessage, unix_now};
+
+/// A [`GraphiteMessage`] along with its line in plaintext protocol format, for sending
+/// the same series over and over without formatting it each time.
+///
+/// Built with [`GraphiteMessage::encoded`] or [`new`](Self::new). The line is formatted
+/// once, when the message is wrapped. A client sends it as is with
+/// [`GraphiteClient::send_encoded`], unless it has options that rewrite lines, such as
+/// `hostname_prefix`, `fixed_timestamp`, `value_formatter` or a custom `separator`, in
+/// which case the message is encoded as usual. [`touch`](Self::touch) restamps the
+/// message by rewriting only the end of the line. Suits messages re-sent on a schedule,
+/// such as heartbeats.
+///
+/// [`GraphiteClient::send_encoded`]: crate::GraphiteClient::send_encoded
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, GraphiteMessage};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .build()?;
+///
+/// let mut heartbeat = GraphiteMessage::new("app.heartbeat", "1").encoded();
+/// loop {
+///     client.send_encoded(&heartbeat)?;
+///     std::thread::sleep(Duration::from_secs(10));
+///     heartbeat.touch();
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncodedMessage {
+    /// The message the line was formatted from.
+    message: GraphiteMessage,
+
+    /// The message in plaintext protocol format, newline included.
+    line: String,
+}
+
+impl EncodedMessage {
+    /// Formats `message` and keeps the line along with it.
+    pub fn new(message: GraphiteMessage) -> Self {
+        let line = message.to_string();
+        Self { message, line }
+    }
+
+    /// Returns the wrapped message.
+    pub fn message(&self) -> &GraphiteMessage {
+        &self.message
+    }
+
+    /// Returns the encoded line, newline included.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the encoded line, newline included, as bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::{EncodedMessage, GraphiteMessage};
+    ///
+    /// let msg: GraphiteMessage = "app.heartbeat 1 1609459200".parse().unwrap();
+    /// assert_eq!(EncodedMessage::new(msg).as_bytes(), b"app.heartbeat 1 1609459200\n");
+    /// ```
+    pub fn as_bytes(&self) -> &[u8] {
+        self.line.as_bytes()
+    }
+
+    /// Sets the timestamp, in seconds since the Unix epoch, rewriting it in the line.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.message.set_timestamp(timestamp);
+        // The timestamp is the last field, so only the end of the line changes.
+        let start = self.line.rfind(' ').map_or(0, |space| space + 1);
+        self.line.truncate(start);
+        self.line.push_str(itoa::Buffer::new().format(timestamp));
+        self.line.push('\n');
+    }
+
+    /// Restamps the message with the current time, like
+    /// [`set_timestamp`](Self::set_timestamp).
+    pub fn touch(&mut self) {
+        self.set_timestamp(unix_now());
+    }
+
+    /// Returns the wrapped message, dropping the line.
+    pub fn into_message(self) -> GraphiteMessage {
+        self.message
+    }
+}
+
+impl From<GraphiteMessage> for EncodedMessage {
+    fn from(message: GraphiteMessage) -> Self {
+        Self::new(message)
+    }
+}
@@ -0,0 +1,700 @@
+//! Errors returned by the clients.
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind},
+    net::AddrParseError,
+    sync::Arc,
+    time::Duration,
+};
+
+/// Error type for Graphite client operations.
+///
+/// Each variant carries the details of one failure, so callers can react differently to,
+/// say, a mistyped address and an unreachable server. Use [`kind`](Self::kind) for a
+/// coarser category that is stable across variants. The [`Display`](fmt::Display) output
+/// is a human-readable message starting with `Graphite Error:`.
+///
+/// I/O errors are reference counted so that the error stays cheap to clone.
+///
+/// # Examples
+///
+/// ```rust
+/// use graphyne::{GraphiteClient, GraphiteError};
+/// use std::time::Duration;
+///
+/// fn try_connect() -> Result<GraphiteClient, GraphiteError> {
+///     GraphiteClient::builder()
+///         .address("127.0.0.1")
+///         .port(2003)
+///         .timeout(Duration::from_millis(100))
+///         .build()
+/// }
+///
+/// match try_connect() {
+///     Ok(client) => println!("Connected successfully"),
+///     Err(GraphiteError::ConnectRefused { .. }) => eprintln!("Carbon is not listening yet"),
+///     Err(e) => eprintln!("Connection failed: {}", e),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum GraphiteError {
+    /// An address couldn't be parsed as an IP address.
+    AddrParse(AddrParseError),
+
+    /// A connection attempt timed out.
+    ConnectTimeout {
+        /// The error the connection attempt failed with.
+        source: Arc<Error>,
+    },
+
+    /// The server refused a connection attempt, typically because nothing is listening.
+    ConnectRefused {
+        /// The error the connection attempt failed with.
+        source: Arc<Error>,
+    },
+
+    /// Any other I/O failure, such as a failed connection attempt or hostname lookup.
+    Io {
+        /// Kind of the I/O error.
+        kind: ErrorKind,
+        /// The I/O error.
+        source: Arc<Error>,
+    },
+
+    /// Every attempt failed with a retryable error and no retries remain.
+    RetriesExhausted {
+        /// Attempts made, counting failed reconnects.
+        attempts: usize,
+        /// The error the last attempt failed with.
+        last: Arc<Error>,
+        /// What the failed send was doing, if this came from a send.
+        context: Option<Box<SendContext>>,
+    },
+
+    /// An operation gave up without making a single attempt.
+    NoAttempts,
+
+    /// A write failed with an error that reconnecting can't fix, so it was not retried.
+    NonRetryable {
+        /// The error the write failed with.
+        source: Arc<Error>,
+        /// What the failed send was doing.
+        context: Option<Box<SendContext>>,
+    },
+
+    /// A send did not complete before its deadline.
+    Timeout {
+        /// The error the last attempt failed with, if one was made.
+        last: Option<Arc<Error>>,
+        /// What the failed send was doing.
+        context: Option<Box<SendContext>>,
+    },
+
+    /// A write that isn't retried within the call failed with a retryable error, and the
+    /// client will reconnect on the next send. Raised under
+    /// [`ReconnectPolicy::Lazy`](crate::ReconnectPolicy::Lazy) and by the non-blocking
+    /// `try_send_message` and `poll_flush`.
+    WriteFailed {
+        /// The error the write failed with.
+        source: Arc<Error>,
+        /// What the failed send was doing.
+        context: Option<Box<SendContext>>,
+    },
+
+    /// Under [`ReconnectPolicy::Lazy`](crate::ReconnectPolicy::Lazy), reconnecting a
+    /// connection broken by an earlier send failed.
+    ReconnectFailed {
+        /// The error the connection attempt failed with.
+        source: Arc<Error>,
+    },
+
+    /// The circuit breaker is open, so the send was not attempted.
+    CircuitOpen {
+        /// What the failed send was doing.
+        context: Option<Box<SendContext>>,
+    },
+
+    /// A multi-destination client skipped a destination it considers unhealthy.
+    DestinationUnhealthy,
+
+    /// A batch was only partly written, and the rest was not retried.
+    BatchIncomplete {
+        /// Category of the failure that stopped the batch.
+        kind: GraphiteErrorKind,
+        /// Messages known to have been written.
+        sent: usize,
+        /// Messages in the batch.
+        messages: usize,
+    },
+
+    /// A message failed validation and was not sent.
+    InvalidMessage {
+        /// Metric path of the message, or as much of it as could be parsed.
+        path: String,
+        /// The rule the message broke.
+        rule: ValidationRule,
+        /// Human-readable description of the failure.
+        detail: String,
+    },
+
+    /// Messages in a batch failed validation, so none of the batch was sent.
+    ///
+    /// Every message is checked, so all the failures are reported rather than only the
+    /// first.
+    InvalidBatch {
+        /// Index into the batch and error of each message that failed validation, in
+        /// batch order.
+        failures: Vec<(usize, GraphiteError)>,
+        /// Messages in the batch.
+        messages: usize,
+    },
+
+    /// The system clock reads before the Unix epoch, or a message had timestamp 0.
+    ClockSkew {
+        /// What was wrong with the time.
+        reason: String,
+    },
+
+    /// A builder option was given an invalid value.
+    InvalidConfig {
+        /// What was wrong with the configuration.
+        reason: String,
+    },
+}
+
+impl GraphiteError {
+    /// Returns the category of this error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteErrorKind, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .max_line_bytes(64)
+    ///     .build()?;
+    ///
+    /// let msg = GraphiteMessage::new(&"a".repeat(100), "1");
+    /// let err = client.send_message(&msg).unwrap_err();
+    /// assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn kind(&self) -> GraphiteErrorKind {
+        match self {
+            GraphiteError::AddrParse(_)
+            | GraphiteError::Io {
+                kind: ErrorKind::InvalidInput,
+                ..
+            } => GraphiteErrorKind::InvalidAddress,
+            GraphiteError::ConnectTimeout { .. }
+            | GraphiteError::ConnectRefused { .. }
+            | GraphiteError::Io { .. }
+            | GraphiteError::ReconnectFailed { .. } => GraphiteErrorKind::Connect,
+            GraphiteError::WriteFailed { .. } => GraphiteErrorKind::Other,
+            GraphiteError::RetriesExhausted { .. } => GraphiteErrorKind::RetriesExhausted,
+            GraphiteError::NoAttempts => GraphiteErrorKind::NoAttempts,
+            GraphiteError::NonRetryable { .. } => GraphiteErrorKind::NonRetryable,
+            GraphiteError::Timeout { .. } => GraphiteErrorKind::Timeout,
+            GraphiteError::CircuitOpen { .. } | GraphiteError::DestinationUnhealthy => {
+                GraphiteErrorKind::CircuitOpen
+            }
+            GraphiteError::BatchIncomplete { kind, .. } => *kind,
+            GraphiteError::InvalidMessage {
+                rule: ValidationRule::LineTooLong { .. },
+                ..
+            } => GraphiteErrorKind::MessageTooLong,
+            GraphiteError::InvalidMessage { .. } => GraphiteErrorKind::InvalidMessage,
+            GraphiteError::InvalidBatch { failures, .. } => failures
+                .first()
+                .map_or(GraphiteErrorKind::InvalidMessage, |(_, err)| err.kind()),
+            GraphiteError::ClockSkew { .. } => GraphiteErrorKind::ClockSkew,
+            GraphiteError::InvalidConfig { .. } => GraphiteErrorKind::InvalidConfig,
+        }
+    }
+
+    /// Returns the human-readable error message, the same as the `Display` output.
+    ///
+    /// This replaces the former public `msg` field.
+    pub fn msg(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns what the failed send was doing, if this error was returned by a send that
+    /// got as far as writing.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// if let Err(err) = client.send_message(&GraphiteMessage::new("app.requests", "42")) {
+    ///     if let Some(context) = err.context() {
+    ///         eprintln!("gave up on {} after {} attempts", context.subject, context.attempts);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn context(&self) -> Option<&SendContext> {
+        match self {
+            GraphiteError::RetriesExhausted { context, .. }
+            | GraphiteError::NonRetryable { context, .. }
+            | GraphiteError::Timeout { context, .. }
+            | GraphiteError::WriteFailed { context, .. }
+            | GraphiteError::CircuitOpen { context } => context.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of the underlying I/O error, if this error was caused by one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    /// use std::io::ErrorKind;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// if let Err(err) = client.send_message(&GraphiteMessage::new("app.requests", "42")) {
+    ///     match err.io_kind() {
+    ///         Some(ErrorKind::BrokenPipe) => eprintln!("relay went away"),
+    ///         Some(ErrorKind::TimedOut) => eprintln!("relay is slow"),
+    ///         _ => eprintln!("{err}"),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn io_kind(&self) -> Option<ErrorKind> {
+        self.io_error().map(Error::kind)
+    }
+
+    /// Returns whether a connection attempt or send ran out of time.
+    ///
+    /// This covers [`ConnectTimeout`](GraphiteError::ConnectTimeout), a send that hit its
+    /// deadline, and any failure caused by an I/O error of kind
+    /// [`TimedOut`](ErrorKind::TimedOut) or, as socket write timeouts report on Unix,
+    /// [`WouldBlock`](ErrorKind::WouldBlock).
+    ///
+    /// The `is_*` predicates look at both the variant and the underlying I/O error, so
+    /// more than one can hold: a send that timed out after its last reconnect was refused
+    /// is both a timeout and a refused connection.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, GraphiteError::ConnectTimeout { .. })
+            || self.kind() == GraphiteErrorKind::Timeout
+            || matches!(
+                self.io_kind(),
+                Some(ErrorKind::TimedOut | ErrorKind::WouldBlock)
+            )
+    }
+
+    /// Returns whether the server refused a connection, typically because nothing is
+    /// listening on the port.
+    ///
+    /// This holds for a failed build or reconnect as well as for a send whose retries
+    /// ended on a refused reconnect.
+    pub fn is_connection_refused(&self) -> bool {
+        self.io_kind() == Some(ErrorKind::ConnectionRefused)
+    }
+
+    /// Returns whether a write failed because the server had closed the connection.
+    pub fn is_broken_pipe(&self) -> bool {
+        self.io_kind() == Some(ErrorKind::BrokenPipe)
+    }
+
+    /// Returns whether an address or message couldn't be parsed.
+    pub fn is_parse_error(&self) -> bool {
+        matches!(
+            self,
+            GraphiteError::AddrParse(_)
+                | GraphiteError::InvalidMessage {
+                    rule: ValidationRule::Syntax,
+                    ..
+                }
+        )
+    }
+
+    /// Returns whether retrying the same operation later could plausibly succeed.
+    ///
+    /// This agrees with the client's own retry decisions: connection failures and write
+    /// errors the client retried until it ran out of attempts or time are retryable, and
+    /// so is a send skipped by an open circuit breaker or an unhealthy destination. A
+    /// [`NonRetryable`](GraphiteError::NonRetryable) write error is not, and neither is
+    /// anything caused by the input itself, such as an unparseable address, an invalid
+    /// or oversized message, or an invalid configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let mut requeue = Vec::new();
+    /// let msg = GraphiteMessage::new("app.requests", "42");
+    /// if let Err(err) = client.send_message(&msg) {
+    ///     if err.is_retryable() {
+    ///         requeue.push(msg);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            GraphiteError::AddrParse(_)
+            | GraphiteError::NonRetryable { .. }
+            | GraphiteError::InvalidMessage { .. }
+            | GraphiteError::InvalidBatch { .. }
+            | GraphiteError::ClockSkew { .. }
+            | GraphiteError::InvalidConfig { .. } => false,
+            // Raised by connection attempts and address lookups, which the client retries
+            // whatever the error, except for an address that can't be resolved as given.
+            GraphiteError::Io { kind, .. } => *kind != ErrorKind::InvalidInput,
+            GraphiteError::BatchIncomplete { kind, .. } => !matches!(
+                kind,
+                GraphiteErrorKind::NonRetryable
+                    | GraphiteErrorKind::MessageTooLong
+                    | GraphiteErrorKind::InvalidMessage
+                    | GraphiteErrorKind::ClockSkew
+                    | GraphiteErrorKind::InvalidConfig
+            ),
+            GraphiteError::ConnectTimeout { .. }
+            | GraphiteError::ConnectRefused { .. }
+            | GraphiteError::RetriesExhausted { .. }
+            | GraphiteError::NoAttempts
+            | GraphiteError::Timeout { .. }
+            | GraphiteError::WriteFailed { .. }
+            | GraphiteError::ReconnectFailed { .. }
+            | GraphiteError::CircuitOpen { .. }
+            | GraphiteError::DestinationUnhealthy => true,
+        }
+    }
+
+    /// Returns the underlying I/O error, if this error was caused by one.
+    fn io_error(&self) -> Option<&Error> {
+        match self {
+            GraphiteError::ConnectTimeout { source }
+            | GraphiteError::ConnectRefused { source }
+            | GraphiteError::Io { source, .. }
+            | GraphiteError::NonRetryable { source, .. }
+            | GraphiteError::WriteFailed { source, .. }
+            | GraphiteError::ReconnectFailed { source }
+            | GraphiteError::RetriesExhausted { last: source, .. }
+            | GraphiteError::Timeout {
+                last: Some(source), ..
+            } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Attaches `context` to a send error; other errors are returned unchanged.
+    pub(crate) fn with_context(mut self, send_context: SendContext) -> Self {
+        if let GraphiteError::RetriesExhausted { context, .. }
+        | GraphiteError::NonRetryable { context, .. }
+        | GraphiteError::Timeout { context, .. }
+        | GraphiteError::WriteFailed { context, .. }
+        | GraphiteError::CircuitOpen { context } = &mut self
+        {
+            *context = Some(Box::new(send_context));
+        }
+        self
+    }
+
+    /// Builds the error for a failed connection attempt.
+    pub(crate) fn connect(err: Error) -> Self {
+        match err.kind() {
+            ErrorKind::TimedOut => GraphiteError::ConnectTimeout {
+                source: Arc::new(err),
+            },
+            ErrorKind::ConnectionRefused => GraphiteError::ConnectRefused {
+                source: Arc::new(err),
+            },
+            _ => err.into(),
+        }
+    }
+
+    /// Builds the error returned when a send runs out of time.
+    pub(crate) fn send_timeout(last_err: Option<Error>) -> Self {
+        GraphiteError::Timeout {
+            last: last_err.map(Arc::new),
+            context: None,
+        }
+    }
+
+    /// Builds the error returned once a retry loop gives up after `attempts` attempts.
+    ///
+    /// The error is only ever built from a failure actually observed during the call,
+    /// never from an unrelated leftover OS error.
+    pub(crate) fn retries_exhausted(last_err: Option<Error>, attempts: usize) -> Self {
+        match last_err {
+            Some(err) => GraphiteError::RetriesExhausted {
+                attempts,
+                last: Arc::new(err),
+                context: None,
+            },
+            None => GraphiteError::NoAttempts,
+        }
+    }
+
+    /// Builds an [`InvalidMessage`](GraphiteError::InvalidMessage) error.
+    pub(crate) fn invalid_message(
+        path: impl Into<String>,
+        rule: ValidationRule,
+        detail: impl Into<String>,
+    ) -> Self {
+        GraphiteError::InvalidMessage {
+            path: path.into(),
+            rule,
+            detail: detail.into(),
+        }
+    }
+
+    /// Writes the message shown by `Display`, without the `Graphite Error:` prefix or
+    /// the send context.
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphiteError::AddrParse(err) => write!(f, "invalid address: {err}"),
+            GraphiteError::ConnectTimeout { source }
+            | GraphiteError::ConnectRefused { source }
+            | GraphiteError::Io { source, .. } => write!(f, "{source}"),
+            GraphiteError::RetriesExhausted { last, .. } => write!(f, "retries exhausted: {last}"),
+            GraphiteError::NoAttempts => f.write_str("no attempts were made"),
+            GraphiteError::NonRetryable { source, .. } => {
+                write!(f, "non-retryable error: {source}")
+            }
+            GraphiteError::Timeout {
+                last: Some(last), ..
+            } => write!(f, "send deadline exceeded: {last}"),
+            GraphiteError::Timeout { last: None, .. } => {
+                f.write_str("send deadline exceeded before any write was attempted")
+            }
+            GraphiteError::WriteFailed { source, .. } => {
+                write!(f, "write failed, reconnecting on next send: {source}")
+            }
+            GraphiteError::ReconnectFailed { source } => write!(f, "reconnect failed: {source}"),
+            GraphiteError::CircuitOpen { .. } => f.write_str("circuit breaker is open"),
+            GraphiteError::DestinationUnhealthy => f.write_str("destination skipped as unhealthy"),
+            GraphiteError::BatchIncomplete { sent, messages, .. } => {
+                write!(f, "batch write failed after {sent} of {messages} messages")
+            }
+            GraphiteError::InvalidMessage { path, detail, .. } if path.is_empty() => {
+                write!(f, "invalid message: {detail}")
+            }
+            GraphiteError::InvalidMessage { path, detail, .. } => {
+                write!(f, "invalid message for '{path}': {detail}")
+            }
+            GraphiteError::InvalidBatch { failures, messages } => {
+                write!(
+                    f,
+                    "{} of {messages} messages in the batch failed validation",
+                    failures.len()
+                )?;
+                if let Some((index, first)) = failures.first() {
+                    write!(f, "; message {index}: ")?;
+                    first.fmt_message(f)?;
+                }
+                Ok(())
+            }
+            GraphiteError::ClockSkew { reason } | GraphiteError::InvalidConfig { reason } => {
+                f.write_str(reason)
+            }
+        }
+    }
+
+    /// Builds an [`InvalidConfig`](GraphiteError::InvalidConfig) error.
+    pub(crate) fn invalid_config(reason: impl Into<String>) -> Self {
+        GraphiteError::InvalidConfig {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for GraphiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Graphite Error: ")?;
+        self.fmt_message(f)?;
+        match self.context() {
+            Some(context) => write!(f, " ({context})"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for GraphiteError {
+    /// Returns the I/O or address parsing error that caused this error, if any.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphiteError::AddrParse(err) => Some(err),
+            _ => self
+                .io_error()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+impl From<AddrParseError> for GraphiteError {
+    /// Converts address parsing errors into `GraphiteError`.
+    ///
+    /// This is called when the provided address string cannot be parsed as a valid IP address.
+    fn from(err: AddrParseError) -> Self {
+        GraphiteError::AddrParse(err)
+    }
+}
+
+impl From<Error> for GraphiteError {
+    /// Converts I/O errors into [`GraphiteError::Io`].
+    fn from(err: Error) -> Self {
+        GraphiteError::Io {
+            kind: err.kind(),
+            source: Arc::new(err),
+        }
+    }
+}
+
+/// The rule a message broke, carried by [`GraphiteError::InvalidMessage`].
+///
+/// # Examples
+///
+/// ```rust
+/// use graphyne::{GraphiteClient, GraphiteError, GraphiteMessage, Transport, ValidationRule};
+///
+/// let mut client = GraphiteClient::builder()
+///     .transport(Transport::Null)
+///     .max_line_bytes(32)
+///     .build()
+///     .unwrap();
+///
+/// let msg = GraphiteMessage::new(&"a".repeat(40), "1");
+/// match client.send_message(&msg) {
+///     Err(GraphiteError::InvalidMessage {
+///         rule: ValidationRule::LineTooLong { len, limit },
+///         ..
+///     }) => assert!(len > limit),
+///     other => panic!("unexpected {other:?}"),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationRule {
+    /// The encoded line exceeded the client's `max_line_bytes`.
+    LineTooLong {
+        /// Length in bytes of the encoded line, including the newline.
+        len: usize,
+        /// The client's `max_line_bytes`.
+        limit: usize,
+    },
+
+    /// The value parsed as `NaN` or an infinity, under
+    /// [`NonFinitePolicy::Error`](crate::NonFinitePolicy::Error).
+    NonFiniteValue,
+
+    /// `batch_dedupe` had to combine a value that isn't a number.
+    NonNumericValue,
+
+    /// A line of the plaintext protocol couldn't be parsed.
+    Syntax,
+}
+
+/// What a failed send was doing, attached to its [`GraphiteError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SendContext {
+    /// What was being sent: `metric '<path>'` or `batch of <n> messages`.
+    pub subject: String,
+
+    /// Attempts made, counting failed reconnects.
+    pub attempts: usize,
+
+    /// Connections replaced during the send.
+    pub reconnects: usize,
+
+    /// Time spent on the send before giving up.
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for SendContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sending {} after {} attempts and {} reconnects in {:?}",
+            self.subject, self.attempts, self.reconnects, self.elapsed
+        )
+    }
+}
+
+/// The category of a [`GraphiteError`].
+///
+/// This lets callers react to broad classes of failure without matching on every
+/// variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GraphiteErrorKind {
+    /// A message exceeded the client's `max_line_bytes` limit and was not sent.
+    MessageTooLong,
+
+    /// Every attempt failed with a retryable error and no retries remain.
+    RetriesExhausted,
+
+    /// A write failed with an error that reconnecting can't fix, so it was not retried.
+    NonRetryable,
+
+    /// An operation did not complete in time: a send ran past the client's
+    /// `send_deadline` or the deadline given to `send_message_by`, a
+    /// [`SyncGraphiteClient`](crate::SyncGraphiteClient) waited out its `lock_timeout`,
+    /// or a sender's final flush outlasted the timeout given to `shutdown_timeout`.
+    Timeout,
+
+    /// A message was rejected before anything was sent, such as one that couldn't be
+    /// parsed or whose value wasn't finite or couldn't be combined as a number. See
+    /// [`ValidationRule`]; an overlong line is reported as
+    /// [`MessageTooLong`](Self::MessageTooLong) instead.
+    InvalidMessage,
+
+    /// An operation gave up without making a single attempt.
+    NoAttempts,
+
+    /// The circuit breaker is open, so the send was not attempted.
+    CircuitOpen,
+
+    /// The system clock reads before the Unix epoch, or a message had timestamp 0.
+    ClockSkew,
+
+    /// A builder option was given an invalid value.
+    InvalidConfig,
+
+    /// An address couldn't be parsed, was rejected as given before any lookup, such as
+    /// one missing its port, or couldn't be resolved when building the client, such as a
+    /// mistyped hostname. Retrying won't help.
+    InvalidAddress,
+
+    /// A connection couldn't be established, or a hostname that resolved before couldn't
+    /// be resolved again. The server may be down or unreachable, so retrying later can
+    /// succeed.
+    Connect,
+
+    /// Any other failure, such as a write that failed under
+    /// [`ReconnectPolicy::Lazy`](crate::ReconnectPolicy::Lazy) or in a non-blocking send.
+    Other,
+}
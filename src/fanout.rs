@@ -0,0 +1,169 @@
+//! Mirroring every send to several Graphite destinations.
+
+use std::time::Duration;
+
+use bon::bon;
+
+use crate::{
+    DestinationStats, GraphiteClient, GraphiteError, GraphiteMessage,
+    health::{DEFAULT_REPROBE_INTERVAL, DestinationHealth, HealthPolicy},
+};
+
+/// How the results of a fanned-out send combine into the result returned to the caller.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FanoutPolicy {
+    /// Fail unless every destination accepted the payload.
+    #[default]
+    AllMustSucceed,
+
+    /// Succeed if at least one destination accepted the payload.
+    AnySuccess,
+
+    /// Fail only if the primary destination failed.
+    ///
+    /// Mirror failures are recorded in [`FanoutGraphiteClient::destination_stats`] but
+    /// never returned, so a broken mirror can't disturb the primary pipeline.
+    PrimaryOnlyErrors,
+}
+
+/// A client that writes every payload to a primary destination and any number of mirrors.
+///
+/// Each destination is a full [`GraphiteClient`] with its own connection, retry,
+/// reconnect and circuit breaker state, so a failure on one destination never forces a
+/// reconnect on another. Destinations are written one after another, the primary first,
+/// so a destination that is down still costs its retries and backoff on every send; set
+/// an `unhealthy_threshold`, or configure mirrors with a `circuit_breaker_threshold` or
+/// [`ReconnectPolicy::Lazy`](crate::ReconnectPolicy::Lazy), to keep that bounded.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{FanoutGraphiteClient, FanoutPolicy, GraphiteClient, GraphiteMessage};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let old_cluster = GraphiteClient::builder().address("10.0.0.5").port(2003).build()?;
+/// let new_cluster = GraphiteClient::builder()
+///     .address("10.1.0.5")
+///     .port(2003)
+///     .circuit_breaker_threshold(3)
+///     .build()?;
+///
+/// let mut client = FanoutGraphiteClient::builder()
+///     .primary(old_cluster)
+///     .mirrors(vec![new_cluster])
+///     .policy(FanoutPolicy::PrimaryOnlyErrors)
+///     .build();
+///
+/// client.send_message(&GraphiteMessage::new("app.requests", "42"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FanoutGraphiteClient {
+    /// Destination whose result is always reported.
+    primary: GraphiteClient,
+
+    /// Destinations that receive a copy of every payload.
+    mirrors: Vec<GraphiteClient>,
+
+    /// Health of the primary followed by each mirror.
+    health: Vec<DestinationHealth>,
+
+    /// When a destination is skipped as unhealthy.
+    health_policy: HealthPolicy,
+
+    /// How per-destination results combine.
+    policy: FanoutPolicy,
+}
+
+#[bon]
+impl FanoutGraphiteClient {
+    /// Creates a fan-out client from already connected clients.
+    #[builder]
+    pub fn new(
+        /// Destination whose result is always reported and whose byte count is returned.
+        primary: GraphiteClient,
+        /// Destinations that receive a copy of every payload.
+        #[builder(default)]
+        mirrors: Vec<GraphiteClient>,
+        /// How per-destination results combine (default: [`FanoutPolicy::AllMustSucceed`]).
+        #[builder(default)]
+        policy: FanoutPolicy,
+        /// Consecutive failed sends after which a destination is skipped, failing with
+        /// [`GraphiteErrorKind::CircuitOpen`](crate::GraphiteErrorKind::CircuitOpen)
+        /// without being attempted, until a probe succeeds. When unset, every destination
+        /// is attempted on every send.
+        unhealthy_threshold: Option<u32>,
+        /// How long an unhealthy destination is skipped before a single send probes it
+        /// again (default: 30 seconds).
+        #[builder(default = DEFAULT_REPROBE_INTERVAL)]
+        reprobe_interval: Duration,
+    ) -> Self {
+        Self {
+            health: vec![DestinationHealth::default(); mirrors.len() + 1],
+            primary,
+            mirrors,
+            policy,
+            health_policy: HealthPolicy {
+                threshold: unhealthy_threshold.map(|threshold| threshold.max(1)),
+                reprobe_interval,
+            },
+        }
+    }
+
+    /// Sends a metric message to every destination.
+    ///
+    /// Returns the primary destination's result as for
+    /// [`GraphiteClient::send_message`], or an error according to the [`FanoutPolicy`].
+    pub fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        self.fan_out(|client| client.send_message(msg))
+    }
+
+    /// Sends a batch of metric messages to every destination.
+    ///
+    /// Returns the primary destination's result as for
+    /// [`GraphiteClient::send_batch_message`], or an error according to the
+    /// [`FanoutPolicy`].
+    pub fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        self.fan_out(|client| client.send_batch_message(msgs))
+    }
+
+    /// Returns counters and health for each destination, the primary first and then the
+    /// mirrors in the order they were given.
+    pub fn destination_stats(&self) -> Vec<DestinationStats> {
+        std::iter::once(&self.primary)
+            .chain(&self.mirrors)
+            .zip(&self.health)
+            .map(|(client, health)| health.stats(self.health_policy, client))
+            .collect()
+    }
+
+    /// Runs `send` against every destination, recording the outcomes and combining them
+    /// according to the policy.
+    fn fan_out(
+        &mut self,
+        mut send: impl FnMut(&mut GraphiteClient) -> Result<usize, GraphiteError>,
+    ) -> Result<usize, GraphiteError> {
+        let primary = self.health[0].send(self.health_policy, &mut self.primary, &mut send);
+        let mut mirror_ok = None;
+        let mut mirror_err = None;
+        for (client, health) in self.mirrors.iter_mut().zip(&mut self.health[1..]) {
+            match health.send(self.health_policy, client, &mut send) {
+                Ok(bytes) => {
+                    mirror_ok.get_or_insert(bytes);
+                }
+                Err(err) => {
+                    mirror_err.get_or_insert(err);
+                }
+            }
+        }
+        match self.policy {
+            FanoutPolicy::AllMustSucceed => {
+                let bytes = primary?;
+                mirror_err.map_or(Ok(bytes), Err)
+            }
+            FanoutPolicy::AnySuccess => primary.or_else(|err| mirror_ok.ok_or(err)),
+            FanoutPolicy::PrimaryOnlyErrors => primary,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Per-destination counters and health for clients that write to several destinations.
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{GraphiteClient, GraphiteError, GraphiteErrorKind};
+
+/// Default time an unhealthy destination is skipped before it is probed again.
+pub(crate) const DEFAULT_REPROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Counters and health for a single destination of a [`FanoutGraphiteClient`] or
+/// [`ShardedGraphiteClient`].
+///
+/// [`FanoutGraphiteClient`]: crate::FanoutGraphiteClient
+/// [`ShardedGraphiteClient`]: crate::ShardedGraphiteClient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DestinationStats {
+    /// Sends the destination accepted.
+    pub sent: u64,
+
+    /// Sends that failed on the destination.
+    pub failed: u64,
+
+    /// Kind of the most recent failure, if any.
+    pub last_error: Option<GraphiteErrorKind>,
+
+    /// Sends not attempted because the destination was unhealthy.
+    pub skipped: u64,
+
+    /// Failed sends since the last success.
+    pub consecutive_failures: u64,
+
+    /// Connections the destination's client has replaced, as for
+    /// [`GraphiteClient::connections_closed`].
+    pub reconnects: u64,
+
+    /// When the destination last accepted a send, if ever.
+    pub last_success: Option<SystemTime>,
+
+    /// Whether the destination is currently considered healthy.
+    ///
+    /// With an `unhealthy_threshold`, a destination is unhealthy once that many sends in
+    /// a row have failed, and is skipped until a probe succeeds. Without one, it is
+    /// unhealthy whenever its most recent send failed.
+    pub healthy: bool,
+}
+
+impl Default for DestinationStats {
+    fn default() -> Self {
+        Self {
+            sent: 0,
+            failed: 0,
+            last_error: None,
+            skipped: 0,
+            consecutive_failures: 0,
+            reconnects: 0,
+            last_success: None,
+            healthy: true,
+        }
+    }
+}
+
+/// When a destination is skipped as unhealthy and how often it is probed again.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HealthPolicy {
+    /// Consecutive failures after which the destination is skipped, or `None` to never
+    /// skip it.
+    pub(crate) threshold: Option<u32>,
+
+    /// How long an unhealthy destination is skipped before a single probe send.
+    pub(crate) reprobe_interval: Duration,
+}
+
+/// Tracks the outcomes of the sends to one destination.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DestinationHealth {
+    /// Counters reported by [`DestinationStats`]; `reconnects` and `healthy` are filled
+    /// in when a snapshot is taken.
+    stats: DestinationStats,
+
+    /// When an unhealthy destination may next be probed.
+    probe_at: Option<Instant>,
+}
+
+impl DestinationHealth {
+    /// Runs `send` against `client` unless the destination is unhealthy and not yet due
+    /// for a probe, recording the outcome.
+    ///
+    /// A skipped send fails with [`GraphiteError::DestinationUnhealthy`] without touching
+    /// the network.
+    pub(crate) fn send(
+        &mut self,
+        policy: HealthPolicy,
+        client: &mut GraphiteClient,
+        send: impl FnOnce(&mut GraphiteClient) -> Result<usize, GraphiteError>,
+    ) -> Result<usize, GraphiteError> {
+        if self.probe_at.is_some_and(|at| Instant::now() < at) {
+            self.stats.skipped += 1;
+            return Err(GraphiteError::DestinationUnhealthy);
+        }
+        let res = send(client);
+        match &res {
+            Ok(_) => {
+                self.stats.sent += 1;
+                self.stats.consecutive_failures = 0;
+                self.stats.last_success = Some(SystemTime::now());
+                self.probe_at = None;
+            }
+            Err(err) => {
+                self.stats.failed += 1;
+                self.stats.consecutive_failures += 1;
+                self.stats.last_error = Some(err.kind());
+                if policy.threshold.is_some_and(|threshold| {
+                    self.stats.consecutive_failures >= u64::from(threshold)
+                }) {
+                    self.probe_at = Some(Instant::now() + policy.reprobe_interval);
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns a snapshot of the counters, with `client`'s reconnects.
+    pub(crate) fn stats(&self, policy: HealthPolicy, client: &GraphiteClient) -> DestinationStats {
+        let healthy = match policy.threshold {
+            Some(threshold) => self.stats.consecutive_failures < u64::from(threshold),
+            None => self.stats.consecutive_failures == 0,
+        };
+        DestinationStats {
+            reconnects: client.connections_closed(),
+            healthy,
+            ..self.stats
+        }
+    }
+}
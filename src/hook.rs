@@ -0,0 +1,36 @@
+//! Storage for user-supplied callbacks.
+
+use std::{fmt, ops::Deref, sync::Arc};
+
+/// A user-supplied callback.
+///
+/// Closures don't implement `Debug`, so this wrapper lets the types holding them keep
+/// deriving it. The callback is reference counted so the holder stays cheap to clone.
+pub(crate) struct Hook<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Hook<F> {
+    /// Wraps an already shared callback.
+    pub(crate) fn new(f: Arc<F>) -> Self {
+        Self(f)
+    }
+}
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> Deref for Hook<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
@@ -0,0 +1,98 @@
+//! Sharing of repeated metric paths between messages.
+
+use std::{collections::HashSet, sync::Arc};
+
+use crate::GraphiteMessage;
+
+/// A set of metric paths, handing out one shared `Arc<str>` per distinct path.
+///
+/// Collectors that emit the same paths over and over can intern each path once and build
+/// messages with [`GraphiteMessage::interned`], so that every message for a path shares
+/// one allocation instead of copying the path.
+///
+/// Interned paths are kept until [`clear`](Self::clear) is called, so intern only paths
+/// from a bounded set.
+///
+/// [`GraphiteMessage::interned`]: crate::GraphiteMessage::interned
+///
+/// # Examples
+///
+/// ```rust
+/// use graphyne::{GraphiteMessage, PathInterner};
+/// use std::sync::Arc;
+///
+/// let mut interner = PathInterner::new();
+/// let path = interner.intern("app.requests");
+/// assert!(Arc::ptr_eq(&path, &interner.intern("app.requests")));
+///
+/// let msg = GraphiteMessage::interned(path, "42");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PathInterner {
+    /// Every path interned so far.
+    paths: HashSet<Arc<str>>,
+}
+
+impl PathInterner {
+    /// Creates an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared copy of `path`, adding it if it hasn't been interned yet.
+    pub fn intern(&mut self, path: &str) -> Arc<str> {
+        if let Some(interned) = self.paths.get(path) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(path);
+        self.paths.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Returns the number of distinct paths interned.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns whether no paths have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Forgets every interned path. Messages already built keep their paths.
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+/// A metric path registered with [`GraphiteClient::register_path`], for sending values
+/// with [`GraphiteClient::send_value`].
+///
+/// The handle keeps the path validated and encoded once, with the registering client's
+/// metric prefix and separator, so each send only formats the value and timestamp.
+/// Handles are cheap to clone and can be shared between threads. Use a handle only with
+/// the client that registered it, or one with the same prefix and separator.
+///
+/// [`GraphiteClient::register_path`]: crate::GraphiteClient::register_path
+/// [`GraphiteClient::send_value`]: crate::GraphiteClient::send_value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MetricHandle {
+    /// The metric path as registered.
+    pub(crate) path: Arc<str>,
+
+    /// The start of every line for the path: the prefixed path and a separator.
+    pub(crate) head: Arc<str>,
+}
+
+impl MetricHandle {
+    /// Returns the metric path as registered, without the client's prefix.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Builds a message for the path stamped with the current time, such as to send in a
+    /// batch. The message shares the handle's copy of the path.
+    pub fn message(&self, value: f64) -> GraphiteMessage {
+        GraphiteMessage::interned(Arc::clone(&self.path), &value.to_string())
+    }
+}
@@ -33,6 +33,15 @@
 //! - **Zero-copy Writes**: Efficient metric transmission
 //! - **Timestamp Generation**: Automatic Unix timestamp creation
 //!
+//! ## Cargo Features
+//!
+//! - `stub`: Builds every client as though `dry_run` were set, so sends encode and
+//!   validate as usual and return `Ok` without opening a connection. Clients built with
+//!   [`Transport::Null`] still record their messages. This lets a library depend on
+//!   Graphyne unconditionally and leave the choice of real sending to the binary. The crate
+//!   compiles for targets without sockets, such as `wasm32-unknown-unknown`, with or
+//!   without it, but only a stub client can send there.
+//!
 //! ## Protocol
 //!
 //! Graphyne uses the Graphite plaintext protocol over TCP. Each metric is formatted as:
@@ -45,15 +54,60 @@
 //! servers.web01.cpu.usage 45.2 1609459200\n
 //! ```
 
+mod batch;
+mod breaker;
+mod connection;
+mod encoded;
+mod error;
+mod fanout;
+mod health;
+mod hook;
+mod intern;
+mod md5;
+mod recent;
+mod rng;
+mod sender;
+mod sharded;
+mod sink;
+mod summary;
+mod sync_client;
+
+pub use batch::{Batch, BatchResult, DedupePolicy, MessageOutcome};
+pub use breaker::BreakerState;
+pub use connection::{TargetAddr, Transport};
+pub use encoded::EncodedMessage;
+pub use error::{GraphiteError, GraphiteErrorKind, SendContext, ValidationRule};
+pub use fanout::{FanoutGraphiteClient, FanoutPolicy};
+pub use health::DestinationStats;
+pub use intern::{MetricHandle, PathInterner};
+pub use sender::{DetachedSender, GraphiteSender, Producer, ShardedProducer, ShardedSender};
+pub use sharded::{HashRing, ReplicaOutcome, ShardedBatchResult, ShardedGraphiteClient};
+pub use sink::MetricSink;
+pub use summary::Summary;
+pub use sync_client::SyncGraphiteClient;
+
 use bon::bon;
+use breaker::{CircuitBreaker, TransitionHook};
+use connection::{Connection, SharedConnection};
+use hook::Hook;
+use recent::RecentSends;
+use rng::Rng;
 use std::{
+    borrow::Borrow,
+    collections::HashMap,
     fmt,
-    io::{Error, Write},
-    net::{AddrParseError, IpAddr, SocketAddr, TcpStream},
+    hash::Hash,
+    io::{Error, ErrorKind, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    ops::Range,
     str::FromStr,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
 /// Default number of retry attempts for connection and send operations.
 ///
 /// If a connection or send fails, the client will retry up to this many times
@@ -65,9 +119,63 @@ const DEFAULT_RETRIES: u8 = 3;
 /// This timeout applies to both initial connections and reconnection attempts.
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Address used by [`GraphiteClient::from_env`] when `GRAPHITE_ADDRESS` is unset.
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+/// Default port of the Carbon plaintext receiver.
+const DEFAULT_PORT: u16 = 2003;
+
 /// Default time to live for TCP packets
 const DEFAULT_TCP_TTL: Duration = Duration::from_secs(240);
 
+/// Default delay before the first retry of a failed connection attempt.
+const DEFAULT_CONNECT_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+/// Default upper bound on the delay between retries.
+const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Largest fraction accepted for [`Jitter::Proportional`].
+const MAX_JITTER_FRACTION: f64 = 10.0;
+
+/// Default maximum length of a single protocol line, matching the line length limit of
+/// Carbon's plaintext receiver.
+const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Capacity the reused encoding buffer is shrunk back to after a send that grew it
+/// beyond, such as an outlier batch.
+const MAX_RETAINED_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Default largest single write `send_batch` makes.
+const DEFAULT_MAX_WRITE_SIZE: usize = 1024 * 1024;
+
+/// I/O error kinds for which a failed write is retried on a fresh connection by default.
+///
+/// Only transient connection-level failures qualify. Anything else (e.g. `InvalidInput`)
+/// would fail identically on a fresh socket, so retrying would just waste attempts.
+pub const DEFAULT_RETRYABLE_KINDS: [ErrorKind; 5] = [
+    ErrorKind::BrokenPipe,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::TimedOut,
+    ErrorKind::WouldBlock,
+];
+
+/// Default time a tripped circuit breaker stays open before allowing a probe send.
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Default string written between the fields of a line.
+const DEFAULT_SEPARATOR: &str = " ";
+
+/// Default time a hostname resolution is reused before reconnects resolve it again.
+const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Fraction of `max_connection_lifetime` that may be randomly shaved off each connection's
+/// lifetime, so that clients started together don't all re-dial at the same moment.
+const CONNECTION_LIFETIME_SPLAY: f64 = 0.1;
+
+/// Longest a client being dropped blocks writing out the lines it is still holding.
+const DROP_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// A client for sending metrics to a Graphite Carbon daemon.
 ///
 /// `GraphiteClient` maintains a persistent TCP connection to a Graphite server and provides
@@ -78,12 +186,21 @@ const DEFAULT_TCP_TTL: Duration = Duration::from_secs(240);
 ///
 /// The client maintains a single TCP connection which is automatically reestablished if it
 /// fails. When `send_message` encounters a connection error, it will attempt to reconnect
-/// up to `retries` times before failing.
+/// and retry up to `send_retries` times before failing.
+///
+/// # Blocking
+///
+/// Retries multiply: each of a send's `send_retries` attempts may trigger a reconnect,
+/// which itself makes up to `connect_retries + 1` connection attempts of up to `timeout`
+/// each, with backoff delays in between. With the defaults a send against a down server can block for well
+/// over a minute. To bound this, set `connect_deadline` (total time spent connecting per
+/// call), `send_deadline` (total time per send), or `total_timeout` (both at once).
 ///
 /// # Thread Safety
 ///
 /// `GraphiteClient` is **not** thread-safe due to the mutable reference required by `send_message`.
-/// For concurrent access, wrap it in a `Mutex` or use multiple client instances.
+/// To share one client between threads, wrap it in a [`SyncGraphiteClient`], or use
+/// multiple client instances.
 ///
 /// # Examples
 ///
@@ -114,7 +231,7 @@ const DEFAULT_TCP_TTL: Duration = Duration::from_secs(240);
 /// let mut client = GraphiteClient::builder()
 ///     .address("127.0.0.1")
 ///     .port(2003)
-///     .retries(5)                       // Try up to 5 times
+///     .send_retries(5)                  // Retry failed sends up to 5 times
 ///     .timeout(Duration::from_secs(10)) // 10 second timeout
 ///     .build()?;
 /// # Ok(())
@@ -122,28 +239,80 @@ const DEFAULT_TCP_TTL: Duration = Duration::from_secs(240);
 /// ```
 #[derive(Debug)]
 pub struct GraphiteClient {
-    /// The active TCP connection to the Graphite server.
+    /// The active connection to the Graphite server.
     ///
     /// This connection is used for all metric transmission and may be replaced
     /// if reconnection is necessary.
-    connection: TcpStream,
+    connection: Connection,
+
+    /// Shared with every client from [`try_clone`](Self::try_clone), so that whichever
+    /// of them reconnects first hands its new connection to the others.
+    shared: Arc<SharedConnection>,
+
+    /// Generation of the shared connection this client last moved to.
+    generation: u64,
 
-    /// Socket address used for reconnection attempts.
+    /// How the server is reached.
+    transport: Transport,
+
+    /// Socket address of the current connection.
     ///
-    /// Stored to enable reconnection without needing to re-parse the address.
+    /// Also the last known good address, tried when re-resolving the hostname fails.
     sock_addr: SocketAddr,
 
-    /// Original address string (currently unused but reserved for future DNS support).
-    _address: String,
+    /// Address of the active endpoint, an IP address or a hostname.
+    address: String,
 
-    /// Original port number (currently unused but reserved for future use).
-    _port: u16,
+    /// Port of the active endpoint.
+    port: u16,
 
-    /// Number of times to retry failed operations.
-    ///
-    /// This applies to both connection attempts and send operations. A value of 3
-    /// means up to 4 total attempts (1 initial + 3 retries).
-    retries: u8,
+    /// Every endpoint as an address and port, in priority order, the primary first.
+    endpoints: Vec<(String, u16)>,
+
+    /// Index into `endpoints` of the endpoint currently connected to.
+    active_endpoint: usize,
+
+    /// How often a client on a fallback endpoint tries to return to a higher-priority one.
+    failback_interval: Option<Duration>,
+
+    /// When the next fail-back attempt is due, if on a fallback endpoint.
+    failback_at: Option<Instant>,
+
+    /// Callback fired when the client switches to a different endpoint.
+    on_endpoint_change: Option<Hook<EndpointHook>>,
+
+    /// Whether failed sends are reported in-band with a meta-metric.
+    self_report: bool,
+
+    /// Whether sends are encoded and validated but not written.
+    dry_run: bool,
+
+    /// Callback receiving the data a dry run would have written.
+    on_dry_run: Option<Hook<DryRunHook>>,
+
+    /// What `try_send_message` does with a line the socket can't take right away.
+    on_would_block: WouldBlockPolicy,
+
+    /// Lines `try_send_message` queued because the socket buffer was full, or that
+    /// `write_buffer` holds back. Written by `poll_flush`, `flush` or ahead of the next
+    /// blocking send.
+    pending: Vec<u8>,
+
+    /// Bytes at the start of `pending` already written. May stop in the middle of a line.
+    pending_written: usize,
+
+    /// Whether the connection is currently in non-blocking mode.
+    nonblocking: bool,
+
+    /// Sends that exhausted their retries since the meta-metric was last written.
+    unreported_failures: u64,
+
+    /// Number of times a failed send is retried. A value of 3 means up to 4 total
+    /// attempts (1 initial + 3 retries).
+    send_retries: u8,
+
+    /// Number of times a failed connection attempt is retried within one reconnect.
+    connect_retries: u8,
 
     /// Timeout duration for connection attempts.
     ///
@@ -153,8 +322,149 @@ pub struct GraphiteClient {
 
     /// Time to live for tcp packets.
     tcp_ttl: Duration,
+
+    /// Overall cap on the wall-clock time spent connecting.
+    ///
+    /// When set, this bounds the total time spent across all connection attempts made by
+    /// a single `build()`, `reconnect()`, or `send_message()` call, regardless of how many
+    /// retries remain.
+    connect_deadline: Option<Duration>,
+
+    /// Timestamp stamped onto every outgoing message, overriding the message's own.
+    fixed_timestamp: Option<u64>,
+
+    /// Prefix prepended to every outgoing metric path, including the trailing dot.
+    prefix: Option<String>,
+
+    /// Maximum age of a connection before it is transparently re-dialed.
+    max_connection_lifetime: Option<Duration>,
+
+    /// When the current connection should be replaced, derived from
+    /// `max_connection_lifetime` plus a random splay.
+    connection_expires_at: Option<Instant>,
+
+    /// Source of randomness for lifetime splay.
+    rng: Rng,
+
+    /// Delay before the first send retry; doubled for each subsequent retry.
+    retry_initial_delay: Duration,
+
+    /// Upper bound on the delay between send retries.
+    retry_max_delay: Duration,
+
+    /// Delay before the first connect retry; doubled for each subsequent retry.
+    connect_retry_initial_delay: Duration,
+
+    /// Upper bound on the delay between connect retries.
+    connect_retry_max_delay: Duration,
+
+    /// Maximum length in bytes of a single protocol line, including the newline.
+    max_line_bytes: usize,
+
+    /// Randomization applied to each delay between retries.
+    retry_jitter: Jitter,
+
+    /// Upper bound of the random delay before each reconnect attempt.
+    reconnect_jitter: Option<Duration>,
+
+    /// I/O error kinds for which a failed write triggers a reconnect and retry.
+    retryable_kinds: Vec<ErrorKind>,
+
+    /// Predicate deciding which failed writes are retried, in place of `retryable_kinds`.
+    retry_if: Option<Hook<RetryPredicate>>,
+
+    /// Overall cap on the wall-clock time a single send may take, including reconnects.
+    send_deadline: Option<Duration>,
+
+    /// Number of connections that have been replaced and shut down.
+    connections_closed: u64,
+
+    /// Transformation applied to every message value at send time.
+    value_formatter: Option<Hook<ValueFormatter>>,
+
+    /// Fails sends fast after repeated failures.
+    breaker: CircuitBreaker,
+
+    /// When a failed write triggers a reconnect.
+    reconnect_policy: ReconnectPolicy,
+
+    /// Set when the connection is known to be broken; the next send reconnects first.
+    connection_dirty: bool,
+
+    /// Whether to check for a connection closed by the server before each send.
+    verify_connection_before_send: bool,
+
+    /// Floor that earlier message timestamps are raised to at send time.
+    min_timestamp: Option<u64>,
+
+    /// Whether a failed write is retried with the same data.
+    delivery: Delivery,
+
+    /// What to do with a value that parses as a non-finite number.
+    on_non_finite: NonFinitePolicy,
+
+    /// How long transmitted data may remain unacknowledged before the connection is
+    /// dropped. Only applied on Linux.
+    user_timeout: Option<Duration>,
+
+    /// Requested size of each connection's kernel send buffer (`SO_SNDBUF`).
+    send_buffer_size: Option<usize>,
+
+    /// Delimiter written between the metric path, value, and timestamp.
+    separator: String,
+
+    /// How duplicate points within a batch are collapsed, if at all.
+    batch_dedupe: Option<DedupePolicy>,
+
+    /// Largest write `send_batch` makes, splitting larger batches into chunks.
+    max_write_size: usize,
+
+    /// Most lines `send_batch` writes at once, splitting larger batches into chunks.
+    max_batch_messages: Option<usize>,
+
+    /// Bytes of single-message sends held in `pending` before they are written, if
+    /// buffering is enabled.
+    write_buffer: Option<usize>,
+
+    /// Lines sent within `dedupe_window`, if it is set.
+    recent_sends: Option<RecentSends>,
+
+    /// Addresses the hostname last resolved to.
+    resolved_addrs: Vec<SocketAddr>,
+
+    /// When the hostname was last resolved, or `None` if it should be resolved on the
+    /// next reconnect. Always `None` when `address` is an IP address.
+    resolved_at: Option<Instant>,
+
+    /// How long a resolution is reused before reconnects resolve the hostname again.
+    dns_cache_ttl: Duration,
+
+    /// Order in which resolved addresses are tried on each connect.
+    address_selection: AddressSelection,
+
+    /// Buffer messages are encoded into, kept between sends to avoid allocating. Empty
+    /// outside of a send.
+    buffer: String,
+
+    /// Last counter value `send_delta` sent a delta for, per path.
+    counter_baselines: HashMap<String, f64>,
 }
 
+/// Signature of the callback installed with the `value_formatter` builder option.
+type ValueFormatter = dyn Fn(&str) -> String + Send + Sync;
+
+/// Signature of the callback installed with the `on_endpoint_change` builder option.
+type EndpointHook = dyn Fn(&str, u16) + Send + Sync;
+
+/// Signature of the callback installed with the `on_connect` builder option.
+type ConnectHook = dyn Fn(&SocketAddr) + Send + Sync;
+
+/// Signature of the predicate installed with the `retry_if` builder option.
+type RetryPredicate = dyn Fn(&Error) -> bool + Send + Sync;
+
+/// Signature of the callback installed with the `on_dry_run` builder option.
+type DryRunHook = dyn Fn(&[u8]) + Send + Sync;
+
 #[bon]
 impl GraphiteClient {
     /// Creates a new `GraphiteClient` using the builder pattern.
@@ -164,16 +474,64 @@ impl GraphiteClient {
     ///
     /// # Arguments
     ///
-    /// * `address` - IP address of the Graphite server (IPv4 or IPv6). **Note**: DNS hostnames
-    ///   are not currently supported.
-    /// * `port` - TCP port number where the Carbon daemon is listening (typically 2003)
-    /// * `retries` - Number of retry attempts for failed operations (default: 3)
+    /// * `address` - IP address (IPv4 or IPv6) or hostname of the Graphite server; required for TCP
+    /// * `port` - TCP port number where the Carbon daemon is listening (default: 2003)
+    /// * `transport` - How the server is reached (default: [`Transport::Tcp`])
+    /// * `retries` - Deprecated; sets both `send_retries` and `connect_retries`
+    /// * `send_retries` - Number of retries for a failed send (default: 3)
+    /// * `connect_retries` - Number of retries for a failed connection attempt (default: 3)
     /// * `timeout` - Maximum duration to wait for connection attempts (default: 5 seconds)
+    /// * `tcp_ttl` - Time to live for TCP packets (default: 240 seconds)
+    /// * `connect_deadline` - Optional cap on the total time spent across all connection attempts
+    /// * `fixed_timestamp` - Optional timestamp stamped onto every outgoing message
+    /// * `hostname_prefix` - Optional base prefix, followed by the local hostname, for every metric path
+    /// * `max_connection_lifetime` - Optional maximum age of a connection before it is re-dialed
+    /// * `retry_initial_delay` - Delay before the first send retry, doubling each time (default: none)
+    /// * `retry_max_delay` - Upper bound on the delay between send retries (default: 2 seconds)
+    /// * `connect_retry_initial_delay` - Delay before the first connect retry (default: `retry_initial_delay`, or 100ms)
+    /// * `connect_retry_max_delay` - Upper bound on the delay between connect retries (default: `retry_max_delay`)
+    /// * `initial_connect_retries` - Number of retries for the connection made by `build` (default: 0)
+    /// * `initial_connect_backoff` - Delay before the first retry of the connection made by `build` (default: `connect_retry_initial_delay`)
+    /// * `max_line_bytes` - Maximum length of a single protocol line (default: 16 KiB)
+    /// * `retry_jitter` - Randomization applied to each delay between retries (default: none)
+    /// * `reconnect_jitter` - Optional upper bound of a random delay before each reconnect attempt
+    /// * `rng_seed` - Optional seed making jitter and splay deterministic
+    /// * `retryable_kinds` - I/O error kinds worth retrying (default: [`DEFAULT_RETRYABLE_KINDS`])
+    /// * `retry_if` - Optional predicate deciding which write errors are retried, overriding `retryable_kinds`
+    /// * `send_deadline` - Optional cap on the total time a single send may take
+    /// * `total_timeout` - Optional cap used for both `connect_deadline` and `send_deadline`
+    /// * `value_formatter` - Optional transformation applied to every value at send time
+    /// * `circuit_breaker_threshold` - Optional number of consecutive failed sends that opens the circuit breaker
+    /// * `circuit_breaker_cooldown` - Time the circuit breaker stays open before a probe (default: 30 seconds)
+    /// * `on_breaker_transition` - Optional callback fired on circuit breaker state transitions
+    /// * `reconnect_policy` - When a failed write triggers a reconnect (default: [`ReconnectPolicy::Eager`])
+    /// * `verify_connection_before_send` - Whether to detect a closed connection before each send (default: false)
+    /// * `min_timestamp` - Optional floor that earlier message timestamps are raised to
+    /// * `delivery` - Whether a failed write is retried with the same data (default: [`Delivery::AtLeastOnce`])
+    /// * `on_non_finite` - What to do with a `NaN` or infinite value (default: [`NonFinitePolicy::Error`])
+    /// * `user_timeout` - Optional `TCP_USER_TIMEOUT` for every connection (Linux only)
+    /// * `send_buffer_size` - Optional `SO_SNDBUF` size requested for every connection
+    /// * `separator` - Delimiter between the fields of each line (default: a single space)
+    /// * `batch_dedupe` - Optional policy collapsing duplicate points within a batch
+    /// * `max_write_size` - Largest write batch sends make, in whole lines (default: 1 MiB)
+    /// * `max_batch_messages` - Optional largest number of lines batch sends write at once
+    /// * `write_buffer` - Optional capacity in bytes of the buffer single-message sends are collected in before writing
+    /// * `dedupe_window` - Optional window within which `send_message` skips an exact resend
+    /// * `dns_cache_ttl` - How long reconnects reuse a hostname resolution (default: 30 seconds)
+    /// * `address_selection` - Order in which resolved addresses are tried (default: [`AddressSelection::Shuffle`])
+    /// * `fallbacks` - Endpoints tried in order when the primary one doesn't accept a connection
+    /// * `failback_interval` - Optional interval between attempts to return to a higher-priority endpoint
+    /// * `on_endpoint_change` - Optional callback fired when the client switches endpoint
+    /// * `on_connect` - Optional callback fired once the initial connection is established
+    /// * `self_report` - Whether to report exhausted sends with a meta-metric (default: false)
+    /// * `dry_run` - Whether sends are encoded and validated without being written (default: false)
+    /// * `on_dry_run` - Optional callback receiving the data a dry run would have written
+    /// * `on_would_block` - What `try_send_message` does with a line the socket can't take yet (default: [`WouldBlockPolicy::Queue`])
     ///
     /// # Returns
     ///
     /// Returns `Ok(GraphiteClient)` if the connection succeeds, or `Err(GraphiteError)` if:
-    /// - The address cannot be parsed as an IP address
+    /// - The address is neither an IP address nor a resolvable hostname
     /// - The connection times out
     /// - The connection is refused
     ///
@@ -203,7 +561,7 @@ impl GraphiteClient {
     /// let client = GraphiteClient::builder()
     ///     .address("192.168.1.100")
     ///     .port(2003)
-    ///     .retries(10)
+    ///     .send_retries(10)
     ///     .timeout(Duration::from_millis(500))
     ///     .build()?;
     /// # Ok(())
@@ -211,18 +569,34 @@ impl GraphiteClient {
     /// ```
     #[builder]
     pub fn new(
-        /// IP address of the Graphite server (IPv4 or IPv6).
+        /// IP address (IPv4 or IPv6) or hostname of the Graphite server.
         ///
-        /// **Note**: DNS hostnames are not currently supported.
-        address: impl Into<String>,
-        /// TCP port number where the Carbon daemon is listening (typically 2003)
+        /// A hostname is resolved when the client is built and each resolved address is
+        /// tried in turn. Reconnects resolve it again once `dns_cache_ttl` has passed.
+        /// Required when `transport` is [`Transport::Tcp`].
+        #[builder(into)]
+        address: Option<String>,
+        /// TCP port number where the Carbon daemon is listening (default: 2003)
+        #[builder(default = DEFAULT_PORT)]
         port: u16,
-        /// Number of times to retry failed operations.
+        /// How the server is reached: TCP to `address` and `port`, or a Unix domain socket.
+        #[builder(default)]
+        transport: Transport,
+        /// Sets both `send_retries` and `connect_retries`, unless they are set explicitly.
+        ///
+        /// **Deprecated**: retrying sends more often also multiplies the worst-case time
+        /// spent reconnecting. Set `send_retries` and `connect_retries` separately instead.
+        retries: Option<u8>,
+
+        /// Number of times a failed send is retried.
         ///
-        /// This applies to both connection attempts and send operations.
-        /// A value of 3 means up to 4 total attempts (1 initial + 3 retries).
-        #[builder(default = DEFAULT_RETRIES)]
-        retries: u8,
+        /// A value of 3 means up to 4 total attempts (1 initial + 3 retries). A failed
+        /// write and a failed reconnect each use up one attempt. Defaults to 3.
+        send_retries: Option<u8>,
+
+        /// Number of times a failed connection attempt is retried within a single
+        /// reconnect, whether explicit or triggered by a failed send. Defaults to 3.
+        connect_retries: Option<u8>,
         /// Timeout duration for connection attempts.
         ///
         /// This timeout is applied to each individual connection attempt during both
@@ -233,103 +607,753 @@ impl GraphiteClient {
         /// Time to live for tcp packets.
         #[builder(default = DEFAULT_TCP_TTL)]
         tcp_ttl: Duration,
+
+        /// Overall cap on the wall-clock time spent connecting.
+        ///
+        /// Unlike `timeout`, which applies to each individual attempt, this bounds the
+        /// total time spent across all attempts. Once it passes, the last error is
+        /// returned even if retries remain. Also bounds the reconnects performed
+        /// internally by `send_message`.
+        connect_deadline: Option<Duration>,
+
+        /// Timestamp applied to every outgoing message at send time.
+        ///
+        /// When set, the timestamp each message was created with is ignored. Useful for
+        /// backfills where all points share the time of the source event.
+        fixed_timestamp: Option<u64>,
+
+        /// Prefixes every metric path with `<hostname_prefix>.<hostname>.`.
+        ///
+        /// The hostname is sanitized with [`hostname_segment`], so a fully-qualified name
+        /// doesn't introduce extra levels into the metric hierarchy.
+        #[builder(into)]
+        hostname_prefix: Option<String>,
+
+        /// Maximum age of a connection before it is re-dialed.
+        ///
+        /// When a send finds the connection older than this, it reconnects before writing,
+        /// which lets a load balancer in front of several relays spread clients across
+        /// backends. Each connection's lifetime is shortened by a random splay of up to 10%
+        /// so that clients started together don't re-dial in lockstep.
+        max_connection_lifetime: Option<Duration>,
+
+        /// Delay before the first retry of a failed send.
+        ///
+        /// The delay doubles for each subsequent retry, up to `retry_max_delay`. Defaults
+        /// to zero, so a failed write is retried as soon as the client has reconnected.
+        /// When set, also used between connect retries unless
+        /// `connect_retry_initial_delay` is set.
+        retry_initial_delay: Option<Duration>,
+
+        /// Upper bound on the delay between send retries.
+        ///
+        /// Also used between connect retries unless `connect_retry_max_delay` is set.
+        #[builder(default = DEFAULT_RETRY_MAX_DELAY)]
+        retry_max_delay: Duration,
+
+        /// Delay before the first retry of a failed connection attempt.
+        ///
+        /// The delay doubles for each subsequent retry, up to `connect_retry_max_delay`.
+        /// Defaults to `retry_initial_delay` if that is set, and to 100ms otherwise.
+        connect_retry_initial_delay: Option<Duration>,
+
+        /// Upper bound on the delay between connect retries.
+        connect_retry_max_delay: Option<Duration>,
+
+        /// Number of times the connection made by `build` is retried before giving up.
+        ///
+        /// Kept apart from `connect_retries` so that startup can wait out a relay that is
+        /// still coming up while reconnects during steady-state sends keep failing fast.
+        /// Bounded by `connect_deadline`. Defaults to 0, a single attempt.
+        #[builder(default)]
+        initial_connect_retries: u8,
+
+        /// Delay before the first retry of the connection made by `build`.
+        ///
+        /// The delay doubles for each subsequent retry, up to `connect_retry_max_delay`.
+        /// Defaults to `connect_retry_initial_delay`.
+        initial_connect_backoff: Option<Duration>,
+
+        /// Maximum length in bytes of a single protocol line, including the newline.
+        ///
+        /// Longer messages are rejected with [`GraphiteErrorKind::MessageTooLong`] before
+        /// anything is written to the socket.
+        #[builder(default = DEFAULT_MAX_LINE_BYTES)]
+        max_line_bytes: usize,
+
+        /// Randomization applied to each delay between retries.
+        ///
+        /// Spreads out reconnects across a fleet of clients after a relay restart. See
+        /// [`Jitter`] for the latency each strategy can add.
+        #[builder(default)]
+        retry_jitter: Jitter,
+
+        /// Upper bound of a random delay slept before each reconnect attempt.
+        ///
+        /// Applies to every reconnect, including the first attempt after a failed write,
+        /// which `retry_jitter` doesn't delay. When a shared relay restarts, this keeps a
+        /// fleet of clients from reconnecting to it at the same instant. The delay is
+        /// drawn uniformly from zero to this bound, never runs past a deadline, and is
+        /// reproducible with `rng_seed`.
+        reconnect_jitter: Option<Duration>,
+
+        /// Seed for the client's random number generator.
+        ///
+        /// Makes jitter and connection lifetime splay deterministic, which is mainly
+        /// useful in tests. When unset the generator is seeded from the system clock.
+        rng_seed: Option<u64>,
+
+        /// I/O error kinds for which a failed write triggers a reconnect and retry.
+        ///
+        /// Writes failing with any other kind are returned immediately as
+        /// [`GraphiteErrorKind::NonRetryable`].
+        #[builder(default = DEFAULT_RETRYABLE_KINDS.to_vec())]
+        retryable_kinds: Vec<ErrorKind>,
+
+        /// Decides whether a failed write triggers a reconnect and retry, given its I/O
+        /// error.
+        ///
+        /// Takes the place of `retryable_kinds` entirely, for network stacks whose errors
+        /// need more than their kind to classify, e.g. by raw OS error code. Writes it
+        /// rejects are returned immediately as [`GraphiteErrorKind::NonRetryable`].
+        #[builder(with = |f: impl Fn(&Error) -> bool + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        retry_if: Option<Hook<RetryPredicate>>,
+
+        /// Overall cap on the wall-clock time a single send may take.
+        ///
+        /// Covers writes, backoff delays, and reconnect attempts. Once it passes, the send
+        /// fails with [`GraphiteErrorKind::Timeout`] even if retries remain.
+        send_deadline: Option<Duration>,
+
+        /// Single cap applied as both `connect_deadline` and `send_deadline`.
+        ///
+        /// Bounds how long any one call can block, however retries and timeouts multiply.
+        /// An explicitly set `connect_deadline` or `send_deadline` takes precedence.
+        total_timeout: Option<Duration>,
+
+        /// Transformation applied to every message value at send time.
+        ///
+        /// Centralizes formatting policy, such as a fixed number of decimals, so that
+        /// messages built anywhere in an application are normalized consistently before
+        /// hitting the wire. When unset, values are sent unchanged.
+        #[builder(with = |f: impl Fn(&str) -> String + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        value_formatter: Option<Hook<ValueFormatter>>,
+
+        /// Number of consecutive failed sends after which the circuit breaker opens.
+        ///
+        /// While open, sends fail immediately with [`GraphiteErrorKind::CircuitOpen`]
+        /// without touching the network. After `circuit_breaker_cooldown` a single probe
+        /// send is let through: if it succeeds the breaker closes, otherwise it reopens.
+        /// When unset, the breaker is disabled.
+        circuit_breaker_threshold: Option<u32>,
+
+        /// How long the circuit breaker stays open before allowing a probe send.
+        #[builder(default = DEFAULT_CIRCUIT_BREAKER_COOLDOWN)]
+        circuit_breaker_cooldown: Duration,
+
+        /// Callback fired on every circuit breaker state transition with the old and new
+        /// states, e.g. to alert when metrics delivery stops.
+        #[builder(with = |f: impl Fn(BreakerState, BreakerState) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_breaker_transition: Option<Hook<TransitionHook>>,
+
+        /// When a failed write triggers a reconnect.
+        ///
+        /// See [`ReconnectPolicy`] for the latency trade-off of each policy.
+        #[builder(default)]
+        reconnect_policy: ReconnectPolicy,
+
+        /// Checks whether the server has closed the connection before each send.
+        ///
+        /// Because writes are buffered by the kernel, the first write after the server
+        /// goes away usually appears to succeed and its data is silently lost. With this
+        /// enabled, a non-blocking peek on the socket detects a received FIN or RST and
+        /// reconnects before writing. Costs two extra syscalls per send.
+        #[builder(default)]
+        verify_connection_before_send: bool,
+
+        /// Floor that earlier message timestamps are raised to at send time.
+        ///
+        /// Guards against devices that boot with an unset clock and stamp messages near
+        /// the Unix epoch. Without a floor, messages with timestamp 0 are rejected with
+        /// [`GraphiteErrorKind::ClockSkew`] rather than sent, as they corrupt Whisper files.
+        min_timestamp: Option<u64>,
+
+        /// Whether a failed write is retried with the same data.
+        ///
+        /// See [`Delivery`] for how each mode trades duplicates against gaps.
+        #[builder(default)]
+        delivery: Delivery,
+
+        /// What to do with a value that parses as a non-finite number, such as `NaN`.
+        ///
+        /// Checked after `value_formatter` is applied, for single sends and batches alike.
+        /// See [`NonFinitePolicy`].
+        #[builder(default)]
+        on_non_finite: NonFinitePolicy,
+
+        /// How long transmitted data may remain unacknowledged before the kernel drops the
+        /// connection (`TCP_USER_TIMEOUT`).
+        ///
+        /// Applied to every connection, including reconnects. Detects a half-open
+        /// connection to a crashed relay much sooner than the default retransmission
+        /// timeout, which can take many minutes, so that sends fail and reconnect promptly.
+        #[cfg(target_os = "linux")]
+        user_timeout: Option<Duration>,
+
+        /// Size in bytes requested for the kernel send buffer of every connection
+        /// (`SO_SNDBUF`), including reconnects.
+        ///
+        /// A larger buffer lets bursty batch sends to a distant relay proceed without
+        /// stalling while earlier data is still unacknowledged, which matters when the
+        /// bandwidth-delay product is high. The OS may clamp or adjust the value; Linux,
+        /// for instance, doubles it and caps it at `net.core.wmem_max`. When unset, the OS
+        /// default applies.
+        send_buffer_size: Option<usize>,
+
+        /// Delimiter written between the metric path, value, and timestamp of every line.
+        ///
+        /// The plaintext protocol uses a single space; only change this for an
+        /// intermediary that expects something else. Must be non-empty and must not
+        /// contain a newline, or `build` fails with [`GraphiteErrorKind::InvalidConfig`].
+        #[builder(into, default = DEFAULT_SEPARATOR.to_string())]
+        separator: String,
+
+        /// Collapses points in a batch that share a metric path and timestamp into one.
+        ///
+        /// Carbon resolves such duplicates unpredictably, so a batch aggregated from
+        /// several sources can otherwise produce nondeterministic values. Timestamps are
+        /// compared after `fixed_timestamp` and `min_timestamp` are applied. When unset,
+        /// batches are sent as given.
+        batch_dedupe: Option<DedupePolicy>,
+
+        /// Largest write in bytes that `send_batch` and `send_batch_detailed` make. A
+        /// larger batch is split into chunks of whole lines, each formatted into a reused
+        /// buffer as it is written.
+        ///
+        /// Keeps each write within reach of the socket send buffer and away from relays
+        /// that mishandle huge writes, and bounds the memory a very large batch needs. A
+        /// line longer than this is written on its own. Every message is still validated
+        /// before the first chunk is written. If a chunk fails after earlier chunks went
+        /// out, `send_batch` returns `Ok` with [`BatchResult::failure`] set instead of an
+        /// error. Batches sent with `send_batch_atomic` are always written whole.
+        #[builder(default = DEFAULT_MAX_WRITE_SIZE)]
+        max_write_size: usize,
+
+        /// Most lines that `send_batch` and `send_batch_detailed` write at once. A batch
+        /// with more is split into chunks of at most this many lines, each written like a
+        /// chunk over `max_write_size`, and whichever limit a chunk reaches first ends it.
+        ///
+        /// For relays that drop lines beyond a count per write or per packet. Batches sent
+        /// with `send_batch_atomic` are always written whole. When unset, only
+        /// `max_write_size` limits a chunk.
+        max_batch_messages: Option<usize>,
+
+        /// Buffers single-message sends, such as `send_message`, until this many bytes
+        /// have built up, and writes them all at once.
+        ///
+        /// Saves a syscall per message when sending many metrics in a loop. A send that
+        /// fills the buffer writes it out first, and [`flush`](Self::flush) writes out
+        /// whatever is left. Dropping the client makes a single attempt to write out the
+        /// rest, blocking for at most a second without retrying or reconnecting, and
+        /// discards whatever that doesn't write; call `flush` first to keep those lines.
+        /// Batches and every other write also write the buffer out first, so lines keep
+        /// their order. A buffered send reports success before anything has been written,
+        /// so failures surface from the send or `flush` that writes the buffer. When unset,
+        /// every send is written immediately.
+        ///
+        /// The value is the buffer's capacity, so this is the option to size the buffer
+        /// by; there is no separate `buffer_capacity`. A larger buffer takes fewer
+        /// syscalls, which matters at high message rates, but holds that much more memory
+        /// and lets points sit longer before they leave, and more of them are at stake if
+        /// the process dies before a flush. Size it to the bytes sent between the flushes
+        /// you make: for example, at 100,000 lines of about 50 bytes a second flushed
+        /// every 100ms, a 512 KiB buffer fills about once per flush.
+        write_buffer: Option<usize>,
+
+        /// How long a sent point is remembered so that `send_message` can skip an exact
+        /// resend of it.
+        ///
+        /// Protects at-least-once pipelines that resend a point after an ambiguous
+        /// failure from counting it twice. Points are compared by their final line, so
+        /// the path, value and timestamp must all match. Only successful sends are
+        /// remembered, and at most the 4096 most recent of them, each as a hash. When
+        /// unset, every send is written.
+        dedupe_window: Option<Duration>,
+
+        /// How long a hostname resolution is reused by reconnects before resolving again.
+        ///
+        /// Keeps a reconnect storm from hammering the resolver while still following DNS
+        /// changes, such as a relay moving to a new IP. If resolving fails, the last address
+        /// that connected is tried instead. Has no effect when `address` is an IP address.
+        /// Defaults to 30 seconds.
+        #[builder(default = DEFAULT_DNS_CACHE_TTL)]
+        dns_cache_ttl: Duration,
+
+        /// Order in which the addresses a hostname resolves to are tried on each connect.
+        ///
+        /// See [`AddressSelection`] for how each strategy spreads clients across a pool.
+        #[builder(default)]
+        address_selection: AddressSelection,
+
+        /// Fallback endpoints, as address and port pairs, in priority order.
+        ///
+        /// Every connect and reconnect walks the primary `address` and `port` and then
+        /// these in order, using the first endpoint that accepts a connection. Send
+        /// semantics are otherwise unchanged.
+        #[builder(default, with = |targets: impl IntoIterator<Item = (impl Into<String>, u16)>| {
+            targets.into_iter().map(|(address, port)| (address.into(), port)).collect()
+        })]
+        fallbacks: Vec<(String, u16)>,
+
+        /// How often a client connected to a fallback endpoint tries to fail back.
+        ///
+        /// Once this much time has passed since switching, the next send first tries each
+        /// higher-priority endpoint once, moving over to the first that accepts a
+        /// connection. Any reconnect also prefers higher-priority endpoints. When unset, a
+        /// working connection to a fallback is kept until it fails.
+        failback_interval: Option<Duration>,
+
+        /// Callback fired with the new endpoint's address and port whenever the client
+        /// switches to a different endpoint, e.g. to alert while running on a fallback.
+        ///
+        /// Not fired for the endpoint the client first connects to; see
+        /// [`active_endpoint`](Self::active_endpoint) for that.
+        #[builder(with = |f: impl Fn(&str, u16) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_endpoint_change: Option<Hook<EndpointHook>>,
+
+        /// Callback fired once with the peer address when `build()` has established the
+        /// initial connection, e.g. to log that the metrics pipeline is online.
+        ///
+        /// Not fired for reconnects. Over a transport other than TCP the address is the
+        /// unspecified `0.0.0.0:0`, like [`connected_addr`](Self::connected_addr).
+        #[builder(with = |f: impl Fn(&SocketAddr) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_connect: Option<Hook<ConnectHook>>,
+
+        /// Reports sends that failed with [`GraphiteErrorKind::RetriesExhausted`] in-band.
+        ///
+        /// The next time a connection is established, the client first writes a single
+        /// `<prefix>graphyne.send_failures <count> <now>` line, where `<prefix>` comes from
+        /// `hostname_prefix` and `<count>` is the number of such failures since the line was
+        /// last written. The line is written once, without retries, and is not itself
+        /// counted as a failure if it can't be written.
+        #[builder(default)]
+        self_report: bool,
+
+        /// Encodes and validates every send without writing it, returning the byte count
+        /// a real send would have.
+        ///
+        /// No connection is opened, whatever the `transport`, so this suits checking
+        /// metric naming and client options in CI. Formatting, the metric prefix,
+        /// `value_formatter` and validation all apply as usual. Unlike with
+        /// [`Transport::Null`], nothing is kept for
+        /// [`sent_messages`](Self::sent_messages); use `on_dry_run` to inspect the data.
+        ///
+        /// Always on with the `stub` feature, except over [`Transport::Null`].
+        #[builder(default)]
+        dry_run: bool,
+
+        /// Callback receiving the encoded lines of each send a `dry_run` client would have
+        /// written. Never called otherwise.
+        #[builder(with = |f: impl Fn(&[u8]) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_dry_run: Option<Hook<DryRunHook>>,
+
+        /// What [`try_send_message`](Self::try_send_message) does with a line when the
+        /// socket buffer is full.
+        ///
+        /// See [`WouldBlockPolicy`]. Blocking sends are unaffected.
+        #[builder(default)]
+        on_would_block: WouldBlockPolicy,
     ) -> Result<Self, GraphiteError> {
-        let address = address.into();
-        let sock_addr = SocketAddr::new(IpAddr::from_str(&address)?, port);
-        let connection = TcpStream::connect_timeout(&sock_addr, timeout)?;
-        connection.set_ttl(tcp_ttl.as_secs() as u32)?;
-        connection.set_nodelay(true)?;
+        let dry_run = dry_run || (cfg!(feature = "stub") && !matches!(transport, Transport::Null));
+        let transport = if dry_run { Transport::Null } else { transport };
+        let connect_deadline = connect_deadline.or(total_timeout);
+        let send_deadline = send_deadline.or(total_timeout);
+        let connect_retry_initial_delay = connect_retry_initial_delay
+            .or(retry_initial_delay)
+            .unwrap_or(DEFAULT_CONNECT_RETRY_INITIAL_DELAY);
+        let retry_initial_delay = retry_initial_delay.unwrap_or_default();
+        let connect_retry_max_delay = connect_retry_max_delay.unwrap_or(retry_max_delay);
+        let endpoints: Vec<_> = std::iter::once((address.clone().unwrap_or_default(), port))
+            .chain(fallbacks)
+            .collect();
+        let mut rng = rng_seed.map_or_else(Rng::from_clock, Rng::new);
+        let deadline = connect_deadline.map(|d| Instant::now() + d);
+        if separator.is_empty() || separator.contains(['\n', '\r']) {
+            return Err(GraphiteError::invalid_config(format!(
+                "invalid separator {separator:?}"
+            )));
+        }
+        if max_batch_messages == Some(0) {
+            return Err(GraphiteError::invalid_config(
+                "max_batch_messages must be at least 1",
+            ));
+        }
+        if let Jitter::Proportional(fraction) = retry_jitter
+            && !(fraction.is_finite() && fraction <= MAX_JITTER_FRACTION)
+        {
+            return Err(GraphiteError::invalid_config(format!(
+                "retry_jitter fraction {fraction} is not finite or exceeds {MAX_JITTER_FRACTION}"
+            )));
+        }
+        #[cfg(not(target_os = "linux"))]
+        let user_timeout = None;
+        if matches!(transport, Transport::Tcp | Transport::Udp) && address.is_none() {
+            return Err(GraphiteError::invalid_config(
+                "an address is required for TCP and UDP",
+            ));
+        }
+        // One initial attempt plus `initial_connect_retries` more.
+        let mut attempt = 0;
+        let (active_endpoint, sock_addr, connection, resolved_addrs, resolved_at) = loop {
+            let established = match &transport {
+                Transport::Tcp => {
+                    let mut established = Err(Error::other("no endpoints to connect to"));
+                    for (index, (address, port)) in endpoints.iter().enumerate() {
+                        established =
+                            resolve_configured(address, *port).and_then(|(addrs, resolved_at)| {
+                                let mut candidates = addrs.clone();
+                                address_selection.order(&mut candidates, None, &mut rng);
+                                let (sock_addr, connection) = Self::connect_any(
+                                    &candidates,
+                                    timeout,
+                                    deadline,
+                                    tcp_ttl,
+                                    user_timeout,
+                                    send_buffer_size,
+                                )?;
+                                Ok((index, sock_addr, connection.into(), addrs, resolved_at))
+                            });
+                        if established.is_ok() {
+                            break;
+                        }
+                    }
+                    established
+                }
+                Transport::Udp => {
+                    let (address, port) = &endpoints[0];
+                    resolve_configured(address, *port).and_then(|(addrs, resolved_at)| {
+                        let mut candidates = addrs.clone();
+                        address_selection.order(&mut candidates, None, &mut rng);
+                        let sock_addr = candidates[0];
+                        let connection = Connection::udp(sock_addr)?;
+                        Ok((0, sock_addr, connection, addrs, resolved_at))
+                    })
+                }
+                #[cfg(unix)]
+                Transport::UnixSocket(path) => UnixStream::connect(path).map(|connection| {
+                    let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+                    (0, unspecified, connection.into(), Vec::new(), None)
+                }),
+                Transport::Null => {
+                    let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+                    Ok((
+                        0,
+                        unspecified,
+                        Connection::Null(Vec::new()),
+                        Vec::new(),
+                        None,
+                    ))
+                }
+            };
+            match established {
+                Ok(established) => break established,
+                Err(err)
+                    if attempt == initial_connect_retries
+                        || err.kind() == ErrorKind::InvalidInput
+                        || deadline.is_some_and(|d| Instant::now() >= d) =>
+                {
+                    return Err(GraphiteError::connect(err));
+                }
+                Err(_) => {}
+            }
+            sleep_backoff(
+                attempt,
+                initial_connect_backoff.unwrap_or(connect_retry_initial_delay),
+                connect_retry_max_delay,
+                retry_jitter,
+                &mut rng,
+                deadline,
+            );
+            attempt += 1;
+        };
+        if let Some(on_connect) = &on_connect {
+            on_connect(&sock_addr);
+        }
+        let (address, port) = endpoints[active_endpoint].clone();
+        let failback_at = failback_interval
+            .filter(|_| active_endpoint > 0)
+            .map(|interval| Instant::now() + interval);
+        let connection_expires_at = Self::expiry(max_connection_lifetime, &mut rng);
 
         Ok(Self {
             connection,
+            shared: Arc::default(),
+            generation: 0,
+            transport,
             sock_addr,
-            _address: address,
-            _port: port,
-            retries,
+            address,
+            port,
+            endpoints,
+            active_endpoint,
+            failback_interval,
+            failback_at,
+            on_endpoint_change,
+            self_report,
+            dry_run,
+            on_dry_run,
+            on_would_block,
+            pending: Vec::new(),
+            pending_written: 0,
+            nonblocking: false,
+            unreported_failures: 0,
+            send_retries: send_retries.or(retries).unwrap_or(DEFAULT_RETRIES),
+            connect_retries: connect_retries.or(retries).unwrap_or(DEFAULT_RETRIES),
             timeout,
             tcp_ttl,
+            connect_deadline,
+            fixed_timestamp,
+            prefix: hostname_prefix.map(|base| format!("{base}.{}.", hostname_segment())),
+            max_connection_lifetime,
+            connection_expires_at,
+            rng,
+            retry_initial_delay,
+            retry_max_delay,
+            connect_retry_initial_delay,
+            connect_retry_max_delay,
+            max_line_bytes,
+            retry_jitter,
+            reconnect_jitter,
+            retryable_kinds,
+            retry_if,
+            send_deadline,
+            connections_closed: 0,
+            value_formatter,
+            breaker: CircuitBreaker::new(
+                circuit_breaker_threshold,
+                circuit_breaker_cooldown,
+                on_breaker_transition,
+            ),
+            reconnect_policy,
+            connection_dirty: false,
+            verify_connection_before_send,
+            min_timestamp,
+            delivery,
+            on_non_finite,
+            user_timeout,
+            send_buffer_size,
+            separator,
+            batch_dedupe,
+            max_write_size,
+            max_batch_messages,
+            write_buffer,
+            recent_sends: dedupe_window.map(RecentSends::new),
+            resolved_addrs,
+            resolved_at,
+            dns_cache_ttl,
+            address_selection,
+            buffer: String::new(),
+            counter_baselines: HashMap::new(),
         })
     }
 
-    /// Attempts to reestablish the TCP connection to the Graphite server.
+    /// Creates a client configured from environment variables.
     ///
-    /// This method tries to create a new connection up to `retries` times, replacing the
-    /// existing connection if successful. It's called automatically by `send_message` when
-    /// a send operation fails, but can also be called manually.
+    /// | Variable              | Builder option | Default     |
+    /// |-----------------------|----------------|-------------|
+    /// | `GRAPHITE_ADDRESS`    | `address`      | `127.0.0.1` |
+    /// | `GRAPHITE_PORT`       | `port`         | `2003`      |
+    /// | `GRAPHITE_RETRIES`    | `retries`      | `3`         |
+    /// | `GRAPHITE_TIMEOUT_MS` | `timeout`      | `5000`      |
     ///
-    /// # Returns
+    /// Unset or empty variables fall back to the default. Every other option keeps its
+    /// builder default; use [`builder`](Self::builder) to set them.
     ///
-    /// Returns `Ok(())` if reconnection succeeds, or `Err(GraphiteError)` if all retry
-    /// attempts are exhausted.
+    /// # Errors
+    ///
+    /// Returns [`GraphiteErrorKind::InvalidConfig`] if a variable is set to a value that
+    /// can't be parsed, and otherwise fails like `build` does.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    /// use graphyne::GraphiteClient;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut client = GraphiteClient::builder()
-    ///     .address("127.0.0.1")
-    ///     .port(2003)
-    ///     .build()?;
-    ///
-    /// // Manually reconnect if needed
-    /// client.reconnect()?;
-    ///
-    /// let msg = GraphiteMessage::new("test.metric", "1");
-    /// client.send_message(&msg)?;
+    /// // GRAPHITE_ADDRESS=graphite.internal GRAPHITE_PORT=2003
+    /// let client = GraphiteClient::from_env()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn reconnect(&mut self) -> Result<(), GraphiteError> {
-        let mut last_err: Error = Error::last_os_error();
-        let mut i = 0;
-        while i < self.retries {
-            let connect = TcpStream::connect_timeout(&self.sock_addr, self.timeout);
-            match connect {
-                Ok(connect) => {
-                    connect.set_ttl(self.tcp_ttl.as_secs() as u32)?;
-                    connect.set_nodelay(true)?;
-                    self.connection = connect;
-                    return Ok(());
-                }
-                Err(err) => last_err = err,
-            }
-            i += 1;
-        }
-        Err(GraphiteError {
-            msg: format!("Graphite Error: {last_err}"),
-        })
+    pub fn from_env() -> Result<Self, GraphiteError> {
+        let address = env_var("GRAPHITE_ADDRESS")?.unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+        let port = env_parse("GRAPHITE_PORT")?.unwrap_or(DEFAULT_PORT);
+        let retries = env_parse("GRAPHITE_RETRIES")?;
+        let timeout = env_parse("GRAPHITE_TIMEOUT_MS")?.map(Duration::from_millis);
+        Self::builder()
+            .address(address)
+            .port(port)
+            .maybe_retries(retries)
+            .maybe_timeout(timeout)
+            .build()
     }
 
-    /// Sends a metric message to the Graphite server.
+    /// Creates a client that records what it sends instead of connecting anywhere.
     ///
-    /// This method writes the formatted metric to the TCP connection. If the write fails
-    /// (e.g., due to a broken connection), it automatically attempts to reconnect and retry
-    /// the send operation up to `retries` times.
+    /// This is shorthand for a client built with [`Transport::Null`] and default options,
+    /// for unit-testing code that emits metrics without running a server. Every send
+    /// succeeds; read back what was sent with [`sent_messages`](Self::sent_messages). Use
+    /// the builder with `transport(Transport::Null)` to record with other options, such as
+    /// a `hostname_prefix`.
     ///
-    /// # Arguments
+    /// # Examples
     ///
-    /// * `msg` - A reference to the `GraphiteMessage` to send
+    /// ```rust
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
     ///
-    /// # Returns
+    /// let mut client = GraphiteClient::null();
+    /// let msg = GraphiteMessage::new("app.requests", "42");
+    /// client.send_message(&msg).unwrap();
     ///
-    /// Returns `Ok(usize)` with the number of bytes written if successful, or
-    /// `Err(GraphiteError)` if all retry attempts fail.
+    /// assert_eq!(client.sent_messages(), vec![msg]);
+    /// ```
+    pub fn null() -> Self {
+        Self::builder()
+            .transport(Transport::Null)
+            .build()
+            .expect("a null client with default options always builds")
+    }
+
+    /// Returns the messages sent so far by a client using [`Transport::Null`], in the
+    /// order they were written.
     ///
-    /// # Connection Behavior
+    /// Messages are parsed back from the encoded lines, so they reflect client-level
+    /// options such as `hostname_prefix` and `fixed_timestamp`. Lines the plaintext parser
+    /// can't read, such as those written with a custom `separator`, are left out. Any
+    /// other transport returns an empty list.
+    pub fn sent_messages(&self) -> Vec<GraphiteMessage> {
+        let Some(recorded) = self.connection.recorded() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(recorded)
+            .lines()
+            .filter_map(|line| line.parse().ok())
+            .collect()
+    }
+
+    /// Sleeps before retry number `attempt` (zero-based), starting from `initial` and
+    /// doubling up to `max`, never sleeping past `deadline`.
+    fn backoff(
+        &mut self,
+        attempt: u8,
+        initial: Duration,
+        max: Duration,
+        deadline: Option<Instant>,
+    ) {
+        sleep_backoff(
+            attempt,
+            initial,
+            max,
+            self.retry_jitter,
+            &mut self.rng,
+            deadline,
+        );
+    }
+
+    /// Sleeps for a random delay of up to `reconnect_jitter`, never past `deadline`.
+    fn sleep_reconnect_jitter(&mut self, deadline: Option<Instant>) {
+        let Some(jitter) = self.reconnect_jitter else {
+            return;
+        };
+        let mut delay = self.rng.duration_up_to(jitter);
+        if let Some(deadline) = deadline {
+            delay = delay.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Computes when a connection established now should be retired.
+    fn expiry(lifetime: Option<Duration>, rng: &mut Rng) -> Option<Instant> {
+        lifetime.map(|lifetime| {
+            let splay = rng.duration_up_to(lifetime.mul_f64(CONNECTION_LIFETIME_SPLAY));
+            Instant::now() + (lifetime - splay)
+        })
+    }
+
+    /// Replaces the current connection, shutting the old one down, and hands the new one
+    /// to the clients from `try_clone`.
+    fn set_connection(&mut self, connection: impl Into<Connection>) {
+        let connection = connection.into();
+        self.connection_expires_at = Self::expiry(self.max_connection_lifetime, &mut self.rng);
+        self.generation =
+            self.shared
+                .publish(&connection, self.sock_addr, self.connection_expires_at);
+        let old = self.replace_connection(connection);
+        // Shut down explicitly rather than relying on drop, so the socket is torn down
+        // promptly and doesn't linger in CLOSE_WAIT during reconnect churn. Clones still
+        // writing to it move to the new connection.
+        let _ = old.shutdown(Shutdown::Both);
+    }
+
+    /// Moves to the connection a client from `try_clone` replaced the shared one with, if
+    /// any, returning whether it did.
+    fn follow_clones(&mut self) -> bool {
+        let Some((generation, latest)) = self.shared.newer_than(self.generation) else {
+            return false;
+        };
+        self.generation = generation;
+        self.sock_addr = latest.sock_addr;
+        self.connection_expires_at = latest.expires_at;
+        self.replace_connection(latest.connection);
+        true
+    }
+
+    /// Switches to `connection`, returning the old one, and resets what belonged to it.
+    fn replace_connection(&mut self, connection: Connection) -> Connection {
+        let mut old = std::mem::replace(&mut self.connection, connection);
+        // A null client keeps everything it recorded across reconnects.
+        if let (Connection::Null(recorded), Connection::Null(new)) =
+            (&mut old, &mut self.connection)
+        {
+            *new = std::mem::take(recorded);
+        }
+        self.connections_closed += 1;
+        self.connection_dirty = false;
+        self.nonblocking = false;
+        // The rest of a line cut short on the old connection would be garbage on this one.
+        self.skip_partial_line();
+        self.report_failures();
+        old
+    }
+
+    /// Writes the `self_report` meta-metric to the current connection if any sends have
+    /// failed since it was last written.
+    fn report_failures(&mut self) {
+        if self.unreported_failures == 0 {
+            return;
+        }
+        let line = format!(
+            "{prefix}graphyne.send_failures{sep}{count}{sep}{now}\n",
+            prefix = self.prefix.as_deref().unwrap_or(""),
+            sep = self.separator,
+            count = self.unreported_failures,
+            now = unix_now(),
+        );
+        if let Ok((_, Ok(()))) = self.write_within(line.as_bytes(), Some(self.timeout)) {
+            self.unreported_failures = 0;
+        }
+    }
+
+    /// Returns how many connections this client has replaced and shut down.
     ///
-    /// 1. Attempts to write the message to the existing connection
-    /// 2. If write fails, calls `reconnect()` to establish a new connection
-    /// 3. Retries the write operation on the new connection
-    /// 4. Repeats steps 2-3 up to `retries` times
+    /// Every successful `reconnect()`, `reset()`, or `update_target()` closes the previous
+    /// connection, as does re-dialing after `max_connection_lifetime`.
     ///
     /// # Examples
     ///
-    /// ## Single metric
-    ///
     /// ```rust,no_run
-    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    /// use graphyne::GraphiteClient;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut client = GraphiteClient::builder()
@@ -337,84 +1361,3062 @@ impl GraphiteClient {
     ///     .port(2003)
     ///     .build()?;
     ///
-    /// let msg = GraphiteMessage::new("cpu.usage", "45.2");
-    /// let bytes_sent = client.send_message(&msg)?;
-    /// println!("Sent {} bytes", bytes_sent);
+    /// client.reconnect()?;
+    /// assert_eq!(client.connections_closed(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connections_closed(&self) -> u64 {
+        self.connections_closed
+    }
+
+    /// Opens and configures a single TCP connection.
+    ///
+    /// The per-attempt `timeout` is shortened to whatever time remains before `deadline`,
+    /// and a `TimedOut` error is returned without connecting if the deadline has passed.
+    fn connect(
+        sock_addr: &SocketAddr,
+        timeout: Duration,
+        deadline: Option<Instant>,
+        tcp_ttl: Duration,
+        user_timeout: Option<Duration>,
+        send_buffer_size: Option<usize>,
+    ) -> Result<TcpStream, Error> {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(Error::new(ErrorKind::TimedOut, "connect deadline exceeded"));
+                }
+                timeout.min(remaining)
+            }
+            None => timeout,
+        };
+        let connection = TcpStream::connect_timeout(sock_addr, timeout)?;
+        connection.set_ttl(tcp_ttl.as_secs() as u32)?;
+        connection.set_nodelay(true)?;
+        #[cfg(target_os = "linux")]
+        if user_timeout.is_some() {
+            socket2::SockRef::from(&connection).set_tcp_user_timeout(user_timeout)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = user_timeout;
+        #[cfg(any(unix, windows))]
+        if let Some(size) = send_buffer_size {
+            socket2::SockRef::from(&connection).set_send_buffer_size(size)?;
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = send_buffer_size;
+        Ok(connection)
+    }
+
+    /// Connects to the first reachable address in `addrs`, trying each in order.
+    ///
+    /// Returns the address that connected, or the last connect error if none did.
+    fn connect_any(
+        addrs: &[SocketAddr],
+        timeout: Duration,
+        deadline: Option<Instant>,
+        tcp_ttl: Duration,
+        user_timeout: Option<Duration>,
+        send_buffer_size: Option<usize>,
+    ) -> Result<(SocketAddr, TcpStream), Error> {
+        let mut last_err = None;
+        for sock_addr in addrs {
+            match Self::connect(
+                sock_addr,
+                timeout,
+                deadline,
+                tcp_ttl,
+                user_timeout,
+                send_buffer_size,
+            ) {
+                Ok(connection) => return Ok((*sock_addr, connection)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| Error::new(ErrorKind::NotFound, "target resolved to no addresses")))
+    }
+
+    /// Returns the addresses a reconnect should try, ordered by `address_selection`.
+    ///
+    /// A hostname is resolved again once the cached resolution is older than
+    /// `dns_cache_ttl`. The address of the current connection is tried last, so a
+    /// reconnect moves to a different address in the pool when one is reachable, but is
+    /// always included, so a failed or changed resolution still leaves the last known good
+    /// address to fall back to.
+    fn connect_candidates(&mut self) -> Vec<SocketAddr> {
+        let stale = self
+            .resolved_at
+            .is_none_or(|at| at.elapsed() >= self.dns_cache_ttl);
+        if stale && IpAddr::from_str(&self.address).is_err() {
+            // Record the attempt even if it fails, so an unreachable resolver is only
+            // queried once per TTL.
+            self.resolved_at = Some(Instant::now());
+            if let Ok(addrs) = (self.address.as_str(), self.port).to_socket_addrs() {
+                let addrs: Vec<_> = addrs.collect();
+                if !addrs.is_empty() {
+                    self.resolved_addrs = addrs;
+                }
+            }
+        }
+        let mut candidates = self.resolved_addrs.clone();
+        self.address_selection
+            .order(&mut candidates, Some(self.sock_addr), &mut self.rng);
+        if !candidates.contains(&self.sock_addr) {
+            candidates.push(self.sock_addr);
+        }
+        candidates
+    }
+
+    /// Returns the address of the server the client is currently connected to.
+    ///
+    /// When `address` is a hostname this is whichever resolved address last accepted a
+    /// connection, and it can change after a reconnect re-resolves the hostname. If the
+    /// connection is broken, this is the address it was last connected to. Over a Unix
+    /// socket this is the unspecified address `0.0.0.0:0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("graphite.example.com")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// println!("connected to {}", client.connected_addr());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connected_addr(&self) -> SocketAddr {
+        self.sock_addr
+    }
+
+    /// Returns the underlying TCP stream, or `None` over another transport.
+    ///
+    /// This is an escape hatch for socket options the client doesn't wrap, e.g. through
+    /// `socket2::SockRef`. Reconnects replace the stream, so options set this way only
+    /// last until the next reconnect. Changing the stream's blocking mode or timeouts,
+    /// shutting it down, or reading from it leaves later sends with unspecified behavior.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// if let Some(stream) = client.connection_ref() {
+    ///     stream.set_nodelay(true)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection_ref(&self) -> Option<&TcpStream> {
+        self.connection.tcp()
+    }
+
+    /// Returns a second client writing to the same socket, with the same options.
+    ///
+    /// This suits one client per thread of a pool, each writing its own lines to a
+    /// shared connection without locking. Over [`Transport::Null`] the clone records
+    /// separately, starting empty.
+    ///
+    /// The clones share the connection, including across reconnects: the first of them
+    /// to find it broken reconnects while the others wait, and then every clone moves to
+    /// the new connection before its next write instead of reconnecting on its own. The
+    /// same goes for any other replacement, such as when `max_connection_lifetime`
+    /// expires, so the server sees a single client. Where a clone reconnects to is still
+    /// up to its own options, so a clone that moves with
+    /// [`update_target`](Self::update_target) or fails over to one of its `fallbacks`
+    /// takes the others along. The connection is shut down when it is replaced or once
+    /// the last clone is dropped. Everything else starts out copied and then diverges,
+    /// such as the circuit breaker state and the `dedupe_window` history, except for what
+    /// belongs to the original's own sends: anything it is holding in `write_buffer` or
+    /// for [`try_send_message`](Self::try_send_message) stays with it, and
+    /// [`send_delta`](Self::send_delta) baselines start empty.
+    ///
+    /// Each line goes out in a single write where the operating system allows, but a
+    /// large batch or a write cut short can interleave with another clone's lines and
+    /// corrupt both. If the original is partway through a line, the clone reconnects
+    /// before its first write, moving every clone off the socket. Socket options,
+    /// including the blocking mode [`try_send_message`](Self::try_send_message) switches,
+    /// are shared with every other handle to the socket. To share one connection between
+    /// threads with sends that can't interleave, use a [`SyncGraphiteClient`] instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket can't be duplicated, such as when the process has
+    /// run out of file descriptors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let mut handles = Vec::new();
+    /// for worker in 0..4 {
+    ///     let mut client = client.try_clone()?;
+    ///     handles.push(std::thread::spawn(move || {
+    ///         let path = format!("workers.{worker}.alive");
+    ///         client.send_message(&GraphiteMessage::new(&path, "1"))
+    ///     }));
+    /// }
+    /// for handle in handles {
+    ///     handle.join().unwrap()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_clone(&self) -> Result<GraphiteClient, GraphiteError> {
+        let connection = self.connection.try_clone()?;
+        let mid_line = self.pending_written > 0
+            && self.pending_written < self.pending.len()
+            && line_start(&self.pending, self.pending_written) != self.pending_written;
+        Ok(GraphiteClient {
+            connection,
+            shared: Arc::clone(&self.shared),
+            generation: self.generation,
+            transport: self.transport.clone(),
+            sock_addr: self.sock_addr,
+            address: self.address.clone(),
+            port: self.port,
+            endpoints: self.endpoints.clone(),
+            active_endpoint: self.active_endpoint,
+            failback_interval: self.failback_interval,
+            failback_at: self.failback_at,
+            on_endpoint_change: self.on_endpoint_change.clone(),
+            self_report: self.self_report,
+            dry_run: self.dry_run,
+            on_dry_run: self.on_dry_run.clone(),
+            on_would_block: self.on_would_block,
+            pending: Vec::new(),
+            pending_written: 0,
+            nonblocking: self.nonblocking,
+            unreported_failures: 0,
+            send_retries: self.send_retries,
+            connect_retries: self.connect_retries,
+            timeout: self.timeout,
+            tcp_ttl: self.tcp_ttl,
+            connect_deadline: self.connect_deadline,
+            fixed_timestamp: self.fixed_timestamp,
+            prefix: self.prefix.clone(),
+            max_connection_lifetime: self.max_connection_lifetime,
+            connection_expires_at: self.connection_expires_at,
+            // Reseed so the clones don't all jitter their retries in step.
+            rng: Rng::new(self.rng.clone().next_u64()),
+            retry_initial_delay: self.retry_initial_delay,
+            retry_max_delay: self.retry_max_delay,
+            connect_retry_initial_delay: self.connect_retry_initial_delay,
+            connect_retry_max_delay: self.connect_retry_max_delay,
+            max_line_bytes: self.max_line_bytes,
+            retry_jitter: self.retry_jitter,
+            reconnect_jitter: self.reconnect_jitter,
+            retryable_kinds: self.retryable_kinds.clone(),
+            retry_if: self.retry_if.clone(),
+            send_deadline: self.send_deadline,
+            connections_closed: 0,
+            value_formatter: self.value_formatter.clone(),
+            breaker: self.breaker.clone(),
+            reconnect_policy: self.reconnect_policy,
+            connection_dirty: self.connection_dirty || mid_line,
+            verify_connection_before_send: self.verify_connection_before_send,
+            min_timestamp: self.min_timestamp,
+            delivery: self.delivery,
+            on_non_finite: self.on_non_finite,
+            user_timeout: self.user_timeout,
+            send_buffer_size: self.send_buffer_size,
+            separator: self.separator.clone(),
+            batch_dedupe: self.batch_dedupe,
+            max_write_size: self.max_write_size,
+            max_batch_messages: self.max_batch_messages,
+            write_buffer: self.write_buffer,
+            recent_sends: self.recent_sends.clone(),
+            resolved_addrs: self.resolved_addrs.clone(),
+            resolved_at: self.resolved_at,
+            dns_cache_ttl: self.dns_cache_ttl,
+            address_selection: self.address_selection,
+            buffer: String::new(),
+            counter_baselines: HashMap::new(),
+        })
+    }
+
+    /// Attempts to reestablish the TCP connection to the Graphite server.
+    ///
+    /// This method makes one connection attempt plus up to `connect_retries` more,
+    /// replacing the existing connection if successful. It's called automatically by
+    /// `send_message` when a send operation fails, but can also be called manually.
+    ///
+    /// Attempts are separated by an exponential backoff starting at
+    /// `connect_retry_initial_delay` and capped at `connect_retry_max_delay`, so this can
+    /// block for up to the sum of those delays plus `timeout` per attempt (bounded overall
+    /// by `connect_deadline`, if set).
+    ///
+    /// If `address` is a hostname whose resolution is older than `dns_cache_ttl`, it is
+    /// resolved again and each fresh address is tried in turn. If resolution fails, the
+    /// address of the last successful connection is tried instead.
+    ///
+    /// With `fallbacks` configured, each attempt walks the endpoints in priority order,
+    /// starting from the primary, and switches to the first that accepts a connection.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if reconnection succeeds, or `Err(GraphiteError)` if all retry
+    /// attempts are exhausted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// // Manually reconnect if needed
+    /// client.reconnect()?;
+    ///
+    /// let msg = GraphiteMessage::new("test.metric", "1");
+    /// client.send_message(&msg)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconnect(&mut self) -> Result<(), GraphiteError> {
+        let deadline = self.connect_deadline.map(|d| Instant::now() + d);
+        self.reconnect_until(deadline)
+    }
+
+    /// Reconnects, giving up once `deadline` (if any) has passed.
+    fn reconnect_until(&mut self, deadline: Option<Instant>) -> Result<(), GraphiteError> {
+        self.redial(deadline)
+            .map_err(|(err, attempts)| GraphiteError::retries_exhausted(Some(err), attempts))
+    }
+
+    /// Reconnects like `reconnect_until`, returning the last connect error and the
+    /// attempts made on failure.
+    fn redial(&mut self, deadline: Option<Instant>) -> Result<(), (Error, usize)> {
+        // Clients from `try_clone` reconnect one at a time, and the first reconnects for
+        // all of them.
+        let shared = Arc::clone(&self.shared);
+        let _reconnecting = shared.reconnecting();
+        if self.follow_clones() {
+            return Ok(());
+        }
+        // One initial attempt plus `connect_retries` more.
+        let mut attempt = 0;
+        loop {
+            self.sleep_reconnect_jitter(deadline);
+            match self.connect_by_priority(self.endpoints.len(), deadline) {
+                Ok(()) => return Ok(()),
+                Err(err)
+                    if attempt == self.connect_retries
+                        || deadline.is_some_and(|d| Instant::now() >= d) =>
+                {
+                    return Err((err, usize::from(attempt) + 1));
+                }
+                Err(_) => {}
+            }
+            self.backoff(
+                attempt,
+                self.connect_retry_initial_delay,
+                self.connect_retry_max_delay,
+                deadline,
+            );
+            attempt += 1;
+        }
+    }
+
+    /// Connects to the first of the `limit` highest-priority endpoints that accepts a
+    /// connection, replacing the current connection and switching endpoint if needed.
+    ///
+    /// Returns the last connect error if none of them does.
+    fn connect_by_priority(
+        &mut self,
+        limit: usize,
+        deadline: Option<Instant>,
+    ) -> Result<(), Error> {
+        if self.transport != Transport::Tcp {
+            let connection = self.dial(deadline)?;
+            self.set_connection(connection);
+            return Ok(());
+        }
+        let mut last_err = Error::other("no endpoints to connect to");
+        for index in 0..limit {
+            let attempt = if index == self.active_endpoint {
+                let candidates = self.connect_candidates();
+                self.connect_to(&candidates, deadline)
+                    .map(|(sock_addr, connection)| (sock_addr, connection, None))
+            } else {
+                self.connect_fallback(index, deadline)
+            };
+            match attempt {
+                Ok((sock_addr, connection, resolution)) => {
+                    if let Some((addrs, resolved_at)) = resolution {
+                        self.resolved_addrs = addrs;
+                        self.resolved_at = resolved_at;
+                    }
+                    self.sock_addr = sock_addr;
+                    self.set_connection(connection);
+                    if index != self.active_endpoint {
+                        self.switch_endpoint(index);
+                    }
+                    return Ok(());
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Resolves and connects to the inactive endpoint at `index`, returning its resolution
+    /// along with the connection.
+    fn connect_fallback(
+        &mut self,
+        index: usize,
+        deadline: Option<Instant>,
+    ) -> Result<(SocketAddr, TcpStream, Option<Resolution>), Error> {
+        let (address, port) = &self.endpoints[index];
+        let (addrs, resolved_at) = resolve(address, *port)?;
+        let mut candidates = addrs.clone();
+        self.address_selection
+            .order(&mut candidates, None, &mut self.rng);
+        let (sock_addr, connection) = self.connect_to(&candidates, deadline)?;
+        Ok((sock_addr, connection, Some((addrs, resolved_at))))
+    }
+
+    /// Connects to the first reachable address in `addrs` using the client's settings.
+    fn connect_to(
+        &self,
+        addrs: &[SocketAddr],
+        deadline: Option<Instant>,
+    ) -> Result<(SocketAddr, TcpStream), Error> {
+        Self::connect_any(
+            addrs,
+            self.timeout,
+            deadline,
+            self.tcp_ttl,
+            self.user_timeout,
+            self.send_buffer_size,
+        )
+    }
+
+    /// Opens a new connection to the current target, without failing over or resolving
+    /// again.
+    fn dial(&self, deadline: Option<Instant>) -> Result<Connection, Error> {
+        match &self.transport {
+            Transport::Tcp => Self::connect(
+                &self.sock_addr,
+                self.timeout,
+                deadline,
+                self.tcp_ttl,
+                self.user_timeout,
+                self.send_buffer_size,
+            )
+            .map(Connection::from),
+            Transport::Udp => Connection::udp(self.sock_addr),
+            #[cfg(unix)]
+            Transport::UnixSocket(path) => UnixStream::connect(path).map(Connection::from),
+            Transport::Null => Ok(Connection::Null(Vec::new())),
+        }
+    }
+
+    /// Makes the endpoint at `index` the active one and fires `on_endpoint_change`.
+    fn switch_endpoint(&mut self, index: usize) {
+        self.active_endpoint = index;
+        (self.address, self.port) = self.endpoints[index].clone();
+        self.failback_at = self
+            .failback_interval
+            .filter(|_| index > 0)
+            .map(|interval| Instant::now() + interval);
+        if let Some(hook) = &self.on_endpoint_change {
+            hook(&self.address, self.port);
+        }
+    }
+
+    /// Tries once to move to a higher-priority endpoint if a fail-back is due.
+    ///
+    /// The current connection is kept if none of them accepts a connection.
+    fn fail_back_if_due(&mut self, deadline: Option<Instant>) {
+        let Some(at) = self.failback_at else {
+            return;
+        };
+        if Instant::now() < at {
+            return;
+        }
+        self.failback_at = self
+            .failback_interval
+            .map(|interval| Instant::now() + interval);
+        let _ = self.connect_by_priority(self.active_endpoint, deadline);
+    }
+
+    /// Returns the address and port of the endpoint the client is currently using.
+    ///
+    /// This is the primary `address` and `port` unless the client has failed over to one
+    /// of the `fallbacks`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("10.0.0.5")
+    ///     .port(2003)
+    ///     .fallbacks([("10.1.0.5", 2003)])
+    ///     .failback_interval(Duration::from_secs(60))
+    ///     .build()?;
+    ///
+    /// let (address, port) = client.active_endpoint();
+    /// println!("sending to {address}:{port}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn active_endpoint(&self) -> (&str, u16) {
+        (&self.address, self.port)
+    }
+
+    /// Returns whether the connection is believed to be healthy.
+    ///
+    /// Returns `false` if a previous write left the connection broken (see
+    /// [`ReconnectPolicy::Lazy`]) or a non-blocking peek shows the server has closed or
+    /// reset it. Cheap enough for readiness probes, but best-effort: `true` does not
+    /// guarantee the next write will succeed, since a failure may not have reached this
+    /// host yet.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// if !client.is_connected() {
+    ///     eprintln!("graphite unavailable, buffering locally");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_connected(&self) -> bool {
+        !self.connection_dirty && !self.peer_closed()
+    }
+
+    /// Checks the connection like [`is_connected`](Self::is_connected), reconnecting if it
+    /// is not healthy.
+    ///
+    /// The reconnect uses the same retry and backoff settings as
+    /// [`reconnect`](Self::reconnect). Like `is_connected`, success is best-effort and
+    /// does not guarantee the next write will go through.
+    pub fn check_connection(&mut self) -> Result<(), GraphiteError> {
+        if self.is_connected() {
+            return Ok(());
+        }
+        self.reconnect()
+    }
+
+    /// Tears down the current connection and connects from scratch.
+    ///
+    /// Unlike [`reconnect`](Self::reconnect), which reuses a cached resolution until
+    /// `dns_cache_ttl` passes, this always resolves the configured address and port again
+    /// before connecting, using the same retry and backoff settings. The old connection is
+    /// shut down first, so if the new connect fails, subsequent sends will attempt to
+    /// reconnect. Clients from [`try_clone`](Self::try_clone) lose the old connection
+    /// too, and move to the new one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let msg = GraphiteMessage::new("test.metric", "1");
+    /// if client.send_message(&msg).is_err() {
+    ///     client.reset()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reset(&mut self) -> Result<(), GraphiteError> {
+        let _ = self.connection.shutdown(Shutdown::Both);
+        // If reconnecting fails, the next send reconnects instead of writing to the
+        // socket just shut down.
+        self.connection_dirty = true;
+        self.resolved_at = None;
+        self.reconnect()
+    }
+
+    /// Points the client at a new Graphite server.
+    ///
+    /// The new target is resolved and connected to before anything else changes; only once
+    /// the new connection is established is the old one shut down and replaced. If every
+    /// resolved address is unreachable, the existing connection is left in place and an
+    /// error is returned. Subsequent reconnects use the new target, spreading over all of
+    /// its resolved addresses according to `address_selection`.
+    ///
+    /// The new target replaces the primary endpoint; any `fallbacks` are kept. A target
+    /// given as a hostname stays one, so reconnects resolve it again as for a hostname
+    /// given to the builder. A client using a Unix socket switches to TCP; a UDP client
+    /// stays on UDP. A client using [`Transport::Null`], including a `dry_run` client,
+    /// only records the new target and keeps sending nowhere.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("10.0.0.5")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// // Migrate to the new relay
+    /// client.update_target("10.0.1.5:2003")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_target(&mut self, addr: impl TargetAddr) -> Result<(), GraphiteError> {
+        let deadline = self.connect_deadline.map(|d| Instant::now() + d);
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let mut candidates = addrs.clone();
+        self.address_selection
+            .order(&mut candidates, Some(self.sock_addr), &mut self.rng);
+        let first = candidates
+            .first()
+            .copied()
+            .ok_or_else(|| GraphiteError::connect(Error::other("no addresses to connect to")));
+        let sock_addr = match self.transport {
+            // Nothing is sent, so there is nothing to connect; the recorded messages are kept.
+            Transport::Null => first?,
+            Transport::Udp => {
+                let sock_addr = first?;
+                let connection = Connection::udp(sock_addr).map_err(GraphiteError::connect)?;
+                self.set_connection(connection);
+                sock_addr
+            }
+            _ => {
+                let (sock_addr, connection) = Self::connect_any(
+                    &candidates,
+                    self.timeout,
+                    deadline,
+                    self.tcp_ttl,
+                    self.user_timeout,
+                    self.send_buffer_size,
+                )
+                .map_err(GraphiteError::connect)?;
+                self.set_connection(connection);
+                self.transport = Transport::Tcp;
+                sock_addr
+            }
+        };
+        self.sock_addr = sock_addr;
+        // Keep a hostname rather than the address it resolved to, so it can be resolved
+        // again later.
+        (self.address, self.port, self.resolved_at) = match addr.host() {
+            Some((host, port)) => (host, port, Some(Instant::now())),
+            None => (sock_addr.ip().to_string(), sock_addr.port(), None),
+        };
+        self.endpoints[0] = (self.address.clone(), self.port);
+        self.active_endpoint = 0;
+        self.failback_at = None;
+        self.resolved_addrs = addrs;
+        Ok(())
+    }
+
+    /// Sends a metric message to the Graphite server.
+    ///
+    /// This method writes the formatted metric to the TCP connection. If the write fails
+    /// (e.g., due to a broken connection), it automatically attempts to reconnect and retry
+    /// the send operation up to `send_retries` times.
+    ///
+    /// The message is formatted into a buffer the client keeps between sends, so sending
+    /// doesn't allocate once the buffer has grown to fit the longest line. To skip the
+    /// formatting as well for a message sent over and over, encode it once with
+    /// [`GraphiteMessage::encoded`] and send it with [`send_encoded`](Self::send_encoded).
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - A reference to the `GraphiteMessage` to send
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(usize)` with the length in bytes of the encoded message if successful,
+    /// or `Err(GraphiteError)` if all retry attempts fail. The message is written in full
+    /// on success, but the count doesn't include bytes written by failed attempts; see
+    /// [`send_message_report`](Self::send_message_report) for those. Written means
+    /// accepted into the operating system's send buffer, not received by the server.
+    ///
+    /// With `write_buffer`, the count is instead of bytes accepted into the client's own
+    /// buffer, and nothing may have been written yet; [`flush`](Self::flush) returns the
+    /// bytes it then writes out, and `send_message_report` tells the two apart. With a
+    /// `dedupe_window`, a message whose line was already sent within the window is
+    /// skipped and `Ok(0)` is returned. An error from the write names the metric and
+    /// carries a [`SendContext`] with the attempts made.
+    ///
+    /// # Connection Behavior
+    ///
+    /// 1. Attempts to write the message to the existing connection
+    /// 2. If write fails with a transient error (by default `BrokenPipe`, `ConnectionReset`,
+    ///    `ConnectionAborted`, `TimedOut` or `WouldBlock`; see `retryable_kinds` and
+    ///    `retry_if`), calls
+    ///    `reconnect()` to establish a new connection. Any other error is returned
+    ///    immediately with [`GraphiteErrorKind::NonRetryable`].
+    /// 3. Retries the write operation on the new connection
+    /// 4. Repeats steps 2-3 up to `send_retries` times; a reconnect that fails uses up an
+    ///    attempt too, and is tried again on the next one
+    ///
+    /// With `retry_initial_delay` set, each reconnect is preceded by an exponential backoff
+    /// delay, capped by `retry_max_delay`, so a send against an unavailable server can
+    /// block for the sum of those delays on top of the reconnect attempts themselves. Set
+    /// `send_deadline` to bound the total.
+    ///
+    /// # Examples
+    ///
+    /// ## Single metric
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let msg = GraphiteMessage::new("cpu.usage", "45.2");
+    /// let bytes_sent = client.send_message(&msg)?;
+    /// println!("Sent {} bytes", bytes_sent);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Multiple metrics
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let metrics = vec![
+    ///     GraphiteMessage::new("server1.cpu", "45"),
+    ///     GraphiteMessage::new("server1.memory", "80"),
+    ///     GraphiteMessage::new("server1.disk", "65"),
+    /// ];
+    ///
+    /// for metric in &metrics {
+    ///     client.send_message(metric)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        Ok(self.send_line(msg, None)?.bytes)
+    }
+
+    /// Sends a single metric message like [`send_message`](Self::send_message), reporting
+    /// how the send went.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let report = client.send_message_report(&GraphiteMessage::new("cpu.usage", "45.2"))?;
+    /// if report.reconnects > 0 {
+    ///     println!("sent after {} attempts", report.attempts);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_message_report(
+        &mut self,
+        msg: &GraphiteMessage,
+    ) -> Result<SendReport, GraphiteError> {
+        let closed_before = self.connections_closed;
+        let outcome = self.send_line(msg, None)?;
+        Ok(SendReport {
+            bytes_written: outcome.bytes_written,
+            bytes_buffered: outcome.bytes_buffered,
+            attempts: outcome.attempts,
+            reconnects: (self.connections_closed - closed_before) as usize,
+        })
+    }
+
+    /// Sends a message whose line was encoded ahead of time, like
+    /// [`send_message`](Self::send_message).
+    ///
+    /// The line held by `msg` is written as is when the client's options wouldn't change
+    /// it. Otherwise the message is encoded again, with `hostname_prefix`,
+    /// `fixed_timestamp`, `value_formatter` and the rest applied as usual.
+    pub fn send_encoded(&mut self, msg: &EncodedMessage) -> Result<usize, GraphiteError> {
+        let message = msg.message();
+        if self.encodes_verbatim(message, msg.line()) {
+            return Ok(self
+                .write_line(&message.metric_path, None, msg.line())?
+                .bytes);
+        }
+        self.send_message(message)
+    }
+
+    /// Sends a single metric message, giving up once `deadline` passes.
+    ///
+    /// Behaves like [`send_message`](Self::send_message), but the whole call, including
+    /// writes, backoff delays and reconnects, is abandoned with
+    /// [`GraphiteErrorKind::Timeout`] once `deadline` is reached, however the retry
+    /// settings work out. If the client also has a `send_deadline`, whichever comes first
+    /// applies.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(50);
+    /// client.send_message_by(&GraphiteMessage::new("cpu.usage", "45.2"), deadline)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_message_by(
+        &mut self,
+        msg: &GraphiteMessage,
+        deadline: Instant,
+    ) -> Result<usize, GraphiteError> {
+        Ok(self.send_line(msg, Some(deadline))?.bytes)
+    }
+
+    /// Sends a single metric message like [`send_message`](Self::send_message), encoding
+    /// it into `buf` instead of the client's internal buffer.
+    ///
+    /// `buf` is cleared first, and holds the encoded line afterwards, for callers that
+    /// also log or forward what was sent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let metrics = [
+    ///     GraphiteMessage::new("server1.cpu", "45"),
+    ///     GraphiteMessage::new("server1.memory", "80"),
+    /// ];
+    /// let mut buf = String::with_capacity(128);
+    /// for metric in &metrics {
+    ///     client.send_message_into(metric, &mut buf)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_message_into(
+        &mut self,
+        msg: &GraphiteMessage,
+        buf: &mut String,
+    ) -> Result<usize, GraphiteError> {
+        buf.clear();
+        Ok(self.send_line_in(msg, None, buf)?.bytes)
+    }
+
+    /// Sends a metric stamped with the current time whose value is formatted straight
+    /// into the client's send buffer.
+    ///
+    /// This avoids building the value as a `String` and then a [`GraphiteMessage`] only
+    /// to copy both into the line. The formatted value is checked like any other, so
+    /// `value_formatter`, `on_non_finite` and `max_line_bytes` still apply. Otherwise this
+    /// behaves like [`send_message`](Self::send_message).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let p99 = 0.123456;
+    /// client.send_fmt("api.latency.p99", format_args!("{p99:.3}"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_fmt(
+        &mut self,
+        path: &str,
+        value: fmt::Arguments<'_>,
+    ) -> Result<usize, GraphiteError> {
+        self.send_fmt_at(path, value, try_unix_now()?)
+    }
+
+    /// Sends a metric like [`send_fmt`](Self::send_fmt), stamped with `timestamp` in
+    /// seconds since the Unix epoch.
+    pub fn send_fmt_at(
+        &mut self,
+        path: &str,
+        value: fmt::Arguments<'_>,
+        timestamp: u64,
+    ) -> Result<usize, GraphiteError> {
+        self.send_formatted(path, None, timestamp, value)
+    }
+
+    /// Encodes a line for `path` with `value` formatted in place, as for
+    /// `encode_line_into`, and writes it like `send_line`.
+    fn send_formatted(
+        &mut self,
+        path: &str,
+        head: Option<&str>,
+        timestamp: u64,
+        value: fmt::Arguments<'_>,
+    ) -> Result<usize, GraphiteError> {
+        self.with_buffer(|client, data| {
+            let encoded = client.encode_line_into(path, head, timestamp, data, |out| {
+                use std::fmt::Write as _;
+
+                // Writing into a `String` cannot fail.
+                let _ = out.write_fmt(value);
+            })?;
+            if !encoded {
+                return Ok(0);
+            }
+            Ok(client.write_line(path, None, data)?.bytes)
+        })
+    }
+
+    /// Validates `path` and encodes it once with the client's metric prefix, returning a
+    /// handle for sending values to it with [`send_value`](Self::send_value).
+    ///
+    /// Suits a fixed set of paths sent at high frequency, since each send then only
+    /// formats the value and timestamp. Fails with [`GraphiteErrorKind::InvalidMessage`]
+    /// if the path is empty or contains whitespace, which would break the line.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let requests = client.register_path("prod.api.requests")?;
+    /// for count in [40.0, 42.0] {
+    ///     client.send_value(&requests, count)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_path(&self, path: &str) -> Result<MetricHandle, GraphiteError> {
+        if path.is_empty() || path.contains(char::is_whitespace) {
+            return Err(GraphiteError::invalid_message(
+                path,
+                ValidationRule::Syntax,
+                format!("metric path {path:?} is empty or contains whitespace"),
+            ));
+        }
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        Ok(MetricHandle {
+            path: Arc::from(path),
+            head: Arc::from(format!("{prefix}{path}{}", self.separator)),
+        })
+    }
+
+    /// Sends `value` for the path registered as `handle`, stamped with the current time.
+    ///
+    /// Apart from the path being encoded already, this behaves like
+    /// [`send_message`](Self::send_message): `value_formatter`, `on_non_finite` and
+    /// `max_line_bytes` apply as usual.
+    pub fn send_value(
+        &mut self,
+        handle: &MetricHandle,
+        value: f64,
+    ) -> Result<usize, GraphiteError> {
+        let timestamp = try_unix_now()?;
+        self.send_formatted(
+            &handle.path,
+            Some(&handle.head),
+            timestamp,
+            format_args!("{value}"),
+        )
+    }
+
+    /// Sends how much the counter at `path` has grown since the last call for that path,
+    /// given its current absolute `value`.
+    ///
+    /// Suits monotonically increasing counters, such as bytes served, when Graphite should
+    /// store per-interval increments, like a StatsD counter. The first observation of a
+    /// path only records the baseline and sends nothing, returning `Ok(0)`. A value lower
+    /// than the previous one is taken as a counter reset, so the new value itself is sent.
+    /// If the send fails the baseline is kept, so the next successful call covers the lost
+    /// increment as well.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// client.send_delta("web.bytes_served", 1_000.0)?; // records the baseline
+    /// client.send_delta("web.bytes_served", 1_250.0)?; // sends 250
+    /// client.send_delta("web.bytes_served", 40.0)?; // reset: sends 40
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_delta(&mut self, path: &str, value: f64) -> Result<usize, GraphiteError> {
+        let Some(&previous) = self.counter_baselines.get(path) else {
+            self.counter_baselines.insert(path.to_owned(), value);
+            return Ok(0);
+        };
+        let delta = if value < previous {
+            value
+        } else {
+            value - previous
+        };
+        let timestamp = try_unix_now()?;
+        let sent = self.send_formatted(path, None, timestamp, format_args!("{delta}"))?;
+        if let Some(baseline) = self.counter_baselines.get_mut(path) {
+            *baseline = value;
+        }
+        Ok(sent)
+    }
+
+    /// Sends `msg` as a StatsD line of the given type, such as `api.requests:3|c`, for a
+    /// client pointed at a StatsD aggregator, usually over [`Transport::Udp`].
+    ///
+    /// The metric prefix applies and the timestamp is left out, since StatsD stamps
+    /// points itself. Otherwise the line is validated and written like any other send:
+    /// `value_formatter`, `on_non_finite` and `max_line_bytes` apply, as do retries,
+    /// `write_buffer` and `dedupe_window`. A path or value containing `:`, `|` or
+    /// whitespace, which would break up the line, is rejected with
+    /// [`ValidationRule::Syntax`]. For a sampled point, write
+    /// [`GraphiteMessage::to_statsd_sampled`] through the client's [`Write`]
+    /// implementation, which writes each buffer in one go.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage, MetricType, Transport};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .transport(Transport::Udp)
+    ///     .address("127.0.0.1")
+    ///     .port(8125)
+    ///     .build()?;
+    ///
+    /// client.send_statsd(&GraphiteMessage::new("api.requests", "1"), MetricType::Counter)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_statsd(
+        &mut self,
+        msg: &GraphiteMessage,
+        metric_type: MetricType,
+    ) -> Result<usize, GraphiteError> {
+        let path = &*msg.metric_path;
+        self.with_buffer(|client, data| {
+            data.push_str(client.prefix.as_deref().unwrap_or_default());
+            data.push_str(path);
+            let colon = data.len();
+            data.push(':');
+            if !client.write_checked_value(path, 0, data, |out| out.push_str(&msg.value))? {
+                return Ok(0);
+            }
+            // StatsD splits a line on `:` and `|`, so neither may appear in the path or
+            // value, and a newline or other whitespace would break up the line.
+            let delimiter = |c: char| matches!(c, ':' | '|') || c.is_whitespace();
+            if data[..colon].contains(delimiter) || data[colon + 1..].contains(delimiter) {
+                let detail = format!("StatsD line {data:?} contains a delimiter or whitespace");
+                return Err(GraphiteError::invalid_message(
+                    path,
+                    ValidationRule::Syntax,
+                    detail,
+                ));
+            }
+            data.push('|');
+            data.push_str(metric_type.suffix());
+            data.push('\n');
+            client.check_line_len(path, 0, data)?;
+            Ok(client.write_line(path, None, data)?.bytes)
+        })
+    }
+
+    /// Sends a metric message without blocking on a full socket buffer.
+    ///
+    /// The connection is put into non-blocking mode, and the line is written only as far
+    /// as the kernel will take it right away. When it can't all be written,
+    /// [`SendStatus::WouldBlock`] is returned and `on_would_block` decides whether the line
+    /// is queued for [`poll_flush`](Self::poll_flush) or not sent at all. The rest of a
+    /// line that was partly written is always queued, so the stream never carries half a
+    /// line, and while anything is queued new lines go behind it.
+    ///
+    /// Nothing is retried: a failed write returns the error and leaves the connection to be
+    /// re-established by the next send, which blocks while it connects. Errors are
+    /// classified as for a blocking send, so one that `retryable_kinds` or `retry_if`
+    /// rejects is returned as [`GraphiteErrorKind::NonRetryable`]. Queued lines are
+    /// also written ahead of the next blocking send. The circuit breaker, `dry_run` and
+    /// message encoding apply as for [`send_message`](Self::send_message); `dedupe_window`
+    /// does not.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage, SendStatus};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let msg = GraphiteMessage::new("loop.ticks", "1");
+    /// if client.try_send_message(&msg)? == SendStatus::WouldBlock {
+    ///     // Try again once the socket is writable.
+    ///     client.poll_flush()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_send_message(&mut self, msg: &GraphiteMessage) -> Result<SendStatus, GraphiteError> {
+        self.with_buffer(|client, data| {
+            if !client.encode_into(msg, data)? {
+                return Ok(SendStatus::Sent);
+            }
+            client.try_write(data.as_bytes())
+        })
+    }
+
+    /// Writes as much of what [`try_send_message`](Self::try_send_message) queued as the
+    /// socket will take without blocking.
+    ///
+    /// Returns [`SendStatus::Sent`] once nothing is left queued, and
+    /// [`SendStatus::WouldBlock`] while some of it still is. Writing resumes exactly where
+    /// the last write stopped, even in the middle of a line. On a failed write the line it
+    /// stopped in is dropped, since its start went to a connection that is now gone, and
+    /// the lines after it stay queued for the next connection.
+    pub fn poll_flush(&mut self) -> Result<SendStatus, GraphiteError> {
+        if self.pending.is_empty() {
+            return Ok(SendStatus::Sent);
+        }
+        self.follow_clones();
+        if self.connection_dirty {
+            self.reconnect()?;
+        }
+        self.flush_pending_nonblocking()
+    }
+
+    /// Returns the number of bytes [`try_send_message`](Self::try_send_message) has queued
+    /// or `write_buffer` is holding that haven't been written yet.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.len() - self.pending_written
+    }
+
+    /// Writes out whatever `write_buffer` or `try_send_message` is holding with a single
+    /// write of at most `DROP_FLUSH_TIMEOUT`, for a client being dropped.
+    fn flush_on_drop(&mut self) {
+        if self.pending_bytes() == 0
+            || self.dry_run
+            || self.connection_dirty
+            || !self.breaker.allow()
+        {
+            return;
+        }
+        if self.nonblocking && self.connection.set_nonblocking(false).is_err() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let timeout = self.timeout.min(DROP_FLUSH_TIMEOUT);
+        let _ = self.write_within(&pending[self.pending_written..], Some(timeout));
+    }
+
+    /// Writes out every line `write_buffer` or
+    /// [`try_send_message`](Self::try_send_message) is holding back, blocking until it is
+    /// written.
+    ///
+    /// Returns the number of bytes written, which is what
+    /// [`pending_bytes`](Self::pending_bytes) reported beforehand. The lines are written
+    /// with the usual retries and reconnects. If those are exhausted the error is returned
+    /// and the lines are dropped, as the error's context reports.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .write_buffer(64 * 1024)
+    ///     .build()?;
+    ///
+    /// for i in 0..1000 {
+    ///     client.send_message(&GraphiteMessage::new(&format!("jobs.{i}.done"), "1"))?;
+    /// }
+    /// client.flush()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn flush(&mut self) -> Result<usize, GraphiteError> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        let bytes = self.pending_bytes();
+        let lines = count_lines(&self.pending[self.pending_written..]);
+        let start = Instant::now();
+        let closed_before = self.connections_closed;
+        // Writing nothing still writes out what is pending first.
+        self.write_with_retry(&[], None, Resume::Line)
+            .map(|_| bytes)
+            .map_err(|failure| {
+                let subject = format!("{lines} buffered lines");
+                self.describe_failure(failure, subject, start, closed_before)
+            })
+    }
+
+    /// Writes `line` without blocking, first flushing anything queued ahead of it.
+    fn try_write(&mut self, line: &[u8]) -> Result<SendStatus, GraphiteError> {
+        if self.dry_run {
+            if let Some(on_dry_run) = &self.on_dry_run {
+                on_dry_run(line);
+            }
+            return Ok(SendStatus::Sent);
+        }
+        if !self.breaker.allow() {
+            return Err(GraphiteError::CircuitOpen { context: None });
+        }
+        self.follow_clones();
+        if self.connection_dirty {
+            self.reconnect()?;
+        }
+        if self.flush_pending_nonblocking()? == SendStatus::WouldBlock {
+            if self.on_would_block == WouldBlockPolicy::Queue {
+                self.pending.extend_from_slice(line);
+            }
+            return Ok(SendStatus::WouldBlock);
+        }
+        let (written, complete) = self.write_nonblocking(line).map_err(|(_, err)| err)?;
+        if complete {
+            return Ok(SendStatus::Sent);
+        }
+        if written > 0 || self.on_would_block == WouldBlockPolicy::Queue {
+            self.pending.extend_from_slice(&line[written..]);
+        }
+        Ok(SendStatus::WouldBlock)
+    }
+
+    /// Writes queued lines without blocking, dropping them once all are written.
+    fn flush_pending_nonblocking(&mut self) -> Result<SendStatus, GraphiteError> {
+        if self.pending.is_empty() {
+            return Ok(SendStatus::Sent);
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let res = self.write_nonblocking(&pending[self.pending_written..]);
+        self.pending = pending;
+        match res {
+            Ok((written, complete)) => {
+                self.pending_written += written;
+                if !complete {
+                    return Ok(SendStatus::WouldBlock);
+                }
+                self.pending.clear();
+                self.pending_written = 0;
+                Ok(SendStatus::Sent)
+            }
+            Err((written, err)) => {
+                self.pending_written += written;
+                self.skip_partial_line();
+                Err(err)
+            }
+        }
+    }
+
+    /// Writes `data` in non-blocking mode, returning how much was written and whether that
+    /// was all of it before the socket buffer filled up.
+    ///
+    /// Any other write error marks the connection dirty and is returned along with how
+    /// much was written before it.
+    fn write_nonblocking(&mut self, data: &[u8]) -> Result<(usize, bool), (usize, GraphiteError)> {
+        if !self.nonblocking {
+            self.connection
+                .set_nonblocking(true)
+                .map_err(|err| (0, err.into()))?;
+            self.nonblocking = true;
+        }
+        let (written, res) = write_counting(&mut self.connection, data);
+        match res {
+            Ok(()) => {
+                self.breaker.record(true);
+                Ok((written, true))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok((written, false)),
+            Err(err) => {
+                self.breaker.record(false);
+                // Classified like a failed write under `ReconnectPolicy::Lazy`.
+                let error = if self.is_retryable(&err) {
+                    self.connection_dirty = true;
+                    GraphiteError::WriteFailed {
+                        source: Arc::new(err),
+                        context: None,
+                    }
+                } else {
+                    GraphiteError::NonRetryable {
+                        source: Arc::new(err),
+                        context: None,
+                    }
+                };
+                Err((written, error))
+            }
+        }
+    }
+
+    /// Drops the rest of the queued line a write stopped in the middle of, if any.
+    fn skip_partial_line(&mut self) {
+        let pos = self.pending_written;
+        if pos > 0 && pos < self.pending.len() && line_start(&self.pending, pos) != pos {
+            self.pending_written = line_end(&self.pending, pos);
+        }
+        if self.pending_written >= self.pending.len() {
+            self.pending.clear();
+            self.pending_written = 0;
+        }
+    }
+
+    /// Leaves non-blocking mode and writes anything `try_send_message` queued, so it goes
+    /// out ahead of a blocking send.
+    ///
+    /// A failure concerns none of the blocking send's own data, so it reports nothing as
+    /// written.
+    fn drain_pending(&mut self, by: Option<Instant>) -> Result<(), WriteFailure> {
+        if self.nonblocking {
+            self.connection.set_nonblocking(false)?;
+            self.nonblocking = false;
+        }
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let mut from = std::mem::take(&mut self.pending_written);
+        // The end of a line already started can only go to the same connection.
+        if from > 0 && line_start(&pending, from) != from {
+            let end = line_end(&pending, from);
+            if write_counting(&mut self.connection, &pending[from..end])
+                .1
+                .is_err()
+            {
+                self.connection_dirty = true;
+            }
+            from = end;
+        }
+        if from == pending.len() {
+            return Ok(());
+        }
+        self.write_with_reconnect(&pending[from..], by, Resume::Line)
+            .map(drop)
+            .map_err(|failure| WriteFailure {
+                written: 0,
+                resent: Vec::new(),
+                ..failure
+            })
+    }
+
+    /// Encodes `msg` and writes it with retries, unless `dedupe_window` is set and the
+    /// same line was sent within it.
+    fn send_line(
+        &mut self,
+        msg: &GraphiteMessage,
+        by: Option<Instant>,
+    ) -> Result<WriteOutcome, GraphiteError> {
+        self.with_buffer(|client, data| client.send_line_in(msg, by, data))
+    }
+
+    /// Calls `f` with the reused encoding buffer, empty, then shrinks the buffer back to
+    /// `MAX_RETAINED_BUFFER_BYTES` if `f` grew it further.
+    fn with_buffer<T>(&mut self, f: impl FnOnce(&mut Self, &mut String) -> T) -> T {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let res = f(self, &mut buffer);
+        buffer.clear();
+        buffer.shrink_to(MAX_RETAINED_BUFFER_BYTES);
+        self.buffer = buffer;
+        res
+    }
+
+    /// Sends `msg` like `send_line`, encoding it at the end of `data`, which must be
+    /// empty.
+    fn send_line_in(
+        &mut self,
+        msg: &GraphiteMessage,
+        by: Option<Instant>,
+        data: &mut String,
+    ) -> Result<WriteOutcome, GraphiteError> {
+        if !self.encode_into(msg, data)? {
+            return Ok(WriteOutcome::default());
+        }
+        self.write_line(&msg.metric_path, by, data)
+    }
+
+    /// Writes the encoded line `data` for `path` with retries, unless `dedupe_window` is
+    /// set and the same line was sent within it.
+    fn write_line(
+        &mut self,
+        path: &str,
+        by: Option<Instant>,
+        data: &str,
+    ) -> Result<WriteOutcome, GraphiteError> {
+        if let Some(recent) = &mut self.recent_sends
+            && recent.contains(data)
+        {
+            return Ok(WriteOutcome::default());
+        }
+        if let Some(capacity) = self.write_buffer
+            && !self.dry_run
+        {
+            if self.pending_bytes() + data.len() > capacity {
+                self.flush()?;
+            }
+            // A line that doesn't fit on its own is written directly, with the buffer now
+            // empty.
+            if data.len() <= capacity {
+                self.pending.extend_from_slice(data.as_bytes());
+                if let Some(recent) = &mut self.recent_sends {
+                    recent.insert(data);
+                }
+                return Ok(WriteOutcome {
+                    bytes: data.len(),
+                    bytes_buffered: data.len(),
+                    ..WriteOutcome::default()
+                });
+            }
+        }
+        let start = Instant::now();
+        let closed_before = self.connections_closed;
+        let outcome = self
+            .write_with_retry(data.as_bytes(), by, Resume::Line)
+            .map_err(|failure| {
+                let subject = format!("metric '{path}'");
+                self.describe_failure(failure, subject, start, closed_before)
+            })?;
+        if let Some(recent) = &mut self.recent_sends {
+            recent.insert(data);
+        }
+        Ok(outcome)
+    }
+
+    /// Sends several metric messages with a single write, returning the number of bytes
+    /// in the encoded batch.
+    ///
+    /// Equivalent to [`send_batch`](Self::send_batch) without the detailed result. A
+    /// batch only partly written under [`Delivery::AtMostOnce`] is reported as an error.
+    pub fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        complete_batch_bytes(self.send_batch(msgs)?)
+    }
+
+    /// Sends several metric messages as one batch, grouped by metric path.
+    ///
+    /// Groups appear in the order their paths first occur in `msgs`, and points within a
+    /// group keep their order, so every series arrives contiguous and in emission order.
+    /// The grouped batch is then sent like [`send_batch_message`](Self::send_batch_message),
+    /// including its retries and its chunking: a batch over `max_write_size` or
+    /// `max_batch_messages` takes several writes, each of whole lines in grouped order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let msgs = [
+    ///     "app.cpu 10 1609459200".parse()?,
+    ///     "app.mem 512 1609459200".parse()?,
+    ///     "app.cpu 12 1609459260".parse()?,
+    /// ];
+    /// // Written as app.cpu, app.cpu, app.mem.
+    /// let sent = client.send_messages_grouped(&msgs)?;
+    /// assert_eq!(sent.groups, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_messages_grouped(
+        &mut self,
+        msgs: &[GraphiteMessage],
+    ) -> Result<GroupedSend, GraphiteError> {
+        let mut group_of = HashMap::new();
+        let mut groups: Vec<Vec<&GraphiteMessage>> = Vec::new();
+        for msg in msgs {
+            let group = *group_of.entry(&*msg.metric_path).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(msg);
+        }
+        let group_count = groups.len();
+        let grouped: Vec<_> = groups.into_iter().flatten().collect();
+        let bytes = complete_batch_bytes(self.send_encoded_batch(&grouped, Resume::Line)?)?;
+        Ok(GroupedSend {
+            groups: group_count,
+            bytes,
+        })
+    }
+
+    /// Sends several metric messages with a single write, reporting how the batch was
+    /// transmitted.
+    ///
+    /// Retry and reconnect behavior matches [`send_message`](Self::send_message). If the
+    /// connection breaks partway through the batch, the retry resumes from the first line
+    /// that was not completely written, rather than resending the whole batch. A line cut
+    /// off by the failure is resent from its start, so only that line can be duplicated.
+    ///
+    /// Lines written before the failure count as sent even though they may still have
+    /// been sitting in the kernel's send buffer when the connection broke. Use
+    /// [`send_batch_atomic`](Self::send_batch_atomic) to resend the whole batch instead.
+    ///
+    /// Under [`Delivery::AtMostOnce`] nothing is resent. If the write fails after part of
+    /// the batch was written, this returns `Ok` with [`BatchResult::failure`] set, and the
+    /// result tells which messages were written, possibly written, and not written. A
+    /// failure before anything was written is returned as an error.
+    ///
+    /// A batch larger than `max_write_size`, or of more lines than `max_batch_messages`,
+    /// is written in chunks, each retried as above.
+    /// A chunk that still fails ends the send, reported through [`BatchResult::failure`]
+    /// if earlier chunks were written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let result = client.send_batch(&[
+    ///     GraphiteMessage::new("jobs.processed", "10"),
+    ///     GraphiteMessage::new("jobs.failed", "1"),
+    /// ])?;
+    /// println!("{} sent once, {} resent", result.sent_once, result.resent);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_batch(&mut self, msgs: &[GraphiteMessage]) -> Result<BatchResult, GraphiteError> {
+        self.send_encoded_batch(msgs, Resume::Line)
+    }
+
+    /// Sends several metric messages, reporting what happened to each message.
+    ///
+    /// The batch is written in chunks within `max_write_size` and `max_batch_messages`,
+    /// like [`send_batch`](Self::send_batch); once a chunk fails, the messages in it
+    /// that weren't written and every later message are reported as failed.
+    ///
+    /// The outcomes are in the order of `msgs`. Unlike [`send_batch`](Self::send_batch),
+    /// a message that fails validation, such as one exceeding `max_line_bytes`, doesn't
+    /// stop the rest of the batch; it is reported as [`MessageOutcome::Failed`]. Retries
+    /// resume from the first line not completely written, as for `send_batch`, and the
+    /// write offsets of every attempt decide which messages were delivered, which may have
+    /// been delivered twice, and which never made it. A write failure is reported through
+    /// the outcomes of the messages it affected rather than as an error, so the failed
+    /// messages can be queued for another attempt.
+    ///
+    /// With `batch_dedupe`, each message gets the outcome of the point it was collapsed
+    /// into.
+    ///
+    /// # Errors
+    ///
+    /// Fails only if the batch as a whole can't be prepared, such as when `batch_dedupe`
+    /// can't combine non-numeric values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage, MessageOutcome};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let msgs = [
+    ///     GraphiteMessage::new("jobs.processed", "10"),
+    ///     GraphiteMessage::new("jobs.failed", "1"),
+    /// ];
+    /// let outcomes = client.send_batch_detailed(&msgs)?;
+    /// let spool: Vec<_> = msgs
+    ///     .iter()
+    ///     .zip(&outcomes)
+    ///     .filter(|(_, outcome)| matches!(outcome, MessageOutcome::Failed(_)))
+    ///     .map(|(msg, _)| msg.clone())
+    ///     .collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_batch_detailed(
+        &mut self,
+        msgs: &[GraphiteMessage],
+    ) -> Result<Vec<MessageOutcome>, GraphiteError> {
+        let deduped;
+        let (lines, merged_into) = match self.batch_dedupe {
+            Some(policy) => {
+                let merged_into;
+                (deduped, merged_into) =
+                    batch::dedupe(msgs, policy, |msg| self.timestamp_for(msg.timestamp))?;
+                (&deduped[..], Some(merged_into))
+            }
+            None => (msgs, None),
+        };
+        let mut data = String::new();
+        let mut outcomes = Vec::with_capacity(lines.len());
+        // The encoded lines, by index into `lines` and byte range in `data`.
+        let mut spans = Vec::with_capacity(lines.len());
+        for (index, msg) in lines.iter().enumerate() {
+            let start = data.len();
+            outcomes.push(match self.encode_into(msg, &mut data) {
+                Ok(true) => {
+                    spans.push((index, start..data.len()));
+                    MessageOutcome::Delivered
+                }
+                Ok(false) => MessageOutcome::Skipped,
+                Err(err) => MessageOutcome::Failed(err),
+            });
+        }
+        let start = Instant::now();
+        let closed_before = self.connections_closed;
+        let messages = spans.len();
+        let chunk_lines = self.max_batch_messages.unwrap_or(usize::MAX);
+        let mut error: Option<GraphiteError> = None;
+        let mut rest = &spans[..];
+        while let Some((_, first)) = rest.first() {
+            // Lines are written in chunks like `send_batch`'s, each within
+            // `max_write_size` and `max_batch_messages`.
+            let from = first.start;
+            let mut count = 1;
+            while count < rest.len()
+                && count < chunk_lines
+                && rest[count].1.end - from <= self.max_write_size
+            {
+                count += 1;
+            }
+            let chunk;
+            (chunk, rest) = rest.split_at(count);
+            if let Some(err) = &error {
+                for (index, _) in chunk {
+                    outcomes[*index] = MessageOutcome::Failed(err.clone());
+                }
+                continue;
+            }
+            let data = &data.as_bytes()[from..chunk[count - 1].1.end];
+            let (done, resent) = match self.write_with_retry(data, None, Resume::Line) {
+                Ok(outcome) => (data.len(), outcome.resent),
+                Err(mut failure) => {
+                    let done = line_start(data, failure.written);
+                    let resent = std::mem::take(&mut failure.resent);
+                    let subject = format!("batch of {messages} messages");
+                    error = Some(self.describe_failure(failure, subject, start, closed_before));
+                    (done, resent)
+                }
+            };
+            for (index, span) in chunk {
+                let span = span.start - from..span.end - from;
+                outcomes[*index] = match &error {
+                    Some(err) if span.end > done => MessageOutcome::Failed(err.clone()),
+                    _ if resent
+                        .iter()
+                        .any(|range| range.start < span.end && span.start < range.end) =>
+                    {
+                        MessageOutcome::PossiblyDuplicated
+                    }
+                    _ => MessageOutcome::Delivered,
+                };
+            }
+        }
+        Ok(match merged_into {
+            Some(merged_into) => merged_into
+                .into_iter()
+                .map(|index| outcomes[index].clone())
+                .collect(),
+            None => outcomes,
+        })
+    }
+
+    /// Sends a batch as one buffer with a single `write_all`, reporting whether any of it
+    /// may have been delivered more than once.
+    ///
+    /// Every message is validated and encoded before anything is written, so a batch
+    /// containing an invalid message is rejected without sending any of it. The encoded
+    /// batch is never split into smaller writes.
+    ///
+    /// # Delivery Semantics
+    ///
+    /// TCP cannot make a batch truly all-or-nothing: a write that fails partway may already
+    /// have delivered a prefix of the batch to the server. Since a failed write is retried
+    /// with the whole batch on a fresh connection, delivery is **at least once**. When that
+    /// happens, [`BatchResult::possibly_duplicated`] is set so that downstream handling
+    /// can account for points that may have been received twice.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let result = client.send_batch_atomic(&[
+    ///     GraphiteMessage::new("jobs.processed", "10"),
+    ///     GraphiteMessage::new("jobs.failed", "1"),
+    /// ])?;
+    /// if result.possibly_duplicated {
+    ///     eprintln!("batch was resent after a reconnect");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_batch_atomic(
+        &mut self,
+        msgs: &[GraphiteMessage],
+    ) -> Result<BatchResult, GraphiteError> {
+        self.send_encoded_batch(msgs, Resume::Start)
+    }
+
+    /// Encodes `msgs` into one buffer and writes it, resuming after failures per `resume`.
+    fn send_encoded_batch<M: Borrow<GraphiteMessage>>(
+        &mut self,
+        msgs: &[M],
+        resume: Resume,
+    ) -> Result<BatchResult, GraphiteError> {
+        self.with_buffer(|client, combined| client.send_encoded_batch_in(msgs, resume, combined))
+    }
+
+    /// Sends `msgs` like `send_encoded_batch`, encoding them into `combined`, which must
+    /// be empty.
+    fn send_encoded_batch_in<M: Borrow<GraphiteMessage>>(
+        &mut self,
+        msgs: &[M],
+        resume: Resume,
+        combined: &mut String,
+    ) -> Result<BatchResult, GraphiteError> {
+        if let Some(policy) = self.batch_dedupe {
+            let (deduped, _) =
+                batch::dedupe(msgs, policy, |msg| self.timestamp_for(msg.timestamp))?;
+            return self.write_encoded_batch(&deduped, resume, combined);
+        }
+        self.write_encoded_batch(msgs, resume, combined)
+    }
+
+    /// Sends the already deduplicated `msgs` like `send_encoded_batch_in`.
+    fn write_encoded_batch<M: Borrow<GraphiteMessage>>(
+        &mut self,
+        msgs: &[M],
+        resume: Resume,
+        combined: &mut String,
+    ) -> Result<BatchResult, GraphiteError> {
+        let (chunk_bytes, chunk_lines) = match resume {
+            Resume::Line => (
+                self.max_write_size,
+                self.max_batch_messages.unwrap_or(usize::MAX),
+            ),
+            Resume::Start => (usize::MAX, usize::MAX),
+        };
+        // Length of the lines no longer kept in `combined`.
+        let mut bytes = 0;
+        let mut messages = 0;
+        let mut failures = Vec::new();
+        for (index, msg) in msgs.iter().enumerate() {
+            match self.encode_into(msg.borrow(), combined) {
+                Ok(true) => messages += 1,
+                Ok(false) => {}
+                Err(err) => failures.push((index, err)),
+            }
+            // A batch too large for one write is encoded again chunk by chunk as it is
+            // written, so from then on validating only needs the current line.
+            if bytes + combined.len() > chunk_bytes || messages > chunk_lines {
+                bytes += combined.len();
+                combined.clear();
+            }
+        }
+        if !failures.is_empty() {
+            return Err(GraphiteError::InvalidBatch {
+                failures,
+                messages: msgs.len(),
+            });
+        }
+        if bytes > 0 {
+            let bytes = bytes + combined.len();
+            combined.clear();
+            let limits = (chunk_bytes, chunk_lines);
+            return self.send_chunked_batch(msgs, messages, bytes, limits, combined);
+        }
+        let data = combined.as_bytes();
+        let start = Instant::now();
+        let closed_before = self.connections_closed;
+        let res = self.write_with_retry(data, None, resume);
+        let reconnects = (self.connections_closed - closed_before) as usize;
+        match res {
+            Ok(outcome) => {
+                let resent = outcome.resent_lines.min(messages);
+                Ok(BatchResult {
+                    messages,
+                    bytes: outcome.bytes,
+                    chunks: 1,
+                    reconnects,
+                    possibly_duplicated: outcome.reconnects > 0,
+                    sent_once: messages - resent,
+                    resent,
+                    maybe_sent: 0,
+                    unsent: 0,
+                    failure: None,
+                })
+            }
+            Err(failure) if self.delivery == Delivery::AtMostOnce && failure.written > 0 => {
+                let sent_once = data[..failure.written]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count();
+                let maybe_sent = usize::from(line_start(data, failure.written) < failure.written);
+                Ok(BatchResult {
+                    messages,
+                    bytes: data.len(),
+                    chunks: 1,
+                    reconnects,
+                    possibly_duplicated: false,
+                    sent_once,
+                    resent: 0,
+                    maybe_sent,
+                    unsent: messages - sent_once - maybe_sent,
+                    failure: Some(failure.error.kind()),
+                })
+            }
+            Err(failure) => {
+                let subject = format!("batch of {messages} messages");
+                Err(self.describe_failure(failure, subject, start, closed_before))
+            }
+        }
+    }
+
+    /// Writes the already validated `msgs` in chunks of whole lines of about
+    /// `chunk_bytes` and at most `chunk_lines` lines each, encoded into `chunk`, stopping
+    /// at the first chunk that fails.
+    ///
+    /// `messages` and `bytes` are the number of lines in the encoded batch and its length.
+    fn send_chunked_batch<M: Borrow<GraphiteMessage>>(
+        &mut self,
+        msgs: &[M],
+        messages: usize,
+        bytes: usize,
+        (chunk_bytes, chunk_lines): (usize, usize),
+        chunk: &mut String,
+    ) -> Result<BatchResult, GraphiteError> {
+        let start = Instant::now();
+        let closed_before = self.connections_closed;
+        let mut result = BatchResult {
+            messages,
+            bytes,
+            chunks: 0,
+            reconnects: 0,
+            possibly_duplicated: false,
+            sent_once: 0,
+            resent: 0,
+            maybe_sent: 0,
+            unsent: 0,
+            failure: None,
+        };
+        let mut pending = msgs.iter().peekable();
+        loop {
+            chunk.clear();
+            let mut lines = 0;
+            while let Some(msg) = pending.peek() {
+                if lines == chunk_lines {
+                    break;
+                }
+                let len = chunk.len();
+                if self.encode_into((*msg).borrow(), chunk)? {
+                    if chunk.len() > chunk_bytes && len > 0 {
+                        // Leave the line for the next chunk.
+                        chunk.truncate(len);
+                        break;
+                    }
+                    lines += 1;
+                }
+                pending.next();
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            let data = chunk.as_bytes();
+            match self.write_with_retry(data, None, Resume::Line) {
+                Ok(outcome) => {
+                    let resent = outcome.resent_lines.min(lines);
+                    result.chunks += 1;
+                    result.possibly_duplicated |= outcome.reconnects > 0;
+                    result.sent_once += lines - resent;
+                    result.resent += resent;
+                }
+                Err(failure)
+                    if result.chunks > 0
+                        || (self.delivery == Delivery::AtMostOnce && failure.written > 0) =>
+                {
+                    let done = line_start(data, failure.written);
+                    let resent: usize = failure
+                        .resent
+                        .iter()
+                        .filter(|range| range.start < done)
+                        .map(|range| count_lines(&data[range.start..range.end.min(done)]))
+                        .sum();
+                    result.chunks += 1;
+                    result.possibly_duplicated |= !failure.resent.is_empty();
+                    result.sent_once += count_lines(&data[..done]) - resent;
+                    result.resent += resent;
+                    result.maybe_sent = usize::from(done < failure.written);
+                    result.unsent = messages - result.sent_once - result.resent - result.maybe_sent;
+                    result.failure = Some(failure.error.kind());
+                    break;
+                }
+                Err(failure) => {
+                    let subject = format!("batch of {messages} messages");
+                    return Err(self.describe_failure(failure, subject, start, closed_before));
+                }
+            }
+        }
+        result.reconnects = (self.connections_closed - closed_before) as usize;
+        Ok(result)
+    }
+
+    /// Returns the error of a failed send, with context about the send appended: what was
+    /// being sent, the attempts made, the reconnects since `closed_before` and the time
+    /// since `start`.
+    fn describe_failure(
+        &self,
+        failure: WriteFailure,
+        subject: String,
+        start: Instant,
+        closed_before: u64,
+    ) -> GraphiteError {
+        let context = SendContext {
+            subject,
+            attempts: failure.attempts,
+            reconnects: (self.connections_closed - closed_before) as usize,
+            elapsed: start.elapsed(),
+        };
+        failure.error.with_context(context)
+    }
+
+    /// Sends a snapshot of metric values, such as one drained from a metrics registry.
+    ///
+    /// Each `(path, value)` pair is sent as a message stamped with the current time, all
+    /// in a single batch. This is the building block for exporting from instrumentation
+    /// libraries like `metrics`: a recorder periodically collects its counters and gauges
+    /// and hands them to this method.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// let snapshot = vec![
+    ///     ("app.requests.total".to_string(), 1024.0),
+    ///     ("app.queue.depth".to_string(), 3.0),
+    /// ];
+    /// client.flush_metrics_snapshot(&snapshot)?;
     /// # Ok(())
     /// # }
     /// ```
+    pub fn flush_metrics_snapshot(
+        &mut self,
+        snapshot: &[(String, f64)],
+    ) -> Result<usize, GraphiteError> {
+        let msgs: Vec<GraphiteMessage> = snapshot
+            .iter()
+            .map(|(path, value)| GraphiteMessage::new(path, &value.to_string()))
+            .collect();
+        self.send_batch_message(&msgs)
+    }
+
+    /// Returns the timestamp a message stamped with `timestamp` is sent with, after
+    /// `fixed_timestamp` and `min_timestamp` are applied.
+    fn timestamp_for(&self, timestamp: u64) -> u64 {
+        let timestamp = self.fixed_timestamp.unwrap_or(timestamp);
+        match self.min_timestamp {
+            Some(floor) => timestamp.max(floor),
+            None => timestamp,
+        }
+    }
+
+    /// Returns whether `encode_into` would produce exactly `line`, the line an
+    /// [`EncodedMessage`] holds for `msg`, so that it can be sent without encoding it
+    /// again.
+    fn encodes_verbatim(&self, msg: &GraphiteMessage, line: &str) -> bool {
+        self.prefix.is_none()
+            && self.value_formatter.is_none()
+            && self.separator == DEFAULT_SEPARATOR
+            && msg.timestamp != 0
+            && self.timestamp_for(msg.timestamp) == msg.timestamp
+            && line.len() <= self.max_line_bytes
+            && !msg
+                .value
+                .trim()
+                .parse::<f64>()
+                .is_ok_and(|v| !v.is_finite())
+    }
+
+    /// Appends `msg` to `out` in the plaintext protocol format, applying client-level
+    /// overrides such as `fixed_timestamp` and the metric prefix.
     ///
-    /// ## Multiple metrics
+    /// Returns whether a line was appended, which it isn't for a non-finite value skipped
+    /// under [`NonFinitePolicy::Skip`]. Fails without modifying `out` if the line would
+    /// exceed `max_line_bytes`, the timestamp is the Unix epoch, or the value is non-finite
+    /// under [`NonFinitePolicy::Error`].
+    fn encode_into(&self, msg: &GraphiteMessage, out: &mut String) -> Result<bool, GraphiteError> {
+        self.encode_line_into(&msg.metric_path, None, msg.timestamp, out, |out| {
+            out.push_str(&msg.value);
+        })
+    }
+
+    /// Appends a line for `path` and `timestamp` to `out` like `encode_into`, with the
+    /// value written by `write_value`.
+    ///
+    /// The value is written in place and then checked, so it needn't be a `String` first.
+    /// A `head` from a [`MetricHandle`] is written as is, in place of the prefixed path and
+    /// separator.
+    fn encode_line_into(
+        &self,
+        path: &str,
+        head: Option<&str>,
+        timestamp: u64,
+        out: &mut String,
+        write_value: impl FnOnce(&mut String),
+    ) -> Result<bool, GraphiteError> {
+        let start = out.len();
+        let timestamp = self.timestamp_for(timestamp);
+        if timestamp == 0 {
+            return Err(GraphiteError::ClockSkew {
+                reason: format!("message for '{path}' has timestamp 0; is the system clock set?"),
+            });
+        }
+        // Copied piecewise rather than through `writeln!`, which is measurably slower
+        // on this hot path.
+        match head {
+            Some(head) => out.push_str(head),
+            None => {
+                out.push_str(self.prefix.as_deref().unwrap_or_default());
+                out.push_str(path);
+                out.push_str(&self.separator);
+            }
+        }
+        if !self.write_checked_value(path, start, out, write_value)? {
+            return Ok(false);
+        }
+        out.push_str(&self.separator);
+        out.push_str(itoa::Buffer::new().format(timestamp));
+        out.push('\n');
+        self.check_line_len(path, start, out)?;
+        Ok(true)
+    }
+
+    /// Appends the value written by `write_value` to `out`, applying `value_formatter`
+    /// and `on_non_finite`, for a line that started at `start`.
+    ///
+    /// Returns whether the line is to be sent; if not, or on error, `out` is truncated
+    /// back to `start`.
+    fn write_checked_value(
+        &self,
+        path: &str,
+        start: usize,
+        out: &mut String,
+        write_value: impl FnOnce(&mut String),
+    ) -> Result<bool, GraphiteError> {
+        let value_start = out.len();
+        write_value(out);
+        if let Some(format_value) = &self.value_formatter {
+            let formatted = format_value(&out[value_start..]);
+            out.truncate(value_start);
+            out.push_str(&formatted);
+        }
+        let value = &out[value_start..];
+        if value.trim().parse::<f64>().is_ok_and(|v| !v.is_finite()) {
+            match self.on_non_finite {
+                NonFinitePolicy::Error => {
+                    let detail = format!("value {value:?} is not finite");
+                    out.truncate(start);
+                    return Err(GraphiteError::invalid_message(
+                        path,
+                        ValidationRule::NonFiniteValue,
+                        detail,
+                    ));
+                }
+                NonFinitePolicy::Skip => {
+                    out.truncate(start);
+                    return Ok(false);
+                }
+                NonFinitePolicy::Zero => {
+                    out.truncate(value_start);
+                    out.push('0');
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Fails, truncating `out` back to `start`, if the line from `start` to the end of
+    /// `out` exceeds `max_line_bytes`.
+    fn check_line_len(
+        &self,
+        path: &str,
+        start: usize,
+        out: &mut String,
+    ) -> Result<(), GraphiteError> {
+        let len = out.len() - start;
+        if len > self.max_line_bytes {
+            out.truncate(start);
+            let limit = self.max_line_bytes;
+            return Err(GraphiteError::invalid_message(
+                path,
+                ValidationRule::LineTooLong { len, limit },
+                format!("line is {len} bytes, exceeding the limit of {limit} bytes"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the current state of the circuit breaker.
+    ///
+    /// Always [`BreakerState::Closed`] unless `circuit_breaker_threshold` is set.
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    /// Writes `data` to the connection unless the circuit breaker is open, recording the
+    /// outcome with the breaker.
+    ///
+    /// `by` is a per-call deadline, applied alongside the client's `send_deadline`.
+    fn write_with_retry(
+        &mut self,
+        data: &[u8],
+        by: Option<Instant>,
+        resume: Resume,
+    ) -> Result<WriteOutcome, WriteFailure> {
+        if self.dry_run {
+            if let Some(on_dry_run) = &self.on_dry_run {
+                on_dry_run(data);
+            }
+            return Ok(WriteOutcome {
+                bytes: data.len(),
+                attempts: 1,
+                ..WriteOutcome::default()
+            });
+        }
+        if !self.breaker.allow() {
+            return Err(GraphiteError::CircuitOpen { context: None }.into());
+        }
+        let res = self
+            .drain_pending(by)
+            .and_then(|()| self.write_with_reconnect(data, by, resume));
+        self.breaker.record(res.is_ok());
+        if self.self_report
+            && let Err(failure) = &res
+            && failure.error.kind() == GraphiteErrorKind::RetriesExhausted
+        {
+            self.unreported_failures += 1;
+        }
+        res
+    }
+
+    /// Writes `data` to the connection, reconnecting and retrying on transient failures.
+    ///
+    /// Only errors `is_retryable` accepts trigger a reconnect; any other error is returned
+    /// immediately. After a failure, writing resumes from the point given by
+    /// `resume`. Under [`Delivery::AtMostOnce`] nothing is retried.
+    fn write_with_reconnect(
+        &mut self,
+        data: &[u8],
+        by: Option<Instant>,
+        resume: Resume,
+    ) -> Result<WriteOutcome, WriteFailure> {
+        let mut last_err = None;
+        // Start of the data not yet known to be written.
+        let mut offset = 0;
+        // Where the most recent failed write stopped.
+        let mut failed_at = 0;
+        // Lines before this index have already been counted in `resent_lines`.
+        let mut counted_until = 0;
+        let mut resent_lines = 0;
+        // Byte ranges of the lines counted in `resent_lines`.
+        let mut resent = Vec::new();
+        let mut bytes_written = 0;
+        let start = Instant::now();
+        let send_deadline = earliest(self.send_deadline.map(|d| start + d), by);
+        let deadline = earliest(self.connect_deadline.map(|d| start + d), send_deadline);
+        self.follow_clones();
+        if self
+            .connection_expires_at
+            .is_some_and(|at| Instant::now() >= at)
+        {
+            // The old connection still works, so if the re-dial fails keep using it; the
+            // next send will try again.
+            let _ = self.reconnect_until(deadline);
+        }
+        self.fail_back_if_due(deadline);
+        if self.verify_connection_before_send && self.peer_closed() {
+            self.connection_dirty = true;
+        }
+        if self.reconnect_policy == ReconnectPolicy::Lazy {
+            return self.write_lazily(data, deadline, send_deadline);
+        }
+        let retries = match self.delivery {
+            Delivery::AtLeastOnce => self.send_retries,
+            Delivery::AtMostOnce => 0,
+        };
+        // One initial attempt plus `retries` more. A failed write and a failed reconnect
+        // each use up an attempt.
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                self.backoff(
+                    attempt - 1,
+                    self.retry_initial_delay,
+                    self.retry_max_delay,
+                    deadline,
+                );
+            }
+            // In case the socket has been broken somewhere, reconnect it.
+            if self.connection_dirty
+                && let Err((err, _)) = self.redial(deadline)
+            {
+                last_err = Some(err);
+                if send_deadline.is_some_and(|d| Instant::now() >= d) {
+                    return Err(WriteFailure {
+                        error: GraphiteError::send_timeout(last_err),
+                        written: failed_at,
+                        attempts: usize::from(attempt) + 1,
+                        resent,
+                    });
+                }
+                continue;
+            }
+            let mut timeout = None;
+            if let Some(send_deadline) = send_deadline {
+                let remaining = send_deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(WriteFailure {
+                        error: GraphiteError::send_timeout(last_err),
+                        written: failed_at,
+                        attempts: usize::from(attempt),
+                        resent,
+                    });
+                }
+                // Keep a write stalled on a full socket buffer from overshooting.
+                timeout = Some(remaining);
+            }
+            let (written, res) = self.write_within(&data[offset..], timeout)?;
+            bytes_written += written;
+            match res {
+                Ok(_) => {
+                    return Ok(WriteOutcome {
+                        bytes: data.len(),
+                        bytes_written,
+                        bytes_buffered: 0,
+                        attempts: usize::from(attempt) + 1,
+                        reconnects: attempt,
+                        resent_lines,
+                        resent,
+                    });
+                }
+                Err(err) if !self.is_retryable(&err) => {
+                    return Err(WriteFailure {
+                        error: GraphiteError::NonRetryable {
+                            source: Arc::new(err),
+                            context: None,
+                        },
+                        written: offset + written,
+                        attempts: usize::from(attempt) + 1,
+                        resent,
+                    });
+                }
+                Err(err) => {
+                    last_err = Some(err);
+                    self.connection_dirty = true;
+                }
+            }
+            failed_at = offset + written;
+            offset = match resume {
+                Resume::Start => 0,
+                Resume::Line => line_start(data, failed_at),
+            };
+            // Lines between the resume point and the failure were at least partly written
+            // and will be written again.
+            if failed_at > offset {
+                let end = line_end(data, failed_at);
+                let from = offset.max(counted_until);
+                if end > from {
+                    resent_lines += count_lines(&data[from..end]);
+                    resent.push(from..end);
+                    counted_until = end;
+                }
+            }
+        }
+        // The data isn't rewritten, but reconnect now so that the next send doesn't hit
+        // the same broken connection. On failure the connection stays dirty.
+        if self.delivery == Delivery::AtMostOnce && self.connection_dirty {
+            let _ = self.redial(deadline);
+        }
+        Err(WriteFailure {
+            error: GraphiteError::retries_exhausted(last_err, usize::from(retries) + 1),
+            attempts: usize::from(retries) + 1,
+            written: failed_at,
+            resent,
+        })
+    }
+
+    /// Writes `data` to the connection like `write_counting`, limited to `timeout` if set.
+    ///
+    /// The previous write timeout is put back afterwards, whatever the outcome. The outer
+    /// error is a failure to change the timeout, before anything was written.
+    fn write_within(
+        &mut self,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, Result<(), Error>), Error> {
+        let Some(timeout) = timeout else {
+            return Ok(write_counting(&mut self.connection, data));
+        };
+        let previous = self.connection.write_timeout()?;
+        self.connection.set_write_timeout(Some(timeout))?;
+        let res = write_counting(&mut self.connection, data);
+        // Left in place, the limit would cut short every later write on this connection.
+        let _ = self.connection.set_write_timeout(previous);
+        Ok(res)
+    }
+
+    /// Returns whether a write failing with `err` should be retried on a new connection,
+    /// according to `retry_if` if set and `retryable_kinds` otherwise.
+    fn is_retryable(&self, err: &Error) -> bool {
+        match &self.retry_if {
+            Some(retry_if) => retry_if(err),
+            None => self.retryable_kinds.contains(&err.kind()),
+        }
+    }
+
+    /// Returns whether the server has closed or reset the connection.
+    fn peer_closed(&self) -> bool {
+        self.connection.peer_closed(self.nonblocking)
+    }
+
+    /// Writes `data` once under [`ReconnectPolicy::Lazy`].
+    ///
+    /// A connection left dirty by an earlier failure gets a single reconnect attempt first.
+    /// A failed write marks the connection dirty and returns without reconnecting.
+    fn write_lazily(
+        &mut self,
+        data: &[u8],
+        deadline: Option<Instant>,
+        send_deadline: Option<Instant>,
+    ) -> Result<WriteOutcome, WriteFailure> {
+        if self.connection_dirty {
+            let shared = Arc::clone(&self.shared);
+            let _reconnecting = shared.reconnecting();
+            if !self.follow_clones() {
+                self.sleep_reconnect_jitter(deadline);
+                let connection =
+                    self.dial(deadline)
+                        .map_err(|err| GraphiteError::ReconnectFailed {
+                            source: Arc::new(err),
+                        })?;
+                self.set_connection(connection);
+            }
+        }
+        let mut timeout = None;
+        if let Some(send_deadline) = send_deadline {
+            let remaining = send_deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(GraphiteError::send_timeout(None).into());
+            }
+            timeout = Some(remaining);
+        }
+        let (written, res) = self.write_within(data, timeout)?;
+        let error = match res {
+            Ok(_) => {
+                return Ok(WriteOutcome {
+                    bytes: data.len(),
+                    bytes_written: written,
+                    bytes_buffered: 0,
+                    attempts: 1,
+                    reconnects: 0,
+                    resent_lines: 0,
+                    resent: Vec::new(),
+                });
+            }
+            Err(err) if !self.is_retryable(&err) => GraphiteError::NonRetryable {
+                source: Arc::new(err),
+                context: None,
+            },
+            Err(err) => {
+                self.connection_dirty = true;
+                GraphiteError::WriteFailed {
+                    source: Arc::new(err),
+                    context: None,
+                }
+            }
+        };
+        Err(WriteFailure {
+            error,
+            written,
+            attempts: 1,
+            resent: Vec::new(),
+        })
+    }
+}
+
+impl Write for GraphiteClient {
+    /// Writes pre-formatted plaintext protocol lines to the connection.
+    ///
+    /// The whole buffer is written with the same circuit breaker, retry and reconnect
+    /// handling as [`send_message`](GraphiteClient::send_message), so on success the
+    /// returned count is always `buf.len()`. Client-level options that rewrite messages,
+    /// such as `hostname_prefix`, `fixed_timestamp` and `value_formatter`, do not apply,
+    /// and `buf` should end on a line boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::GraphiteClient;
+    /// use std::io::Write;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    ///
+    /// writeln!(client, "servers.web01.cpu.usage 45.2 1609459200")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.write_with_retry(buf, None, Resume::Line) {
+            Ok(outcome) => Ok(outcome.bytes),
+            Err(failure) if failure.error.kind() == GraphiteErrorKind::Timeout => {
+                Err(Error::new(ErrorKind::TimedOut, failure.error))
+            }
+            Err(failure) => Err(Error::other(failure.error)),
+        }
+    }
+
+    /// Writes out anything buffered, then flushes the underlying connection.
+    fn flush(&mut self) -> std::io::Result<()> {
+        GraphiteClient::flush(self).map_err(Error::other)?;
+        self.connection.flush()
+    }
+}
+
+/// The result of a successful `write_with_retry`.
+#[derive(Debug, Clone, Default)]
+struct WriteOutcome {
+    /// Length of the data, all of which has been written.
+    bytes: usize,
+
+    /// Bytes accepted by the socket across all attempts, including any written again.
+    bytes_written: usize,
+
+    /// Bytes held back by `write_buffer` instead of being written.
+    bytes_buffered: usize,
+
+    /// Write attempts made, including the successful one.
+    attempts: usize,
+
+    /// Reconnects performed after failed writes before the data went through.
+    reconnects: u8,
+
+    /// Lines that were at least partly written before a failure and then written again.
+    resent_lines: usize,
+
+    /// Byte ranges of the lines counted in `resent_lines`.
+    resent: Vec<Range<usize>>,
+}
+
+/// A failed `write_with_retry`, with how far the data got.
+#[derive(Debug)]
+struct WriteFailure {
+    /// Why the write failed.
+    error: GraphiteError,
+
+    /// Bytes of the data written before the final failure. Only whole lines before this
+    /// point are known to have been written.
+    written: usize,
+
+    /// Attempts made before giving up, counting failed reconnects.
+    attempts: usize,
+
+    /// Byte ranges of the lines written again after an earlier failed attempt.
+    resent: Vec<Range<usize>>,
+}
+
+impl From<GraphiteError> for WriteFailure {
+    fn from(error: GraphiteError) -> Self {
+        WriteFailure {
+            error,
+            written: 0,
+            attempts: 0,
+            resent: Vec::new(),
+        }
+    }
+}
+
+impl From<Error> for WriteFailure {
+    fn from(err: Error) -> Self {
+        GraphiteError::from(err).into()
+    }
+}
+
+impl From<WriteFailure> for GraphiteError {
+    fn from(failure: WriteFailure) -> Self {
+        failure.error
+    }
+}
+
+/// Where writing resumes after a failed write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    /// Resend all of the data.
+    Start,
+
+    /// Resend from the start of the first line that was not completely written.
+    Line,
+}
+
+/// Writes all of `buf` like `write_all`, also returning how many bytes were written when
+/// it fails.
+fn write_counting(stream: &mut impl Write, buf: &[u8]) -> (usize, Result<(), Error>) {
+    let mut written = 0;
+    while written < buf.len() {
+        match stream.write(&buf[written..]) {
+            Ok(0) => return (written, Err(Error::from(ErrorKind::WriteZero))),
+            Ok(n) => written += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return (written, Err(err)),
+        }
+    }
+    (written, Ok(()))
+}
+
+/// Returns the index where the line containing the byte before `pos` starts, or `pos`
+/// itself if it is at a line boundary.
+fn line_start(data: &[u8], pos: usize) -> usize {
+    data[..pos]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+/// Returns the index just past the end of the line containing the byte before `pos`.
+fn line_end(data: &[u8], pos: usize) -> usize {
+    data[pos - 1..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |newline| pos + newline)
+}
+
+/// Counts the lines in `data`, including a final line without a trailing newline.
+fn count_lines(data: &[u8]) -> usize {
+    let newlines = data.iter().filter(|&&b| b == b'\n').count();
+    newlines + usize::from(data.last().is_some_and(|&b| b != b'\n'))
+}
+
+/// Returns whichever of two optional deadlines comes first.
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Sleeps before retry `attempt`: `initial` doubled per attempt, capped at `max`,
+/// jittered, and cut short at `deadline`.
+fn sleep_backoff(
+    attempt: u8,
+    initial: Duration,
+    max: Duration,
+    jitter: Jitter,
+    rng: &mut Rng,
+    deadline: Option<Instant>,
+) {
+    let factor = 1u32.checked_shl(u32::from(attempt)).unwrap_or(u32::MAX);
+    let delay = initial.saturating_mul(factor).min(max);
+    let mut delay = jitter.apply(delay, rng);
+    if let Some(deadline) = deadline {
+        delay = delay.min(deadline.saturating_duration_since(Instant::now()));
+    }
+    if !delay.is_zero() {
+        std::thread::sleep(delay);
+    }
+}
+
+/// The socket addresses an endpoint resolved to and, for a hostname, when it was resolved.
+type Resolution = (Vec<SocketAddr>, Option<Instant>);
+
+/// Resolves `address`, an IP address or a hostname.
+fn resolve(address: &str, port: u16) -> Result<Resolution, Error> {
+    match IpAddr::from_str(address) {
+        Ok(ip) => Ok((vec![SocketAddr::new(ip, port)], None)),
+        Err(_) => Ok((
+            (address, port).to_socket_addrs()?.collect(),
+            Some(Instant::now()),
+        )),
+    }
+}
+
+/// Resolves an address given to the builder, like `resolve`.
+///
+/// A lookup failure here means the address itself is wrong, such as a typo, rather than
+/// the server being down, so it is reported as `InvalidInput`, which isn't retried.
+fn resolve_configured(address: &str, port: u16) -> Result<Resolution, Error> {
+    resolve(address, port).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("failed to resolve address {address:?}: {err}"),
+        )
+    })
+}
+
+/// Returns the bytes of a batch sent in full, or a `BatchIncomplete` error for one that
+/// was only partly written.
+fn complete_batch_bytes(result: BatchResult) -> Result<usize, GraphiteError> {
+    match result.failure {
+        Some(kind) => Err(GraphiteError::BatchIncomplete {
+            kind,
+            sent: result.sent_once + result.resent,
+            messages: result.messages,
+        }),
+        None => Ok(result.bytes),
+    }
+}
+
+/// Reads environment variable `name`, treating an empty value as unset.
+fn env_var(name: &str) -> Result<Option<String>, GraphiteError> {
+    match std::env::var(name) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(GraphiteError::invalid_config(format!(
+            "invalid {name}: {err}"
+        ))),
+    }
+}
+
+/// Reads and parses environment variable `name`, treating an empty value as unset.
+fn env_parse<T: FromStr>(name: &str) -> Result<Option<T>, GraphiteError> {
+    env_var(name)?
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| GraphiteError::invalid_config(format!("invalid {name} {value:?}")))
+        })
+        .transpose()
+}
+
+impl Drop for GraphiteClient {
+    /// Gracefully closes the TCP connection when the client is dropped.
+    ///
+    /// Unflushed `write_buffer` contents are written first, with a single write limited
+    /// to one second: unlike [`flush`](GraphiteClient::flush), dropping never retries,
+    /// reconnects or backs off, so it can't hold up the dropping thread for the full
+    /// retry schedule. The connection is then shut down, preventing resource leaks,
+    /// unless a client from [`try_clone`](GraphiteClient::try_clone) is still using it.
+    /// Any errors during either are silently ignored, and lines the write didn't get
+    /// out are lost.
+    fn drop(&mut self) {
+        self.flush_on_drop();
+        if Arc::strong_count(&self.shared) == 1 {
+            let _ = self.connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Returns the local hostname as a single metric path segment.
+///
+/// Dots and whitespace are replaced with underscores, so a fully-qualified hostname such as
+/// `web01.example.com` becomes `web01_example_com` rather than adding levels to the metric
+/// hierarchy.
+///
+/// # Examples
+///
+/// ```rust
+/// let segment = graphyne::hostname_segment();
+/// assert!(!segment.contains('.'));
+/// ```
+pub fn hostname_segment() -> String {
+    // Targets without an OS to ask, such as WebAssembly, have no hostname of their own.
+    #[cfg(not(any(unix, windows)))]
+    let hostname = std::ffi::OsString::from("localhost");
+    #[cfg(any(unix, windows))]
+    let hostname = gethostname::gethostname();
+    hostname
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c == '.' || c.is_whitespace() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Randomization applied to the delay between retry attempts.
+///
+/// Without jitter, every client that lost its connection at the same moment retries at the
+/// same moments too, which can overwhelm a relay that has just restarted. Jitter spreads
+/// those retries out.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, Jitter};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .retry_jitter(Jitter::Full)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Jitter {
+    /// Sleep for exactly the backoff delay.
+    #[default]
+    None,
+
+    /// Sleep for a random duration between zero and the backoff delay.
+    ///
+    /// This never adds latency; on average it halves the time spent sleeping.
+    Full,
+
+    /// Sleep for the backoff delay plus a random extra of up to the given fraction of it.
+    ///
+    /// For example `Proportional(0.5)` turns a 100ms delay into 100-150ms. The worst-case
+    /// latency added to a send is this fraction of the sum of all its backoff delays. The
+    /// builder rejects a fraction that isn't finite or is over 10.
+    Proportional(f64),
+}
+
+impl Jitter {
+    /// Applies this jitter strategy to `delay`.
+    fn apply(self, delay: Duration, rng: &mut Rng) -> Duration {
+        match self {
+            Jitter::None => delay,
+            Jitter::Full => rng.duration_up_to(delay),
+            Jitter::Proportional(fraction) => {
+                delay + rng.duration_up_to(delay.mul_f64(fraction.max(0.0)))
+            }
+        }
+    }
+}
+
+/// When a client reconnects after a failed write.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, ReconnectPolicy};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .reconnect_policy(ReconnectPolicy::Lazy)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReconnectPolicy {
+    /// Reconnect and retry within the failing send, with backoff, up to `send_retries`
+    /// times.
+    ///
+    /// A send can block for every backoff delay and reconnect attempt before returning.
+    #[default]
+    Eager,
+
+    /// Return the write error immediately and reconnect at the start of the next send.
+    ///
+    /// Each send makes at most one reconnect attempt and one write, without backoff, so
+    /// per-call latency stays bounded by a single connect timeout plus a single write, and
+    /// reconnect attempts are naturally spaced out by the caller's send interval. Failed
+    /// sends are not retried; the caller decides whether to resend.
+    Lazy,
+}
+
+/// How a single send was carried out, returned by
+/// [`GraphiteClient::send_message_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SendReport {
+    /// Bytes accepted by the socket across all attempts.
     ///
-    /// ```rust,no_run
-    /// use graphyne::{GraphiteClient, GraphiteMessage};
+    /// Equal to the encoded message length unless a failed attempt had already written
+    /// part of it before the message was written again, or zero if it was buffered
+    /// instead. Accepted by the socket means handed to the operating system, not
+    /// acknowledged by the server.
+    pub bytes_written: usize,
+
+    /// Bytes of the message held by `write_buffer` for a later write, or zero if it was
+    /// written straight away.
     ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut client = GraphiteClient::builder()
-    ///     .address("127.0.0.1")
-    ///     .port(2003)
-    ///     .build()?;
+    /// These count as written once [`GraphiteClient::flush`] or a later send writes the
+    /// buffer out.
+    pub bytes_buffered: usize,
+
+    /// Write attempts made, including the successful one.
+    pub attempts: usize,
+
+    /// Connections replaced during the send, whether after a failed write or because
+    /// the connection had reached `max_connection_lifetime` or was found closed.
+    pub reconnects: usize,
+}
+
+/// What [`GraphiteClient::send_messages_grouped`] sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GroupedSend {
+    /// Number of distinct metric paths, each sent as one contiguous group.
+    pub groups: usize,
+
+    /// Bytes in the encoded batch.
+    pub bytes: usize,
+}
+
+/// Whether a client retries a failed write with the same data.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{Delivery, GraphiteClient};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .delivery(Delivery::AtMostOnce)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delivery {
+    /// Reconnect and write the data again after a failure, up to `send_retries` times.
     ///
-    /// let metrics = vec![
-    ///     GraphiteMessage::new("server1.cpu", "45"),
-    ///     GraphiteMessage::new("server1.memory", "80"),
-    ///     GraphiteMessage::new("server1.disk", "65"),
-    /// ];
+    /// Data that was partly delivered before the failure may be received twice.
+    #[default]
+    AtLeastOnce,
+
+    /// Never write the same data twice.
     ///
-    /// for metric in &metrics {
-    ///     client.send_message(metric)?;
-    /// }
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
-        let mut last_err: Error = Error::last_os_error();
-        let mut i = 0;
-        let data = msg.to_string();
-        while i < self.retries {
-            let res = self.connection.write_all(data.as_bytes());
-            match res {
-                Ok(_) => return Ok(data.len()),
-                Err(err) => last_err = err,
-            }
-            // In case the socket has been broken somewhere, reconnect it.
-            self.reconnect()?;
-            i += 1;
-        }
-        Err(GraphiteError {
-            msg: format!("Graphite Error: {last_err}"),
-        })
-    }
+    /// After a failed write the client reconnects for the benefit of later sends, but
+    /// returns the error instead of retrying. Suits metrics feeding carbon-aggregator
+    /// sums, where a duplicate point is worse than a missing one.
+    AtMostOnce,
+}
 
-    pub fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
-        let mut last_err: Error = Error::last_os_error();
+/// What a client does with a value that parses as a non-finite number, such as `NaN`,
+/// `inf` or `-Infinity`.
+///
+/// Carbon silently drops such values, leaving a gap in the series that is hard to trace
+/// back to its cause.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, NonFinitePolicy};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .on_non_finite(NonFinitePolicy::Skip)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// Fail the send with [`ValidationRule::NonFiniteValue`], so the bug that produced the
+    /// value surfaces.
+    #[default]
+    Error,
 
-        let combined: String = msgs.iter().map(ToString::to_string).collect();
+    /// Leave the message out and report success. A batch is sent without it.
+    Skip,
 
-        let mut i = 0;
-        while i < self.retries {
-            let res = self.connection.write_all(combined.as_bytes());
-            match res {
-                Ok(_) => return Ok(combined.len()),
-                Err(err) => last_err = err,
-            }
-            // In case the socket has been broken somewhere, reconnect it.
-            self.reconnect()?;
-            i += 1;
-        }
-        Err(GraphiteError {
-            msg: format!("Graphite Error: {last_err}"),
-        })
-    }
+    /// Send `0` in place of the value.
+    Zero,
 }
 
-impl Drop for GraphiteClient {
-    /// Gracefully closes the TCP connection when the client is dropped.
+/// Result of a [`GraphiteClient::try_send_message`] or [`GraphiteClient::poll_flush`]
+/// that didn't fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendStatus {
+    /// Everything was handed to the kernel.
+    Sent,
+
+    /// The socket buffer is full. Whatever wasn't written is queued for
+    /// [`GraphiteClient::poll_flush`], or for a line nobody started writing, dropped if the
+    /// client's [`WouldBlockPolicy`] says so.
+    WouldBlock,
+}
+
+/// What [`GraphiteClient::try_send_message`] does with a line the socket can't take
+/// without blocking.
+///
+/// A line the kernel took part of is always queued, since the stream would otherwise
+/// be left with half a line.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, WouldBlockPolicy};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .on_would_block(WouldBlockPolicy::Discard)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WouldBlockPolicy {
+    /// Queue the line behind anything already queued, to be written by
+    /// [`GraphiteClient::poll_flush`] or ahead of the next blocking send.
+    #[default]
+    Queue,
+
+    /// Leave the line unsent, so the queue never grows past a partly written line.
+    Discard,
+}
+
+/// Order in which a client tries the addresses its target resolves to.
+///
+/// When a hostname resolves to several addresses, such as a DNS-load-balanced pool of
+/// relays, always connecting to the first one concentrates every client on one relay.
+/// Whatever the strategy, a reconnect tries the address of the connection it replaces
+/// last, so it moves to another address in the pool when one is reachable.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{AddressSelection, GraphiteClient};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("graphite.prod")
+///     .port(2003)
+///     .address_selection(AddressSelection::RoundRobin)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressSelection {
+    /// Try the addresses in a random order on every connect.
+    #[default]
+    Shuffle,
+
+    /// Try the addresses in the order the resolver returned them.
+    Ordered,
+
+    /// Try the addresses in resolver order, starting after the one last connected to.
     ///
-    /// This ensures that the connection is properly shut down, preventing resource leaks.
-    /// Any errors during shutdown are silently ignored.
-    fn drop(&mut self) {
-        let _ = self.connection.shutdown(std::net::Shutdown::Both);
+    /// Successive reconnects cycle through the whole pool. The first connect starts at the
+    /// first address.
+    RoundRobin,
+}
+
+impl AddressSelection {
+    /// Orders `addrs` for a connect that replaces a connection to `current`, if any.
+    fn order(self, addrs: &mut [SocketAddr], current: Option<SocketAddr>, rng: &mut Rng) {
+        match self {
+            AddressSelection::Shuffle => rng.shuffle(addrs),
+            AddressSelection::Ordered => {}
+            AddressSelection::RoundRobin => {
+                if let Some(pos) = addrs.iter().position(|addr| Some(*addr) == current) {
+                    addrs.rotate_left(pos + 1);
+                }
+            }
+        }
+        if let Some(pos) = addrs.iter().position(|addr| Some(*addr) == current) {
+            addrs[pos..].rotate_left(1);
+        }
     }
 }
 
@@ -442,6 +4444,12 @@ impl Drop for GraphiteClient {
 /// - `company.application.server.component.metric`
 /// - `environment.service.host.subsystem.value`
 ///
+/// # Equality
+///
+/// Two messages are equal, and hash alike, when their paths, values and timestamps are
+/// all equal, so a `HashSet` of messages collapses exact resends of a point. Values are
+/// compared as text: `1` and `1.0` are different messages.
+///
 /// # Examples
 ///
 /// ```rust
@@ -456,12 +4464,13 @@ impl Drop for GraphiteClient {
 /// // Create a message with namespace hierarchy
 /// let metric = GraphiteMessage::new("prod.api.gateway.latency.p95", "125");
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GraphiteMessage {
     /// The hierarchical path identifying this metric in Graphite.
     ///
-    /// Should use dots as separators (e.g., "servers.web01.cpu.usage").
-    metric_path: String,
+    /// Should use dots as separators (e.g., "servers.web01.cpu.usage"). Shared, so that
+    /// messages built from an interned path don't copy it.
+    metric_path: Arc<str>,
 
     /// The numeric value of the metric as a string.
     ///
@@ -481,6 +4490,10 @@ impl GraphiteMessage {
     /// The timestamp is automatically generated using the system clock at the moment
     /// this method is called.
     ///
+    /// If the system clock reads before the Unix epoch, the timestamp is 0, which a
+    /// client refuses to send unless it has a `min_timestamp` floor. Use
+    /// [`try_new`](Self::try_new) to detect this when the message is created.
+    ///
     /// # Arguments
     ///
     /// * `metric_path` - The hierarchical path for this metric (e.g., "app.cpu.usage")
@@ -502,16 +4515,249 @@ impl GraphiteMessage {
     /// ```
     pub fn new(metric_path: &str, value: &str) -> Self {
         Self {
-            metric_path: metric_path.to_string(),
+            metric_path: Arc::from(metric_path),
+            value: value.to_string(),
+            timestamp: unix_now(),
+        }
+    }
+
+    /// Creates a new metric message with the current timestamp from a shared metric path.
+    ///
+    /// Unlike [`new`](Self::new), the path isn't copied: the message holds another
+    /// reference to `metric_path`. Pair this with a [`PathInterner`] to share one
+    /// allocation among all messages for the same path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    /// use std::sync::Arc;
+    ///
+    /// let path: Arc<str> = Arc::from("app.requests");
+    /// let first = GraphiteMessage::interned(Arc::clone(&path), "1");
+    /// let second = GraphiteMessage::interned(path, "2");
+    /// ```
+    pub fn interned(metric_path: Arc<str>, value: &str) -> Self {
+        Self {
+            metric_path,
+            value: value.to_string(),
+            timestamp: unix_now(),
+        }
+    }
+
+    /// Creates a new metric message with the current timestamp, failing if the system
+    /// clock reads before the Unix epoch.
+    ///
+    /// Devices that boot with an unset real-time clock, or whose clock is stepped
+    /// backwards by NTP, can briefly report such a time. The error has kind
+    /// [`GraphiteErrorKind::ClockSkew`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    ///
+    /// let msg = GraphiteMessage::try_new("requests.count", "150").unwrap();
+    /// assert!(msg.to_string().starts_with("requests.count 150 "));
+    /// ```
+    pub fn try_new(metric_path: &str, value: &str) -> Result<Self, GraphiteError> {
+        Ok(Self {
+            metric_path: Arc::from(metric_path),
             value: value.to_string(),
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs(),
+            timestamp: try_unix_now()?,
+        })
+    }
+
+    /// Creates a message recording a duration, such as the time an operation took.
+    ///
+    /// The value is written in the given `unit`, as a decimal with fractional digits only
+    /// when needed. The unit is explicit so that dashboards never have to guess whether a
+    /// series is in seconds or milliseconds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::{DurationUnit, GraphiteMessage};
+    /// use std::time::Duration;
+    ///
+    /// let elapsed = Duration::from_millis(1250);
+    ///
+    /// let secs = GraphiteMessage::from_duration("api.latency", elapsed, DurationUnit::Seconds);
+    /// assert!(secs.to_string().starts_with("api.latency 1.25 "));
+    ///
+    /// let millis = GraphiteMessage::from_duration("api.latency_ms", elapsed, DurationUnit::Milliseconds);
+    /// assert!(millis.to_string().starts_with("api.latency_ms 1250 "));
+    /// ```
+    pub fn from_duration(metric_path: &str, duration: Duration, unit: DurationUnit) -> Self {
+        let nanos = duration.as_nanos() as f64;
+        let value = match unit {
+            DurationUnit::Seconds => nanos / 1e9,
+            DurationUnit::Milliseconds => nanos / 1e6,
+        };
+        Self::new(metric_path, &value.to_string())
+    }
+
+    /// Returns the message in plaintext protocol format without the trailing newline.
+    ///
+    /// Useful when framing lines yourself, e.g. behind a length prefix. The
+    /// [`Display`](fmt::Display) implementation gives the newline-terminated form.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    ///
+    /// let msg: GraphiteMessage = "test.metric 42 1609459200".parse().unwrap();
+    /// assert_eq!(msg.format_line(), "test.metric 42 1609459200");
+    /// ```
+    pub fn format_line(&self) -> String {
+        format!("{} {} {}", self.metric_path, self.value, self.timestamp)
+    }
+
+    /// Writes the message in plaintext protocol format, without the trailing newline, to
+    /// `w`, leaving the framing to the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    ///
+    /// let msg: GraphiteMessage = "test.metric 42 1609459200".parse().unwrap();
+    /// let mut framed = Vec::new();
+    /// msg.write_to(&mut framed).unwrap();
+    /// framed.push(b'\0');
+    /// assert_eq!(framed, b"test.metric 42 1609459200\0");
+    /// ```
+    pub fn write_to(&self, w: &mut impl Write) -> std::io::Result<()> {
+        write!(w, "{} {} {}", self.metric_path, self.value, self.timestamp)
+    }
+
+    /// Sets the timestamp, in seconds since the Unix epoch.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = timestamp;
+    }
+
+    /// Restamps the message with the current time.
+    pub fn touch(&mut self) {
+        self.set_timestamp(unix_now());
+    }
+
+    /// Returns the length in bytes of this message in plaintext protocol format.
+    pub(crate) fn encoded_len(&self) -> usize {
+        let timestamp = itoa::Buffer::new().format(self.timestamp).len();
+        // Two separating spaces and the trailing newline.
+        self.metric_path.len() + self.value.len() + timestamp + 3
+    }
+
+    /// Formats the message once and keeps the line along with it, for sending it over
+    /// and over with [`GraphiteClient::send_encoded`] without formatting it each time.
+    ///
+    /// The line is cached in the returned [`EncodedMessage`] rather than in the message
+    /// itself, so that a `GraphiteMessage` stays a plain value whose `Clone`, `Eq` and
+    /// `Hash` see only its path, value and timestamp.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    ///
+    /// let msg: GraphiteMessage = "app.heartbeat 1 1609459200".parse().unwrap();
+    /// let mut encoded = msg.encoded();
+    /// assert_eq!(encoded.as_bytes(), b"app.heartbeat 1 1609459200\n");
+    ///
+    /// encoded.set_timestamp(1609459260);
+    /// assert_eq!(encoded.as_bytes(), b"app.heartbeat 1 1609459260\n");
+    /// ```
+    pub fn encoded(&self) -> EncodedMessage {
+        EncodedMessage::new(self.clone())
+    }
+
+    /// Formats the message as a StatsD line of the given type, without a trailing
+    /// newline.
+    ///
+    /// StatsD stamps points itself when it flushes, so the timestamp is left out.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::{GraphiteMessage, MetricType};
+    ///
+    /// let msg = GraphiteMessage::new("api.requests", "3");
+    /// assert_eq!(msg.to_statsd(MetricType::Counter), "api.requests:3|c");
+    /// assert_eq!(msg.to_statsd(MetricType::Timer), "api.requests:3|ms");
+    /// ```
+    pub fn to_statsd(&self, metric_type: MetricType) -> String {
+        format!(
+            "{}:{}|{}",
+            self.metric_path,
+            self.value,
+            metric_type.suffix()
+        )
+    }
+
+    /// Formats the message like [`to_statsd`](Self::to_statsd), marked as one of a sample
+    /// taken at `sample_rate`.
+    ///
+    /// A `sample_rate` strictly between 0 and 1 adds the `|@rate` suffix, telling the
+    /// aggregator the point stands for `1 / rate` points: it scales counters up by that
+    /// factor and weighs timers accordingly. The value itself is sent as is. Any other
+    /// rate means nothing was sampled away, so the suffix is left out.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::{GraphiteMessage, MetricType};
+    ///
+    /// let msg = GraphiteMessage::new("api.requests", "1");
+    /// assert_eq!(msg.to_statsd_sampled(MetricType::Counter, 0.1), "api.requests:1|c|@0.1");
+    /// assert_eq!(msg.to_statsd_sampled(MetricType::Counter, 1.0), "api.requests:1|c");
+    /// ```
+    pub fn to_statsd_sampled(&self, metric_type: MetricType, sample_rate: f64) -> String {
+        let mut line = self.to_statsd(metric_type);
+        if sample_rate > 0.0 && sample_rate < 1.0 {
+            line.push_str(&format!("|@{sample_rate}"));
+        }
+        line
+    }
+}
+
+/// The StatsD type a message is sent as by [`GraphiteMessage::to_statsd`] and
+/// [`GraphiteClient::send_statsd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MetricType {
+    /// A count to add to the aggregator's running total, sent as `|c`.
+    Counter,
+
+    /// A value that replaces the previous one, sent as `|g`.
+    Gauge,
+
+    /// A duration in milliseconds, sent as `|ms`.
+    Timer,
+}
+
+impl MetricType {
+    /// Returns the StatsD type suffix, without the leading `|`.
+    fn suffix(self) -> &'static str {
+        match self {
+            MetricType::Counter => "c",
+            MetricType::Gauge => "g",
+            MetricType::Timer => "ms",
         }
     }
 }
 
+/// The unit a [`Duration`] is expressed in by [`GraphiteMessage::from_duration`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DurationUnit {
+    /// Fractional seconds, e.g. `1.25`.
+    #[default]
+    Seconds,
+
+    /// Fractional milliseconds, e.g. `1250` or `0.5`.
+    Milliseconds,
+}
+
 impl fmt::Display for GraphiteMessage {
     /// Formats the message according to the Graphite plaintext protocol.
     ///
@@ -533,68 +4779,68 @@ impl fmt::Display for GraphiteMessage {
     }
 }
 
-/// Error type for Graphite client operations.
-///
-/// `GraphiteError` wraps various error conditions that can occur during client operations,
-/// including connection failures, send failures, and address parsing errors.
-///
-/// # Examples
-///
-/// ```rust
-/// use graphyne::{GraphiteClient, GraphiteError};
-/// use std::time::Duration;
-///
-/// fn try_connect() -> Result<GraphiteClient, GraphiteError> {
-///     GraphiteClient::builder()
-///         .address("127.0.0.1")
-///         .port(2003)
-///         .timeout(Duration::from_millis(100))
-///         .build()
-/// }
-///
-/// match try_connect() {
-///     Ok(client) => println!("Connected successfully"),
-///     Err(e) => eprintln!("Connection failed: {}", e),
-/// }
-/// ```
-#[derive(Clone)]
-pub struct GraphiteError {
-    /// Human-readable error message describing what went wrong.
-    pub msg: String,
-}
+impl FromStr for GraphiteMessage {
+    type Err = GraphiteError;
 
-impl fmt::Display for GraphiteError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.msg)
-    }
-}
+    /// Parses a line in the Graphite plaintext protocol format.
+    ///
+    /// The line is split on whitespace into a metric path, a value, and an optional
+    /// timestamp. When the timestamp is absent the current time is used. A trailing
+    /// newline is accepted, so this round-trips with the `Display` implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graphyne::GraphiteMessage;
+    ///
+    /// let msg: GraphiteMessage = "servers.web01.cpu.usage 45.2 1609459200\n".parse().unwrap();
+    /// assert_eq!(msg.to_string(), "servers.web01.cpu.usage 45.2 1609459200\n");
+    ///
+    /// assert!("missing.value".parse::<GraphiteMessage>().is_err());
+    /// ```
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            let path = line.split_whitespace().next().unwrap_or_default();
+            GraphiteError::invalid_message(
+                path,
+                ValidationRule::Syntax,
+                format!("invalid message line {line:?}: {reason}"),
+            )
+        };
 
-impl fmt::Debug for GraphiteError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "GraphiteError {{ msg: {:?} }}", self.msg)
+        let mut fields = line.split_whitespace();
+        let (Some(metric_path), Some(value)) = (fields.next(), fields.next()) else {
+            return Err(invalid("expected a metric path and a value"));
+        };
+        let timestamp = match fields.next() {
+            Some(timestamp) => timestamp
+                .parse()
+                .map_err(|_| invalid("timestamp is not a non-negative integer"))?,
+            None => unix_now(),
+        };
+        if fields.next().is_some() {
+            return Err(invalid("unexpected trailing fields"));
+        }
+
+        Ok(Self {
+            metric_path: Arc::from(metric_path),
+            value: value.to_string(),
+            timestamp,
+        })
     }
 }
 
-impl std::error::Error for GraphiteError {}
-
-impl From<AddrParseError> for GraphiteError {
-    /// Converts address parsing errors into `GraphiteError`.
-    ///
-    /// This is called when the provided address string cannot be parsed as a valid IP address.
-    fn from(err: AddrParseError) -> Self {
-        GraphiteError {
-            msg: err.to_string(),
-        }
-    }
+/// Returns the current Unix timestamp in seconds, or 0 if the clock is before the epoch.
+fn unix_now() -> u64 {
+    try_unix_now().unwrap_or_default()
 }
 
-impl From<Error> for GraphiteError {
-    /// Converts I/O errors into `GraphiteError`.
-    ///
-    /// This handles connection errors, timeout errors, and write failures.
-    fn from(err: Error) -> Self {
-        GraphiteError {
-            msg: err.to_string(),
-        }
+/// Returns the current Unix timestamp in seconds, failing if the clock is before the epoch.
+fn try_unix_now() -> Result<u64, GraphiteError> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => Ok(elapsed.as_secs()),
+        Err(err) => Err(GraphiteError::ClockSkew {
+            reason: format!("system clock is {:?} before the Unix epoch", err.duration()),
+        }),
     }
 }
@@ -0,0 +1,71 @@
+//! Memory of recently sent lines, for skipping exact resends.
+
+use std::{
+    collections::{HashSet, VecDeque, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+/// Most lines remembered at once; the oldest are forgotten first beyond this.
+const CAPACITY: usize = 4096;
+
+/// Hashes of the lines sent within the last `window`, oldest first.
+#[derive(Debug, Clone)]
+pub(crate) struct RecentSends {
+    /// How long a sent line is remembered.
+    window: Duration,
+
+    /// Line hashes and when each was sent, in send order.
+    sent: VecDeque<(u64, Instant)>,
+
+    /// The hashes in `sent`, for constant-time lookup.
+    hashes: HashSet<u64>,
+}
+
+impl RecentSends {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sent: VecDeque::new(),
+            hashes: HashSet::new(),
+        }
+    }
+
+    /// Returns whether `line` was sent within the window.
+    pub(crate) fn contains(&mut self, line: &str) -> bool {
+        self.forget_expired();
+        self.hashes.contains(&Self::hash(line))
+    }
+
+    /// Remembers `line` as sent now.
+    pub(crate) fn insert(&mut self, line: &str) {
+        self.forget_expired();
+        let hash = Self::hash(line);
+        if !self.hashes.insert(hash) {
+            return;
+        }
+        if self.sent.len() == CAPACITY
+            && let Some((oldest, _)) = self.sent.pop_front()
+        {
+            self.hashes.remove(&oldest);
+        }
+        self.sent.push_back((hash, Instant::now()));
+    }
+
+    /// Drops lines sent longer than `window` ago.
+    fn forget_expired(&mut self) {
+        while let Some(&(hash, sent_at)) = self.sent.front() {
+            if sent_at.elapsed() < self.window {
+                break;
+            }
+            self.sent.pop_front();
+            self.hashes.remove(&hash);
+        }
+    }
+
+    fn hash(line: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        line.hash(&mut hasher);
+        hasher.finish()
+    }
+}
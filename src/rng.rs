@@ -0,0 +1,66 @@
+//! A small, non-cryptographic random number generator.
+//!
+//! Used to spread out time-based behavior (connection lifetime splay, retry jitter) across
+//! a fleet of clients. Quality requirements are minimal, so this avoids pulling in a full
+//! random number crate.
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// An xorshift64* generator.
+#[derive(Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator with a fixed seed, producing a deterministic sequence.
+    pub(crate) fn new(seed: u64) -> Self {
+        // The all-zero state is a fixed point for xorshift, so perturb the seed.
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Creates a generator seeded from the system clock.
+    pub(crate) fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Self::new(nanos as u64 ^ (nanos >> 64) as u64)
+    }
+
+    /// Returns the next value in the sequence.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a value uniformly distributed in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Shuffles `items` in place (Fisher-Yates).
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Returns a duration uniformly distributed in `[0, max]`.
+    pub(crate) fn duration_up_to(&mut self, max: Duration) -> Duration {
+        max.mul_f64(self.next_f64())
+    }
+}
+
+impl fmt::Debug for Rng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The state is meaningless to readers and changes on every draw.
+        f.write_str("Rng")
+    }
+}
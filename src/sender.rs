@@ -0,0 +1,720 @@
+//! Sending from dedicated threads, so producers never wait on the network.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use bon::bon;
+
+use crate::{
+    Batch, DedupePolicy, GraphiteError, GraphiteMessage, MetricSink, batch::ExpiredHook, hook::Hook,
+};
+
+/// Default time between flushes of a [`GraphiteSender`].
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default number of buffered messages at which a [`GraphiteSender`] flushes early.
+const DEFAULT_MAX_BATCH_MESSAGES: usize = 1000;
+
+/// Default number of messages a [`GraphiteSender`] queues before dropping new ones.
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// What a [`Producer`] asks of the sending thread.
+#[derive(Debug)]
+enum Command {
+    /// Buffer a message for the next flush.
+    Send(GraphiteMessage),
+
+    /// Wake up to notice that `stopping` is set.
+    Shutdown,
+}
+
+/// State shared between the handles and the sending thread.
+#[derive(Debug, Default)]
+struct Shared {
+    /// Messages on the queue that the thread hasn't taken yet.
+    queued: AtomicUsize,
+
+    /// Messages dropped because the queue was full or a failed flush left too many.
+    dropped: AtomicU64,
+
+    /// Flushes that failed, leaving their messages for the next one.
+    failed_flushes: AtomicU64,
+
+    /// Messages dropped for exceeding `max_metric_age`.
+    expired: AtomicU64,
+
+    /// Points folded into an earlier buffered point by `coalesce`.
+    coalesced: AtomicU64,
+
+    /// Set once shutdown starts, after which no more messages are accepted.
+    stopping: AtomicBool,
+}
+
+/// A thread owning a client, which sends the metrics [`Producer`] handles queue.
+///
+/// The thread buffers queued messages and flushes them as one batch every
+/// `flush_interval`, or as soon as `max_batch_messages` are buffered, using the client's
+/// usual retry and reconnect logic. Producers only ever put a message on a bounded
+/// queue, so they never wait on the network: when the queue is full the message is
+/// dropped and counted in [`dropped`](Self::dropped).
+///
+/// A failed flush keeps its messages for the next one, up to `queue_capacity` of them,
+/// so a batch the server partly received before the failure may be sent twice. Set
+/// `max_metric_age` to drop those too old to be worth replaying after an outage, and
+/// `coalesce` to fold points sharing a path and timestamp into one while they wait, as
+/// for a [`Batch`]. Dropping the sender, or calling [`shutdown`](Self::shutdown),
+/// flushes whatever was queued before stopping the thread. To have the thread run until
+/// the last producer is dropped instead, [`detach`](Self::detach) it.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, GraphiteMessage, GraphiteSender};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .build()?;
+/// let sender = GraphiteSender::builder().client(client).build();
+///
+/// let producer = sender.producer();
+/// std::thread::spawn(move || {
+///     let _ = producer.send(GraphiteMessage::new("worker.jobs", "1"));
+/// })
+/// .join()
+/// .unwrap();
+///
+/// sender.shutdown()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct GraphiteSender {
+    /// Handle the sender keeps, for handing out clones and requesting shutdown.
+    producer: Producer,
+
+    /// The sending thread.
+    thread: SendingThread,
+}
+
+#[bon]
+impl GraphiteSender {
+    /// Starts the sending thread, which takes ownership of `client`.
+    #[builder]
+    pub fn new<S: MetricSink + Send + 'static>(
+        /// Where the thread sends metrics, such as a [`GraphiteClient`](crate::GraphiteClient).
+        client: S,
+        /// Time between flushes (default: 1 second).
+        #[builder(default = DEFAULT_FLUSH_INTERVAL)]
+        flush_interval: Duration,
+        /// Number of buffered messages that triggers a flush before `flush_interval` is up
+        /// (default: 1000).
+        #[builder(default = DEFAULT_MAX_BATCH_MESSAGES)]
+        max_batch_messages: usize,
+        /// Number of messages that can wait on the queue, and that a failed flush keeps
+        /// for the next one (default: 10000).
+        #[builder(default = DEFAULT_QUEUE_CAPACITY)]
+        queue_capacity: usize,
+        /// Optional age beyond which buffered messages are dropped instead of sent, as
+        /// for [`Batch::max_metric_age`].
+        max_metric_age: Option<Duration>,
+        /// Optional callback receiving the messages dropped by `max_metric_age`, called
+        /// on the sending thread.
+        #[builder(with = |f: impl Fn(&[GraphiteMessage]) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_expired: Option<Hook<ExpiredHook>>,
+        /// Optional policy combining buffered points that share a metric path and
+        /// timestamp, as for [`Batch::coalesce`].
+        coalesce: Option<DedupePolicy>,
+    ) -> Self {
+        let (tx, rx) = mpsc::sync_channel(queue_capacity);
+        let (done_tx, done) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let mut batch = Batch::new();
+        if let Some(max_age) = max_metric_age {
+            batch = batch.max_metric_age(max_age);
+        }
+        if let Some(on_expired) = on_expired {
+            batch = batch.on_expired(move |msgs| on_expired(msgs));
+        }
+        if let Some(policy) = coalesce {
+            batch = batch.coalesce(policy);
+        }
+        let worker = Worker {
+            sink: client,
+            rx,
+            batch,
+            flush_interval,
+            max_batch_messages: max_batch_messages.max(1),
+            queue_capacity,
+            shared: Arc::clone(&shared),
+        };
+        let worker = thread::spawn(move || {
+            // Dropped when the thread finishes, even by panicking.
+            let _done = done_tx;
+            worker.run()
+        });
+        Self {
+            producer: Producer {
+                tx: Arc::new(tx),
+                shared,
+            },
+            thread: SendingThread {
+                worker: Some(worker),
+                done,
+            },
+        }
+    }
+
+    /// Returns a new handle for queueing messages from any thread.
+    pub fn producer(&self) -> Producer {
+        self.producer.clone()
+    }
+
+    /// Returns how many messages are waiting on the queue for the thread to take them.
+    pub fn queue_depth(&self) -> usize {
+        self.producer.shared.queued.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many messages have been dropped, because the queue was full or a
+    /// failed flush left more than `queue_capacity` buffered.
+    pub fn dropped(&self) -> u64 {
+        self.producer.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many flushes have failed.
+    pub fn failed_flushes(&self) -> u64 {
+        self.producer.shared.failed_flushes.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many messages have been dropped for exceeding `max_metric_age`.
+    pub fn expired(&self) -> u64 {
+        self.producer.shared.expired.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many queued points have been folded into an earlier one by
+    /// `coalesce`, and so won't be sent on their own.
+    pub fn coalesced(&self) -> u64 {
+        self.producer.shared.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Flushes every message queued so far and stops the thread, returning the result of
+    /// that final flush.
+    ///
+    /// Producers still held elsewhere can no longer queue messages afterwards.
+    pub fn shutdown(mut self) -> Result<(), GraphiteError> {
+        self.request_stop();
+        self.wait(None)
+    }
+
+    /// Shuts down like [`shutdown`](Self::shutdown), but waits at most `timeout` for the
+    /// final flush.
+    ///
+    /// If the thread is still flushing by then, it is left to finish in the background and
+    /// a [`GraphiteErrorKind::Timeout`](crate::GraphiteErrorKind::Timeout) error is
+    /// returned.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Result<(), GraphiteError> {
+        self.request_stop();
+        self.wait(Some(Instant::now() + timeout))
+    }
+
+    /// Lets the thread run on its own, returning a producer and a handle to the thread.
+    ///
+    /// The thread keeps sending until every producer has been dropped, then flushes
+    /// whatever was queued and finishes with the result of that final flush.
+    /// [`DetachedSender::join`] waits for that, such as before the process exits, and
+    /// [`DetachedSender::shutdown_timeout`] stops the thread without waiting for the
+    /// producers, bounding how long the final flush may take. Dropping the handle leaves
+    /// the thread running. Without a sender, the [`dropped`](Self::dropped) and
+    /// [`failed_flushes`](Self::failed_flushes) counts are no longer available.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteClient, GraphiteMessage, GraphiteSender};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = GraphiteClient::builder()
+    ///     .address("127.0.0.1")
+    ///     .port(2003)
+    ///     .build()?;
+    /// let (metrics, thread) = GraphiteSender::builder().client(client).build().detach();
+    ///
+    /// let _ = metrics.send(GraphiteMessage::new("app.started", "1"));
+    ///
+    /// drop(metrics);
+    /// thread.join()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn detach(mut self) -> (Producer, DetachedSender) {
+        let thread = SendingThread {
+            worker: self.thread.worker.take(),
+            done: std::mem::replace(&mut self.thread.done, mpsc::channel().1),
+        };
+        let handle = DetachedSender {
+            tx: Arc::downgrade(&self.producer.tx),
+            shared: Arc::clone(&self.producer.shared),
+            thread,
+        };
+        // Without its thread, dropping the sender leaves the thread running.
+        (self.producer.clone(), handle)
+    }
+
+    /// Stops accepting messages and tells the thread to flush and stop.
+    pub(crate) fn request_stop(&self) {
+        request_stop(&self.producer.shared, Some(&self.producer.tx));
+    }
+
+    /// Waits until `deadline`, if any, for the thread to finish, returning the result of
+    /// its final flush. Does nothing if the thread has already been waited for.
+    pub(crate) fn wait(&mut self, deadline: Option<Instant>) -> Result<(), GraphiteError> {
+        self.thread.wait(deadline)
+    }
+}
+
+impl Drop for GraphiteSender {
+    /// Flushes queued messages and stops the thread. A failure of the final flush is
+    /// ignored; call [`shutdown`](GraphiteSender::shutdown) to see it.
+    fn drop(&mut self) {
+        if self.thread.worker.is_some() && !thread::panicking() {
+            self.request_stop();
+            let _ = self.wait(None);
+        }
+    }
+}
+
+/// The thread of a [`GraphiteSender`] that was [detached](GraphiteSender::detach), for
+/// waiting on it or stopping it.
+///
+/// The handle doesn't keep the thread's queue open, so the thread still finishes once
+/// the last [`Producer`] is dropped. Dropping the handle leaves the thread running.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, GraphiteMessage, GraphiteSender};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .build()?;
+/// let (metrics, thread) = GraphiteSender::builder().client(client).build().detach();
+///
+/// let worker = std::thread::spawn(move || {
+///     let _ = metrics.send(GraphiteMessage::new("worker.jobs", "1"));
+/// });
+///
+/// // Stop without waiting for every producer to go away.
+/// thread.shutdown_timeout(Duration::from_secs(5))?;
+/// # worker.join().unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DetachedSender {
+    /// The producers' queue, while any producer is left.
+    tx: Weak<SyncSender<Command>>,
+
+    /// State shared with the producers.
+    shared: Arc<Shared>,
+
+    /// The sending thread.
+    thread: SendingThread,
+}
+
+impl DetachedSender {
+    /// Waits for the thread to finish after the last producer is dropped, returning the
+    /// result of its final flush.
+    pub fn join(mut self) -> Result<(), GraphiteError> {
+        self.thread.wait(None)
+    }
+
+    /// Flushes every message queued so far and stops the thread, returning the result of
+    /// that final flush, like [`GraphiteSender::shutdown`].
+    ///
+    /// Producers still held elsewhere can no longer queue messages afterwards.
+    pub fn shutdown(mut self) -> Result<(), GraphiteError> {
+        request_stop(&self.shared, self.tx.upgrade().as_deref());
+        self.thread.wait(None)
+    }
+
+    /// Shuts down like [`shutdown`](Self::shutdown), but waits at most `timeout` for the
+    /// final flush, like [`GraphiteSender::shutdown_timeout`].
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Result<(), GraphiteError> {
+        request_stop(&self.shared, self.tx.upgrade().as_deref());
+        self.thread.wait(Some(Instant::now() + timeout))
+    }
+
+    /// Returns whether the thread has finished.
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .worker
+            .as_ref()
+            .is_none_or(JoinHandle::is_finished)
+    }
+}
+
+/// Stops `shared`'s producers queueing messages and wakes up the thread through `tx`, if
+/// the queue is still open, to flush and stop.
+fn request_stop(shared: &Shared, tx: Option<&SyncSender<Command>>) {
+    shared.stopping.store(true, Ordering::Relaxed);
+    // A full queue wakes the thread up anyway, and a disconnected one means it is gone.
+    if let Some(tx) = tx {
+        let _ = tx.try_send(Command::Shutdown);
+    }
+}
+
+/// A sending thread, until it is joined.
+#[derive(Debug)]
+struct SendingThread {
+    /// The thread, until it is joined.
+    worker: Option<JoinHandle<Result<(), GraphiteError>>>,
+
+    /// Disconnected once the thread has finished.
+    done: Receiver<()>,
+}
+
+impl SendingThread {
+    /// Waits until `deadline`, if any, for the thread to finish, returning the result of
+    /// its final flush. Does nothing if the thread has already been waited for.
+    fn wait(&mut self, deadline: Option<Instant>) -> Result<(), GraphiteError> {
+        let Some(worker) = self.worker.take() else {
+            return Ok(());
+        };
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Err(RecvTimeoutError::Timeout) = self.done.recv_timeout(remaining) {
+                return Err(GraphiteError::send_timeout(None));
+            }
+        }
+        match worker.join() {
+            Ok(res) => res,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// A cheap, cloneable handle for queueing messages on a [`GraphiteSender`].
+#[derive(Debug, Clone)]
+pub struct Producer {
+    /// Queue read by the sending thread, shared by every clone so that a
+    /// [`DetachedSender`] can reach it without keeping it open.
+    tx: Arc<SyncSender<Command>>,
+
+    /// State shared with the sender.
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// Queues `msg` for the sending thread, without waiting.
+    ///
+    /// Hands the message back if the queue is full, in which case it is also counted as
+    /// dropped, or if the sender is shutting down.
+    pub fn send(&self, msg: GraphiteMessage) -> Result<(), GraphiteMessage> {
+        if self.shared.stopping.load(Ordering::Relaxed) {
+            return Err(msg);
+        }
+        // Counted before it is queued, so the thread never takes it off the count first.
+        self.shared.queued.fetch_add(1, Ordering::Relaxed);
+        let res = self.tx.try_send(Command::Send(msg));
+        if res.is_err() {
+            self.shared.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        match res {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(Command::Send(msg))) => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(msg)
+            }
+            Err(TrySendError::Disconnected(Command::Send(msg))) => Err(msg),
+            Err(TrySendError::Full(Command::Shutdown))
+            | Err(TrySendError::Disconnected(Command::Shutdown)) => {
+                unreachable!("producers only queue messages")
+            }
+        }
+    }
+
+    /// Queues each of `msgs` like [`send`](Self::send), without waiting.
+    ///
+    /// Hands back the messages that couldn't be queued, in their original order.
+    pub fn send_batch(
+        &self,
+        msgs: impl IntoIterator<Item = GraphiteMessage>,
+    ) -> Result<(), Vec<GraphiteMessage>> {
+        let rejected: Vec<_> = msgs
+            .into_iter()
+            .filter_map(|msg| self.send(msg).err())
+            .collect();
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(rejected)
+        }
+    }
+}
+
+/// State of the sending thread.
+struct Worker<S> {
+    /// Where messages are sent.
+    sink: S,
+
+    /// Queue fed by the producers.
+    rx: Receiver<Command>,
+
+    /// Messages waiting for the next flush.
+    batch: Batch,
+
+    /// Time between flushes.
+    flush_interval: Duration,
+
+    /// Number of buffered messages that triggers an early flush.
+    max_batch_messages: usize,
+
+    /// Number of messages a failed flush may leave buffered.
+    queue_capacity: usize,
+
+    /// State shared with the handles.
+    shared: Arc<Shared>,
+}
+
+impl<S: MetricSink> Worker<S> {
+    /// Buffers and flushes queued messages until asked to stop or every handle is gone.
+    fn run(mut self) -> Result<(), GraphiteError> {
+        let mut next_flush = Instant::now() + self.flush_interval;
+        loop {
+            let wait = next_flush.saturating_duration_since(Instant::now());
+            let received = self.rx.recv_timeout(wait);
+            if self.shared.stopping.load(Ordering::Relaxed) {
+                if let Ok(Command::Send(msg)) = received {
+                    self.take(msg);
+                }
+                return self.finish();
+            }
+            match received {
+                Ok(Command::Send(msg)) => {
+                    self.take(msg);
+                    if self.batch.len() < self.max_batch_messages {
+                        continue;
+                    }
+                }
+                Ok(Command::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    return self.finish();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            let _ = self.flush();
+            next_flush = Instant::now() + self.flush_interval;
+        }
+    }
+
+    /// Buffers a message taken off the queue.
+    fn take(&mut self, msg: GraphiteMessage) {
+        self.shared.queued.fetch_sub(1, Ordering::Relaxed);
+        self.batch.push_message(msg);
+        self.shared
+            .coalesced
+            .store(self.batch.coalesced(), Ordering::Relaxed);
+    }
+
+    /// Flushes the buffered messages along with any still queued.
+    fn finish(mut self) -> Result<(), GraphiteError> {
+        while let Ok(command) = self.rx.try_recv() {
+            if let Command::Send(msg) = command {
+                self.take(msg);
+            }
+        }
+        self.flush()
+    }
+
+    /// Sends the buffered messages. After a failure those not yet written stay buffered
+    /// for the next flush, except for the oldest beyond `queue_capacity`.
+    fn flush(&mut self) -> Result<(), GraphiteError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let res = self.batch.send(&mut self.sink);
+        self.shared
+            .expired
+            .store(self.batch.expired(), Ordering::Relaxed);
+        let Err(err) = res else {
+            return Ok(());
+        };
+        self.shared.failed_flushes.fetch_add(1, Ordering::Relaxed);
+        let excess = self.batch.len().saturating_sub(self.queue_capacity);
+        if excess > 0 {
+            let kept = self.batch.messages()[excess..].to_vec();
+            self.batch.clear();
+            for msg in kept {
+                self.batch.push_message(msg);
+            }
+            self.shared
+                .dropped
+                .fetch_add(excess as u64, Ordering::Relaxed);
+        }
+        Err(err)
+    }
+}
+
+/// Several [`GraphiteSender`] threads, each with its own connection, for more throughput
+/// than one connection can carry.
+///
+/// [`ShardedProducer`] handles route each message to a worker by a hash of its metric
+/// path, so every point of a path goes through the same queue and connection, in order.
+/// Each worker batches, retries and reconnects on its own, exactly like a
+/// `GraphiteSender`; [`workers`](Self::workers) exposes their queue depths and drop
+/// counts, to tell whether more are needed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, GraphiteMessage, ShardedSender};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let clients = (0..4)
+///     .map(|_| GraphiteClient::builder().address("127.0.0.1").port(2003).build())
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let sender = ShardedSender::builder().clients(clients).build()?;
+///
+/// let producer = sender.producer();
+/// let _ = producer.send(GraphiteMessage::new("collector.points", "1"));
+///
+/// sender.shutdown_timeout(Duration::from_secs(5))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShardedSender {
+    /// One sender per client, in the order the clients were given.
+    workers: Vec<GraphiteSender>,
+}
+
+#[bon]
+impl ShardedSender {
+    /// Starts one sending thread per client, each taking ownership of its client.
+    ///
+    /// The remaining options apply to every worker, as for [`GraphiteSender`].
+    #[builder]
+    pub fn new<S: MetricSink + Send + 'static>(
+        /// Where each worker sends metrics, one worker per client. Must not be empty.
+        clients: Vec<S>,
+        /// Time between flushes (default: 1 second).
+        #[builder(default = DEFAULT_FLUSH_INTERVAL)]
+        flush_interval: Duration,
+        /// Number of buffered messages that triggers a worker's flush before
+        /// `flush_interval` is up (default: 1000).
+        #[builder(default = DEFAULT_MAX_BATCH_MESSAGES)]
+        max_batch_messages: usize,
+        /// Size of each worker's queue (default: 10000).
+        #[builder(default = DEFAULT_QUEUE_CAPACITY)]
+        queue_capacity: usize,
+        /// Optional age beyond which a worker drops buffered messages instead of sending
+        /// them.
+        max_metric_age: Option<Duration>,
+        /// Optional callback receiving the messages dropped by `max_metric_age`, called
+        /// on the thread of the worker that dropped them.
+        #[builder(with = |f: impl Fn(&[GraphiteMessage]) + Send + Sync + 'static| Hook::new(Arc::new(f)))]
+        on_expired: Option<Hook<ExpiredHook>>,
+        /// Optional policy combining points a worker has buffered that share a metric
+        /// path and timestamp.
+        coalesce: Option<DedupePolicy>,
+    ) -> Result<Self, GraphiteError> {
+        if clients.is_empty() {
+            return Err(GraphiteError::invalid_config(
+                "a sharded sender needs at least one client",
+            ));
+        }
+        let workers = clients
+            .into_iter()
+            .map(|client| {
+                GraphiteSender::builder()
+                    .client(client)
+                    .flush_interval(flush_interval)
+                    .max_batch_messages(max_batch_messages)
+                    .queue_capacity(queue_capacity)
+                    .maybe_max_metric_age(max_metric_age)
+                    .maybe_on_expired(
+                        on_expired
+                            .clone()
+                            .map(|on_expired| move |msgs: &[GraphiteMessage]| on_expired(msgs)),
+                    )
+                    .maybe_coalesce(coalesce)
+                    .build()
+            })
+            .collect();
+        Ok(Self { workers })
+    }
+
+    /// Returns a new handle for queueing messages from any thread.
+    pub fn producer(&self) -> ShardedProducer {
+        ShardedProducer {
+            producers: self.workers.iter().map(GraphiteSender::producer).collect(),
+        }
+    }
+
+    /// Returns the workers, in the order their clients were given, for their queue depths
+    /// and their dropped, expired and coalesced counts.
+    pub fn workers(&self) -> &[GraphiteSender] {
+        &self.workers
+    }
+
+    /// Returns the index of the worker that messages for `metric_path` are routed to.
+    pub fn worker_index(&self, metric_path: &str) -> usize {
+        shard(metric_path, self.workers.len())
+    }
+
+    /// Flushes every worker's queue and stops the threads, waiting at most `timeout` in
+    /// total.
+    ///
+    /// All workers drain at once. Returns the first error among their final flushes, or a
+    /// [`GraphiteErrorKind::Timeout`](crate::GraphiteErrorKind::Timeout) error if some are
+    /// still flushing when `timeout` is up; those are left to finish in the background.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Result<(), GraphiteError> {
+        let deadline = Instant::now() + timeout;
+        for worker in &self.workers {
+            worker.request_stop();
+        }
+        let mut res = Ok(());
+        for worker in &mut self.workers {
+            let outcome = worker.wait(Some(deadline));
+            res = res.and(outcome);
+        }
+        res
+    }
+}
+
+/// A cheap, cloneable handle for queueing messages on a [`ShardedSender`].
+#[derive(Debug, Clone)]
+pub struct ShardedProducer {
+    /// One handle per worker, in worker order.
+    producers: Arc<[Producer]>,
+}
+
+impl ShardedProducer {
+    /// Queues `msg` for the worker its metric path routes to, without waiting.
+    ///
+    /// Hands the message back if that worker's queue is full, in which case it is also
+    /// counted in the worker's drops, or if the sender is shutting down.
+    pub fn send(&self, msg: GraphiteMessage) -> Result<(), GraphiteMessage> {
+        let index = shard(&msg.metric_path, self.producers.len());
+        self.producers[index].send(msg)
+    }
+}
+
+/// Returns which of `count` workers handles `metric_path`.
+fn shard(metric_path: &str, count: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    metric_path.hash(&mut hasher);
+    (hasher.finish() % count as u64) as usize
+}
@@ -0,0 +1,459 @@
+//! Sharding metrics across carbon-cache instances the way carbon-relay does.
+
+use std::{collections::HashSet, time::Duration};
+
+use bon::bon;
+
+use crate::{
+    DestinationStats, GraphiteClient, GraphiteError, GraphiteErrorKind, GraphiteMessage,
+    health::{DEFAULT_REPROBE_INTERVAL, DestinationHealth, HealthPolicy},
+    md5,
+};
+
+/// Number of ring positions each destination occupies, matching carbon.
+const REPLICA_COUNT: usize = 100;
+
+/// Consistent-hash ring used to assign metric paths to destinations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HashRing {
+    /// The MD5-based ring carbon-relay uses with `RELAY_METHOD = consistent-hashing` and
+    /// the default `carbon_ch` hash type.
+    ///
+    /// Each destination is placed on the ring under its host and instance name, exactly
+    /// as carbon does, so metrics land on the instance graphite-web expects to find them
+    /// on, as long as the destinations, instance names and their order match carbon's
+    /// `DESTINATIONS`.
+    #[default]
+    CarbonCh,
+}
+
+/// Positions of destinations on a consistent-hash ring.
+#[derive(Debug, Clone)]
+struct Ring {
+    /// Ring positions and the destination index at each, sorted by position.
+    entries: Vec<(u32, usize)>,
+}
+
+impl Ring {
+    /// Builds a `carbon_ch` ring from each destination's node key.
+    fn carbon_ch(keys: &[String]) -> Self {
+        let mut taken = HashSet::new();
+        let mut entries = Vec::with_capacity(keys.len() * REPLICA_COUNT);
+        for (node, key) in keys.iter().enumerate() {
+            for replica in 0..REPLICA_COUNT {
+                // Carbon resolves collisions by probing forward, in insertion order.
+                let mut position = Self::position(&format!("{key}:{replica}"));
+                while !taken.insert(position) {
+                    position += 1;
+                }
+                entries.push((position, node));
+            }
+        }
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    /// Returns the ring position of `key`: the first 16 bits of its MD5 digest.
+    fn position(key: &str) -> u32 {
+        let digest = md5::digest(key.as_bytes());
+        u32::from(digest[0]) << 8 | u32::from(digest[1])
+    }
+
+    /// Returns the destination index owning `key`.
+    fn node(&self, key: &str) -> usize {
+        self.entries[self.index(key)].1
+    }
+
+    /// Returns up to `count` distinct destination indexes for `key`, walking the ring
+    /// from its owner the way carbon's `get_nodes` does.
+    fn nodes(&self, key: &str, count: usize) -> Vec<usize> {
+        let mut nodes = Vec::with_capacity(count);
+        let start = self.index(key);
+        for offset in 0..self.entries.len() {
+            if nodes.len() == count {
+                break;
+            }
+            let node = self.entries[(start + offset) % self.entries.len()].1;
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+        }
+        nodes
+    }
+
+    /// Returns the index into `entries` of the first position at or after `key`'s.
+    fn index(&self, key: &str) -> usize {
+        let position = Self::position(key);
+        self.entries.partition_point(|&(at, _)| at < position) % self.entries.len()
+    }
+}
+
+/// How a batch was delivered by [`ShardedGraphiteClient::send_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ShardedBatchResult {
+    /// Total bytes written, summed over every replica.
+    pub bytes: usize,
+
+    /// Outcome of the batch sent to each destination that had messages routed to it.
+    pub replicas: Vec<ReplicaOutcome>,
+
+    /// Messages delivered to at least `min_successful_replicas` replicas but not all of
+    /// them, i.e. running degraded.
+    pub degraded: usize,
+
+    /// Messages delivered to fewer than `min_successful_replicas` replicas.
+    pub failed: usize,
+}
+
+/// Outcome of the per-destination batch within a [`ShardedBatchResult`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReplicaOutcome {
+    /// Index of the destination, in the order the destinations were given.
+    pub destination: usize,
+
+    /// Number of messages routed to the destination.
+    pub messages: usize,
+
+    /// Why the destination's batch failed, if it did.
+    pub error: Option<GraphiteErrorKind>,
+}
+
+/// A client that routes each metric to one of several carbon-cache instances by its path.
+///
+/// This lets applications write straight to a carbon-cache cluster without a relay tier.
+/// Metrics are assigned with the same consistent-hash ring carbon-relay uses (see
+/// [`HashRing`]), and each destination has its own [`GraphiteClient`] with independent
+/// connection and retry state.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteMessage, HashRing, ShardedGraphiteClient};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = ShardedGraphiteClient::builder()
+///     .destinations(&[("10.0.0.1:2003", Some("a")), ("10.0.0.2:2003", Some("b"))])
+///     .hash_ring(HashRing::CarbonCh)
+///     .build()?;
+///
+/// client.send_message(&GraphiteMessage::new("servers.web01.cpu.usage", "45.2"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShardedGraphiteClient {
+    /// One client per destination, in the order given.
+    clients: Vec<GraphiteClient>,
+
+    /// Assignment of metric paths to entries of `clients`.
+    ring: Ring,
+
+    /// Number of distinct destinations each message is written to.
+    replication_factor: usize,
+
+    /// Replicas that must accept a message for it to count as delivered.
+    min_successful_replicas: usize,
+
+    /// Health of each entry of `clients`.
+    health: Vec<DestinationHealth>,
+
+    /// When a destination is skipped as unhealthy.
+    health_policy: HealthPolicy,
+}
+
+#[bon]
+impl ShardedGraphiteClient {
+    /// Creates a sharded client, connecting to every destination.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GraphiteErrorKind::InvalidConfig`] if there are no destinations, a
+    /// destination isn't a valid `host:port`, two destinations share a host and instance
+    /// name, or `min_successful_replicas` isn't between 1 and `replication_factor`, and
+    /// otherwise fails like [`GraphiteClient::builder`] if any destination can't be
+    /// connected to.
+    #[builder]
+    pub fn new(
+        /// Destinations as `host:port` and an optional carbon instance name, in the same
+        /// order as carbon's `DESTINATIONS`.
+        #[builder(with = |destinations: &[(&str, Option<&str>)]| {
+            destinations
+                .iter()
+                .map(|(target, instance)| (target.to_string(), instance.map(str::to_string)))
+                .collect()
+        })]
+        destinations: Vec<(String, Option<String>)>,
+        /// Consistent-hash ring used to pick a destination (default: [`HashRing::CarbonCh`]).
+        #[builder(default)]
+        hash_ring: HashRing,
+        /// `send_retries` for every destination's client.
+        send_retries: Option<u8>,
+        /// `connect_retries` for every destination's client.
+        connect_retries: Option<u8>,
+        /// `timeout` for every destination's client.
+        timeout: Option<Duration>,
+        /// Number of distinct destinations each message is written to, like carbon's
+        /// `REPLICATION_FACTOR` (default: 1).
+        ///
+        /// Each message goes to the destination owning its path and the next
+        /// `replication_factor - 1` distinct destinations on the ring. With fewer
+        /// destinations than that, every destination gets a copy.
+        #[builder(default = 1)]
+        replication_factor: usize,
+        /// Number of replicas that must accept a message for a send to succeed
+        /// (default: 1).
+        #[builder(default = 1)]
+        min_successful_replicas: usize,
+        /// Consecutive failed batches after which a destination is skipped, until a probe
+        /// succeeds. Messages routed to a skipped destination are reported as failing on
+        /// that replica with [`GraphiteErrorKind::CircuitOpen`] rather than rerouted, so
+        /// they never land on a node graphite-web won't look on. When unset, every
+        /// destination is attempted on every send.
+        unhealthy_threshold: Option<u32>,
+        /// How long an unhealthy destination is skipped before a single batch probes it
+        /// again (default: 30 seconds).
+        #[builder(default = DEFAULT_REPROBE_INTERVAL)]
+        reprobe_interval: Duration,
+    ) -> Result<Self, GraphiteError> {
+        if destinations.is_empty() {
+            return Err(GraphiteError::invalid_config(
+                "a sharded client needs at least one destination",
+            ));
+        }
+        if min_successful_replicas == 0 || min_successful_replicas > replication_factor {
+            return Err(GraphiteError::invalid_config(format!(
+                "min_successful_replicas {min_successful_replicas} must be \
+                     between 1 and replication_factor {replication_factor}"
+            )));
+        }
+        let mut keys = Vec::with_capacity(destinations.len());
+        let mut clients = Vec::with_capacity(destinations.len());
+        for (target, instance) in &destinations {
+            let (host, port) = parse_destination(target)?;
+            let key = node_key(host, instance.as_deref());
+            if keys.contains(&key) {
+                return Err(GraphiteError::invalid_config(format!(
+                    "duplicate destination {key}"
+                )));
+            }
+            keys.push(key);
+            clients.push(
+                GraphiteClient::builder()
+                    .address(host)
+                    .port(port)
+                    .maybe_send_retries(send_retries)
+                    .maybe_connect_retries(connect_retries)
+                    .maybe_timeout(timeout)
+                    .build()?,
+            );
+        }
+        let ring = match hash_ring {
+            HashRing::CarbonCh => Ring::carbon_ch(&keys),
+        };
+        Ok(Self {
+            health: vec![DestinationHealth::default(); clients.len()],
+            clients,
+            ring,
+            replication_factor,
+            min_successful_replicas,
+            health_policy: HealthPolicy {
+                threshold: unhealthy_threshold.map(|threshold| threshold.max(1)),
+                reprobe_interval,
+            },
+        })
+    }
+
+    /// Returns the index, into the destinations as given, of the destination `metric_path`
+    /// is routed to.
+    pub fn destination_index(&self, metric_path: &str) -> usize {
+        self.ring.node(metric_path)
+    }
+
+    /// Returns the indexes of every destination `metric_path` is replicated to, the
+    /// owning destination first.
+    pub fn destination_indexes(&self, metric_path: &str) -> Vec<usize> {
+        self.ring.nodes(metric_path, self.replication_factor)
+    }
+
+    /// Returns counters and health for each destination, in the order the destinations
+    /// were given.
+    ///
+    /// Each batch sent to a destination counts as one send.
+    pub fn destination_stats(&self) -> Vec<DestinationStats> {
+        self.clients
+            .iter()
+            .zip(&self.health)
+            .map(|(client, health)| health.stats(self.health_policy, client))
+            .collect()
+    }
+
+    /// Sends a metric message to every replica its path is routed to.
+    ///
+    /// Returns the bytes written summed over the replicas, or an error if fewer than
+    /// `min_successful_replicas` accepted it.
+    pub fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        self.send_batch_message(std::slice::from_ref(msg))
+    }
+
+    /// Sends a batch of metric messages, grouped into one batch per destination.
+    ///
+    /// Returns the bytes written summed over the replicas, or the first destination error
+    /// if any message reached fewer than `min_successful_replicas` replicas. Use
+    /// [`send_batch`](Self::send_batch) to see per-replica outcomes.
+    pub fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        let (result, first_err) = self.send_grouped(msgs);
+        match first_err {
+            Some(err) if result.failed > 0 => Err(err),
+            _ => Ok(result.bytes),
+        }
+    }
+
+    /// Sends a batch of metric messages, grouped into one batch per destination, and
+    /// reports how each destination fared.
+    ///
+    /// Messages keep their relative order within each destination's batch, and every
+    /// destination is attempted even if another fails, so a failure on one replica
+    /// doesn't stop the others. Destination failures are reported in the result rather
+    /// than as an error, so operators can see when they're running degraded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use graphyne::{GraphiteMessage, ShardedGraphiteClient};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = ShardedGraphiteClient::builder()
+    ///     .destinations(&[
+    ///         ("10.0.0.1:2003", Some("a")),
+    ///         ("10.0.0.2:2003", Some("b")),
+    ///         ("10.0.0.3:2003", Some("c")),
+    ///     ])
+    ///     .replication_factor(2)
+    ///     .build()?;
+    ///
+    /// let result = client.send_batch(&[GraphiteMessage::new("app.requests", "42")])?;
+    /// if result.degraded > 0 {
+    ///     eprintln!("running degraded: {:?}", result.replicas);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_batch(
+        &mut self,
+        msgs: &[GraphiteMessage],
+    ) -> Result<ShardedBatchResult, GraphiteError> {
+        Ok(self.send_grouped(msgs).0)
+    }
+
+    /// Sends `msgs` to their replicas, returning the result and the first destination
+    /// error, if any.
+    fn send_grouped(
+        &mut self,
+        msgs: &[GraphiteMessage],
+    ) -> (ShardedBatchResult, Option<GraphiteError>) {
+        let mut batches = vec![Vec::new(); self.clients.len()];
+        let routes: Vec<_> = msgs
+            .iter()
+            .map(|msg| {
+                let nodes = self.destination_indexes(&msg.metric_path);
+                for &node in &nodes {
+                    batches[node].push(msg.clone());
+                }
+                nodes
+            })
+            .collect();
+
+        let mut result = ShardedBatchResult {
+            bytes: 0,
+            replicas: Vec::new(),
+            degraded: 0,
+            failed: 0,
+        };
+        let mut first_err = None;
+        let mut succeeded = vec![false; self.clients.len()];
+        let destinations = self.clients.iter_mut().zip(&mut self.health).zip(batches);
+        for (destination, ((client, health), batch)) in destinations.enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+            let sent = health.send(self.health_policy, client, |client| {
+                client.send_batch_message(&batch)
+            });
+            let error = match sent {
+                Ok(sent) => {
+                    result.bytes += sent;
+                    succeeded[destination] = true;
+                    None
+                }
+                Err(err) => {
+                    let kind = err.kind();
+                    first_err.get_or_insert(err);
+                    Some(kind)
+                }
+            };
+            result.replicas.push(ReplicaOutcome {
+                destination,
+                messages: batch.len(),
+                error,
+            });
+        }
+
+        for nodes in routes {
+            let delivered = nodes.iter().filter(|&&node| succeeded[node]).count();
+            if delivered < self.min_successful_replicas.min(nodes.len()) {
+                result.failed += 1;
+            } else if delivered < nodes.len() {
+                result.degraded += 1;
+            }
+        }
+        (result, first_err)
+    }
+}
+
+/// Splits a `host:port` destination, accepting a bracketed IPv6 host.
+fn parse_destination(target: &str) -> Result<(&str, u16), GraphiteError> {
+    let invalid = || {
+        GraphiteError::invalid_config(format!(
+            "invalid destination {target:?}, expected host:port"
+        ))
+    };
+    let (host, port) = target.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let port = port.parse().map_err(|_| invalid())?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host, port))
+}
+
+/// Returns the ring key carbon uses for a destination: the Python `repr` of its
+/// `(host, instance)` tuple.
+fn node_key(host: &str, instance: Option<&str>) -> String {
+    let instance = instance.map_or_else(|| "None".to_string(), py_str_repr);
+    format!("({}, {instance})", py_str_repr(host))
+}
+
+/// Quotes `s` the way Python's `repr` does for a plain string.
+fn py_str_repr(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        if c == '\\' || c == quote {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push(quote);
+    out
+}
@@ -0,0 +1,105 @@
+//! The send interface shared by every client.
+
+use crate::{
+    FanoutGraphiteClient, GraphiteClient, GraphiteError, GraphiteMessage, ShardedGraphiteClient,
+    SyncGraphiteClient,
+};
+
+/// Something metric messages can be sent to.
+///
+/// Implemented by [`GraphiteClient`], [`FanoutGraphiteClient`],
+/// [`ShardedGraphiteClient`] and [`SyncGraphiteClient`] (also through a shared
+/// reference), so code that emits metrics can be generic over where they go. In tests,
+/// pass a [`GraphiteClient::null`] client or an implementation of your own.
+///
+/// # Examples
+///
+/// ```rust
+/// use graphyne::{GraphiteClient, GraphiteError, GraphiteMessage, MetricSink};
+///
+/// fn report_queue_depth(sink: &mut impl MetricSink, depth: usize) -> Result<(), GraphiteError> {
+///     sink.send_message(&GraphiteMessage::new("app.queue.depth", &depth.to_string()))?;
+///     Ok(())
+/// }
+///
+/// let mut client = GraphiteClient::null();
+/// report_queue_depth(&mut client, 3).unwrap();
+/// assert_eq!(client.sent_messages().len(), 1);
+/// ```
+pub trait MetricSink {
+    /// Sends a single metric message, returning the number of bytes written.
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError>;
+
+    /// Sends several metric messages, returning the number of bytes written.
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError>;
+}
+
+impl MetricSink for GraphiteClient {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        GraphiteClient::send_message(self, msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        GraphiteClient::send_batch_message(self, msgs)
+    }
+}
+
+impl MetricSink for FanoutGraphiteClient {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        FanoutGraphiteClient::send_message(self, msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        FanoutGraphiteClient::send_batch_message(self, msgs)
+    }
+}
+
+impl MetricSink for ShardedGraphiteClient {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        ShardedGraphiteClient::send_message(self, msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        ShardedGraphiteClient::send_batch_message(self, msgs)
+    }
+}
+
+impl MetricSink for SyncGraphiteClient {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        SyncGraphiteClient::send_message(self, msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        SyncGraphiteClient::send_batch_message(self, msgs)
+    }
+}
+
+impl MetricSink for &SyncGraphiteClient {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        SyncGraphiteClient::send_message(self, msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        SyncGraphiteClient::send_batch_message(self, msgs)
+    }
+}
+
+impl<S: MetricSink + ?Sized> MetricSink for &mut S {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        (**self).send_message(msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        (**self).send_batch_message(msgs)
+    }
+}
+
+impl<S: MetricSink + ?Sized> MetricSink for Box<S> {
+    fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        (**self).send_message(msg)
+    }
+
+    fn send_batch_message(&mut self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        (**self).send_batch_message(msgs)
+    }
+}
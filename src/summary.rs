@@ -0,0 +1,84 @@
+//! Emission of a distribution as several series sharing a timestamp.
+
+use std::sync::Arc;
+
+use crate::{GraphiteMessage, unix_now};
+
+/// Statistics of a distribution, such as request latencies over a flush interval.
+///
+/// [`messages`](Self::messages) fans the summary out into one series per statistic under
+/// a base path, with the suffixes `.count`, `.sum`, `.min`, `.max` and `.p95`. Every
+/// message gets the same timestamp, so the series always line up in time.
+///
+/// # Examples
+///
+/// ```rust
+/// use graphyne::Summary;
+///
+/// let summary = Summary::from_values(&[12.0, 15.0, 11.0, 40.0]).unwrap();
+/// let msgs = summary.messages("api.latency");
+/// assert_eq!(msgs.len(), 5);
+/// assert!(msgs[3].to_string().starts_with("api.latency.max 40 "));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// Number of values.
+    pub count: u64,
+
+    /// Sum of the values.
+    pub sum: f64,
+
+    /// Smallest value.
+    pub min: f64,
+
+    /// Largest value.
+    pub max: f64,
+
+    /// 95th percentile of the values.
+    pub p95: f64,
+}
+
+impl Summary {
+    /// Computes the summary of `values`, or returns `None` if there are none.
+    ///
+    /// The percentile is the nearest-rank one: the smallest value at least 95% of the
+    /// values are less than or equal to.
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let (&min, &max) = (sorted.first()?, sorted.last()?);
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Some(Self {
+            count: sorted.len() as u64,
+            sum: sorted.iter().sum(),
+            min,
+            max,
+            p95: sorted[rank.saturating_sub(1)],
+        })
+    }
+
+    /// Returns one message per statistic under `base_path`, all stamped with the current
+    /// time.
+    pub fn messages(&self, base_path: &str) -> Vec<GraphiteMessage> {
+        self.messages_at(base_path, unix_now())
+    }
+
+    /// Returns one message per statistic under `base_path`, all stamped with `timestamp`
+    /// in seconds since the Unix epoch.
+    pub fn messages_at(&self, base_path: &str, timestamp: u64) -> Vec<GraphiteMessage> {
+        [
+            ("count", self.count.to_string()),
+            ("sum", self.sum.to_string()),
+            ("min", self.min.to_string()),
+            ("max", self.max.to_string()),
+            ("p95", self.p95.to_string()),
+        ]
+        .into_iter()
+        .map(|(suffix, value)| GraphiteMessage {
+            metric_path: Arc::from(format!("{base_path}.{suffix}")),
+            value,
+            timestamp,
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,161 @@
+//! A client shared between threads behind a lock.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bon::bon;
+
+use crate::{GraphiteClient, GraphiteError, GraphiteMessage};
+
+/// Longest pause between attempts to take the lock while waiting with a timeout.
+const MAX_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A [`GraphiteClient`] that any number of threads can send through.
+///
+/// Clones share one client and its connection, and every method takes `&self`, so code
+/// holding only a shared reference can still emit metrics. Cloning only bumps a
+/// reference count, which suits handler state in web frameworks that require `Clone`.
+/// The client, with its connection and statistics, is dropped along with the last clone,
+/// closing the connection as usual.
+///
+/// # Locking
+///
+/// Sends take turns through a mutex around the whole client, so lines from different
+/// threads never interleave and only one thread touches the connection at a time:
+///
+/// - The thread whose send finds the connection broken is the one that reconnects, and
+///   it does so while holding the lock. Threads arriving meanwhile wait and then write
+///   over the new connection rather than each dialing their own.
+/// - A send holds the lock for as long as it takes, including its reconnects and
+///   retries, so one stalled send holds up every other thread. Set `lock_timeout` to have
+///   them give up with a [`GraphiteErrorKind::Timeout`](crate::GraphiteErrorKind::Timeout)
+///   error instead.
+/// - A thread that panicked while sending doesn't disable the client: the next sender
+///   takes over the poisoned lock and reconnects before writing, in case the panic left
+///   half a line on the connection.
+///
+/// For senders that must never wait, use a [`GraphiteSender`](crate::GraphiteSender).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphyne::{GraphiteClient, GraphiteMessage, SyncGraphiteClient};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = GraphiteClient::builder()
+///     .address("127.0.0.1")
+///     .port(2003)
+///     .build()?;
+/// let shared = SyncGraphiteClient::builder()
+///     .client(client)
+///     .lock_timeout(Duration::from_millis(50))
+///     .build();
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|worker| {
+///         let shared = shared.clone();
+///         std::thread::spawn(move || {
+///             let path = format!("workers.{worker}.alive");
+///             shared.send_message(&GraphiteMessage::new(&path, "1"))
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyncGraphiteClient {
+    /// The shared client.
+    client: Arc<Mutex<GraphiteClient>>,
+
+    /// How long to wait for the lock before giving up, if at all.
+    lock_timeout: Option<Duration>,
+}
+
+#[bon]
+impl SyncGraphiteClient {
+    /// Wraps `client` for sharing between threads.
+    #[builder]
+    pub fn new(
+        /// The client to share.
+        client: GraphiteClient,
+        /// How long a call waits for another thread's send to finish before failing with
+        /// a timeout. When unset, calls wait as long as it takes.
+        lock_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            lock_timeout,
+        }
+    }
+
+    /// Sends a single metric message like [`GraphiteClient::send_message`].
+    pub fn send_message(&self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+        self.lock()?.send_message(msg)
+    }
+
+    /// Sends several metric messages like [`GraphiteClient::send_batch_message`].
+    pub fn send_batch_message(&self, msgs: &[GraphiteMessage]) -> Result<usize, GraphiteError> {
+        self.lock()?.send_batch_message(msgs)
+    }
+
+    /// Calls `f` with exclusive access to the client, for anything not wrapped here.
+    ///
+    /// Fails only if `lock_timeout` runs out before the lock is free.
+    pub fn with_client<T>(
+        &self,
+        f: impl FnOnce(&mut GraphiteClient) -> T,
+    ) -> Result<T, GraphiteError> {
+        Ok(f(&mut *self.lock()?))
+    }
+
+    /// Takes the lock, waiting at most `lock_timeout` if set.
+    fn lock(&self) -> Result<MutexGuard<'_, GraphiteClient>, GraphiteError> {
+        let Some(timeout) = self.lock_timeout else {
+            return Ok(self
+                .client
+                .lock()
+                .unwrap_or_else(|poisoned| self.recover(poisoned)));
+        };
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_micros(10);
+        loop {
+            match self.client.try_lock() {
+                Ok(guard) => return Ok(guard),
+                Err(TryLockError::Poisoned(poisoned)) => return Ok(self.recover(poisoned)),
+                Err(TryLockError::WouldBlock) if Instant::now() >= deadline => {
+                    return Err(GraphiteError::send_timeout(None));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    thread::sleep(delay.min(deadline.saturating_duration_since(Instant::now())));
+                    delay = (delay * 2).min(MAX_LOCK_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    /// Takes over the lock from a thread that panicked while holding it.
+    fn recover<'a>(
+        &self,
+        poisoned: PoisonError<MutexGuard<'a, GraphiteClient>>,
+    ) -> MutexGuard<'a, GraphiteClient> {
+        let mut client = poisoned.into_inner();
+        self.client.clear_poison();
+        // The panic may have cut a write short, so start over on a fresh connection.
+        client.connection_dirty = true;
+        client
+    }
+}
+
+impl From<GraphiteClient> for SyncGraphiteClient {
+    fn from(client: GraphiteClient) -> Self {
+        Self::builder().client(client).build()
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use graphyne::{GraphiteClient, GraphiteMessage};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Counts the allocations made by each thread, so the server thread doesn't count.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    #[test]
+    fn test_send_message_does_not_allocate() {
+        let listener = TcpListener::bind("127.0.0.1:20104").unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            while stream.read(&mut buf).is_ok_and(|n| n > 0) {}
+        });
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20104)
+            .build()
+            .unwrap();
+        let msg = GraphiteMessage::new("test.allocations", "42");
+        client.send_message(&msg).unwrap();
+
+        let before = allocations();
+        for _ in 0..1000 {
+            client.send_message(&msg).unwrap();
+        }
+        assert_eq!(allocations() - before, 0);
+    }
+}
@@ -1,7 +1,17 @@
+#![cfg(not(feature = "stub"))]
+
 #[cfg(test)]
 mod tests {
-    use graphyne::GraphiteClient;
+    use graphyne::{
+        AddressSelection, Batch, BreakerState, DedupePolicy, Delivery, EncodedMessage,
+        FanoutGraphiteClient, FanoutPolicy, GraphiteClient, GraphiteError, GraphiteErrorKind,
+        GraphiteMessage, GraphiteSender, HashRing, Jitter, MessageOutcome, MetricSink, MetricType,
+        NonFinitePolicy, ReconnectPolicy, SendStatus, ShardedGraphiteClient, ShardedSender,
+        SyncGraphiteClient, ValidationRule,
+    };
+    use std::io::{Read, Write};
     use std::net::TcpListener;
+    use std::sync::{Arc, Mutex, mpsc};
     use std::time::Duration;
 
     // Dummy listener that accepts connections
@@ -25,6 +35,53 @@ mod tests {
         }
     }
 
+    // Listener that accepts a single connection and reports everything written to it
+    struct RecordingGraphiteServer {
+        rx: mpsc::Receiver<String>,
+    }
+
+    impl RecordingGraphiteServer {
+        fn start(port: u16) -> Self {
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+                while let Ok((mut stream, _)) = listener.accept() {
+                    let mut data = String::new();
+                    let _ = stream.read_to_string(&mut data);
+                    if tx.send(data).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            // pause to start -- seems to be a race without waiting
+            std::thread::sleep(Duration::from_millis(50));
+
+            Self { rx }
+        }
+
+        // Waits for the next connection to close and returns what was received on it
+        fn received(&self) -> String {
+            self.rx.recv_timeout(Duration::from_secs(5)).unwrap()
+        }
+    }
+
+    // Numbered test metrics under one prefix, such as `test.series.1 1 1600000000`
+    #[derive(Clone, Copy)]
+    struct Series(&'static str);
+
+    impl Series {
+        // Line number `i` of the series, without the newline
+        fn line(&self, i: usize) -> String {
+            format!("{}.{i} {i} 1600000000", self.0)
+        }
+
+        // Message parsed from line number `i`
+        fn msg(&self, i: usize) -> GraphiteMessage {
+            self.line(i).parse().unwrap()
+        }
+    }
+
     #[test]
     fn test_client_builder_defaults() {
         let port = 20031;
@@ -114,4 +171,3513 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_connect_deadline_bounds_build() {
+        let deadline = Duration::from_millis(200);
+        let start = std::time::Instant::now();
+        let result = GraphiteClient::builder()
+            .address("10.255.255.1")
+            .port(2003)
+            .retries(3)
+            .timeout(Duration::from_secs(5))
+            .connect_deadline(deadline)
+            .build();
+
+        // Some networks refuse or intercept this address rather than black-holing it, so
+        // only the bound on elapsed time is asserted.
+        let _ = result;
+        assert!(start.elapsed() < deadline + Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_fixed_timestamp_overrides_message_timestamp() {
+        let port = 20035;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.single", "1"))
+            .unwrap();
+        client
+            .send_batch_message(&[
+                GraphiteMessage::new("test.batch.a", "2"),
+                GraphiteMessage::new("test.batch.b", "3"),
+            ])
+            .unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.single 1 1600000000\n\
+             test.batch.a 2 1600000000\n\
+             test.batch.b 3 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_update_target_switches_connection() {
+        let old_server = RecordingGraphiteServer::start(20036);
+        let new_server = RecordingGraphiteServer::start(20037);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20036)
+            .build()
+            .unwrap();
+        client.update_target("127.0.0.1:20037").unwrap();
+        assert_eq!(old_server.received(), "");
+
+        client
+            .send_message(&GraphiteMessage::new("test.migrated", "1"))
+            .unwrap();
+        drop(client);
+
+        assert!(new_server.received().starts_with("test.migrated 1 "));
+    }
+
+    #[test]
+    fn test_update_target_keeps_old_connection_on_failure() {
+        let server = RecordingGraphiteServer::start(20038);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20038)
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        assert!(client.update_target("127.0.0.1:6969").is_err());
+
+        client
+            .send_message(&GraphiteMessage::new("test.unchanged", "1"))
+            .unwrap();
+        drop(client);
+
+        assert!(server.received().starts_with("test.unchanged 1 "));
+    }
+
+    #[test]
+    fn test_hostname_prefix() {
+        let port = 20039;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .hostname_prefix("servers")
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("cpu.usage", "45"))
+            .unwrap();
+        drop(client);
+
+        let expected = format!("servers.{}.cpu.usage 45 ", graphyne::hostname_segment());
+        assert!(server.received().starts_with(&expected));
+    }
+
+    #[test]
+    fn test_max_connection_lifetime_redials() {
+        let port = 20040;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .max_connection_lifetime(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.first", "1"))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        client
+            .send_message(&GraphiteMessage::new("test.second", "2"))
+            .unwrap();
+        drop(client);
+
+        assert!(server.received().starts_with("test.first 1 "));
+        assert!(server.received().starts_with("test.second 2 "));
+    }
+
+    #[test]
+    fn test_batch_send_and_reuse() {
+        let port = 20041;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+
+        let mut batch = Batch::new();
+        batch
+            .push("test.a", "1")
+            .push_message(GraphiteMessage::new("test.b", "2"));
+        assert_eq!(batch.len(), 2);
+        let expected_size = batch.size_bytes();
+
+        assert_eq!(batch.send(&mut client).unwrap(), expected_size);
+        assert!(batch.is_empty());
+        assert_eq!(batch.size_bytes(), 0);
+
+        batch.push("test.c", "3");
+        batch.send(&mut client).unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.a 1 1600000000\n\
+             test.b 2 1600000000\n\
+             test.c 3 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_reconnect_backs_off_between_attempts() {
+        let port = 20042;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(3)
+            .retry_initial_delay(Duration::from_millis(50))
+            .retry_max_delay(Duration::from_millis(80))
+            .build()
+            .unwrap();
+        drop(listener);
+
+        let start = std::time::Instant::now();
+        assert!(client.reconnect().is_err());
+        let elapsed = start.elapsed();
+
+        // Three attempts separated by delays of 50ms and 80ms (capped from 100ms)
+        assert!(elapsed >= Duration::from_millis(130));
+        assert!(elapsed < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_max_line_bytes_rejects_before_sending() {
+        let port = 20043;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .max_line_bytes(32)
+            .build()
+            .unwrap();
+        let long = GraphiteMessage::new(&"a".repeat(32), "1");
+
+        let err = client.send_message(&long).unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+
+        let err = client
+            .send_batch_message(&[GraphiteMessage::new("test.ok", "1"), long])
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+        drop(client);
+
+        assert_eq!(server.received(), "");
+    }
+
+    #[test]
+    fn test_retry_jitter_stays_within_bounds() {
+        let port = 20044;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(3)
+            .retry_initial_delay(Duration::from_millis(50))
+            .retry_max_delay(Duration::from_millis(50))
+            .retry_jitter(Jitter::Proportional(1.0))
+            .rng_seed(7)
+            .build()
+            .unwrap();
+        drop(listener);
+
+        let start = std::time::Instant::now();
+        assert!(client.reconnect().is_err());
+        let elapsed = start.elapsed();
+
+        // Two delays of 50ms, each stretched by up to another 50ms
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(200) + Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_retry_jitter_rejects_unbounded_fraction() {
+        for fraction in [f64::INFINITY, f64::NAN, 1e20] {
+            let err = GraphiteClient::builder()
+                .transport(graphyne::Transport::Null)
+                .retry_jitter(Jitter::Proportional(fraction))
+                .build()
+                .unwrap_err();
+            assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig, "{fraction}");
+        }
+        GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .retry_jitter(Jitter::Proportional(10.0))
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reset_reconnects_from_scratch() {
+        let port = 20045;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.before", "1"))
+            .unwrap();
+        client.reset().unwrap();
+        assert!(server.received().starts_with("test.before 1 "));
+
+        client
+            .send_message(&GraphiteMessage::new("test.after", "2"))
+            .unwrap();
+        drop(client);
+
+        assert!(server.received().starts_with("test.after 2 "));
+
+        // A reset that can't reconnect leaves the next send to reconnect first, even
+        // under a policy that wouldn't reconnect after a failed write.
+        let port = 20135;
+        let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .connect_retries(0)
+            .reconnect_policy(ReconnectPolicy::Lazy)
+            .build()
+            .unwrap();
+        drop(listener);
+        assert!(client.reset().is_err());
+        let server = RecordingGraphiteServer::start(port);
+        client
+            .send_message(&GraphiteMessage::new("test.after.reset", "3"))
+            .unwrap();
+        drop(client);
+        assert!(server.received().starts_with("test.after.reset 3 "));
+    }
+
+    #[test]
+    fn test_error_reports_exhausted_retries() {
+        let port = 20046;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retry_initial_delay(Duration::ZERO)
+            .build()
+            .unwrap();
+        drop(listener);
+
+        let err = client.reconnect().unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::RetriesExhausted);
+    }
+
+    #[test]
+    fn test_non_retryable_write_error_fails_fast() {
+        let port = 20047;
+        let _server = DummyGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retryable_kinds(vec![])
+            .build()
+            .unwrap();
+
+        // The server drops the connection, so writes start failing once the reset arrives.
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let err = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_message(&msg).err()
+            })
+            .unwrap();
+        assert_eq!(err.kind(), GraphiteErrorKind::NonRetryable);
+    }
+
+    #[test]
+    fn test_try_send_message_classifies_errors_like_blocking_sends() {
+        let port = 20130;
+        let _server = DummyGraphiteServer::start(port);
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let first_error = |retry: bool| {
+            let mut client = GraphiteClient::builder()
+                .address("127.0.0.1")
+                .port(port)
+                .retry_if(move |_| retry)
+                .build()
+                .unwrap();
+            // The server drops the connection, so writes start failing once the reset
+            // arrives.
+            (0..100)
+                .find_map(|_| {
+                    std::thread::sleep(Duration::from_millis(10));
+                    client.try_send_message(&msg).err()
+                })
+                .unwrap()
+        };
+
+        let err = first_error(false);
+        assert_eq!(err.kind(), GraphiteErrorKind::NonRetryable);
+        assert!(!err.is_retryable());
+        let err = first_error(true);
+        assert!(matches!(err, GraphiteError::WriteFailed { .. }), "{err:?}");
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_flush_metrics_snapshot() {
+        let port = 20048;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        client
+            .flush_metrics_snapshot(&[
+                ("app.requests".to_string(), 1024.0),
+                ("app.load".to_string(), 0.5),
+            ])
+            .unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "app.requests 1024 1600000000\n\
+             app.load 0.5 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_send_deadline_bounds_send() {
+        let port = 20049;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let deadline = Duration::from_millis(300);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(100)
+            .retry_initial_delay(Duration::from_millis(50))
+            .retry_max_delay(Duration::from_millis(50))
+            .send_deadline(deadline)
+            .build()
+            .unwrap();
+        drop(listener);
+
+        // The first writes may land in the socket buffer before the reset is noticed.
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let (err, elapsed) = (0..100)
+            .find_map(|_| {
+                let start = std::time::Instant::now();
+                let res = client.send_message(&msg);
+                res.err().map(|err| (err, start.elapsed()))
+            })
+            .unwrap();
+        assert_eq!(err.kind(), GraphiteErrorKind::Timeout);
+        assert!(elapsed < deadline + Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_total_timeout_bounds_reconnect() {
+        let port = 20050;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let total = Duration::from_millis(200);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(50)
+            .retry_initial_delay(Duration::from_millis(50))
+            .total_timeout(total)
+            .build()
+            .unwrap();
+        drop(listener);
+
+        let start = std::time::Instant::now();
+        assert!(client.reconnect().is_err());
+        assert!(start.elapsed() < total + Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_zero_retries_makes_single_attempt() {
+        let port = 20051;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(0)
+            .build()
+            .unwrap();
+        client.reconnect().unwrap();
+        drop(client);
+
+        // One connection from build() and exactly one from reconnect()
+        assert_eq!(server.received(), "");
+        assert_eq!(server.received(), "");
+        assert!(server.rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_zero_retries_reports_write_error() {
+        let port = 20052;
+        let _server = DummyGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(0)
+            .build()
+            .unwrap();
+
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let err = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_message(&msg).err()
+            })
+            .unwrap();
+        assert_eq!(err.kind(), GraphiteErrorKind::RetriesExhausted);
+        assert!(!err.msg().contains("Success"));
+    }
+
+    #[test]
+    fn test_errors_never_report_stale_os_errors() {
+        let port = 20053;
+        let _server = DummyGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_deadline(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        let err = client
+            .send_message(&GraphiteMessage::new("test.metric", "1"))
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::Timeout);
+        assert!(!err.msg().contains("Success"));
+    }
+
+    #[test]
+    fn test_reconnect_shuts_down_old_connection() {
+        let port = 20054;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        client.reconnect().unwrap();
+        client.reconnect().unwrap();
+        assert_eq!(client.connections_closed(), 2);
+
+        // Both replaced connections are closed while the client is still alive
+        assert_eq!(server.received(), "");
+        assert_eq!(server.received(), "");
+    }
+
+    #[test]
+    fn test_value_formatter_normalizes_values() {
+        let port = 20055;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .value_formatter(|value: &str| match value.parse::<f64>() {
+                Ok(value) => format!("{value:.2}"),
+                Err(_) => value.to_string(),
+            })
+            .build()
+            .unwrap();
+        client
+            .send_batch_message(&[
+                GraphiteMessage::new("test.a", "1"),
+                GraphiteMessage::new("test.b", "3.14159"),
+            ])
+            .unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.a 1.00 1600000000\n\
+             test.b 3.14 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_and_recovers() {
+        let port = 20056;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&transitions);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(1)
+            .retry_initial_delay(Duration::ZERO)
+            .circuit_breaker_threshold(2)
+            .circuit_breaker_cooldown(Duration::from_millis(200))
+            .on_breaker_transition(move |from, to| recorded.lock().unwrap().push((from, to)))
+            .build()
+            .unwrap();
+        drop(listener);
+
+        // Fail until the breaker trips; the first writes may land in the socket buffer.
+        let msg = GraphiteMessage::new("test.metric", "1");
+        for _ in 0..100 {
+            if client.breaker_state() == BreakerState::Open {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            let _ = client.send_message(&msg);
+        }
+        assert_eq!(client.breaker_state(), BreakerState::Open);
+        let err = client.send_message(&msg).unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::CircuitOpen);
+
+        // After the cooldown a probe goes through and closes the breaker.
+        let _server = DummyGraphiteServer::start(port);
+        std::thread::sleep(Duration::from_millis(200));
+        client.send_message(&msg).unwrap();
+        assert_eq!(client.breaker_state(), BreakerState::Closed);
+
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (BreakerState::Closed, BreakerState::Open),
+                (BreakerState::Open, BreakerState::HalfOpen),
+                (BreakerState::HalfOpen, BreakerState::Closed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_batch_atomic_reports_clean_delivery() {
+        let port = 20057;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        let result = client
+            .send_batch_atomic(&[
+                GraphiteMessage::new("test.a", "1"),
+                GraphiteMessage::new("test.b", "2"),
+            ])
+            .unwrap();
+        assert_eq!(result.messages, 2);
+        assert_eq!(result.bytes, 40);
+        assert_eq!(result.chunks, 1);
+        assert_eq!(result.reconnects, 0);
+        assert!(!result.possibly_duplicated);
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.a 1 1600000000\n\
+             test.b 2 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_send_batch_atomic_flags_resend_after_reconnect() {
+        let port = 20058;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retry_initial_delay(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        // Reset the accepted connection so the next writes fail, while a fresh listener
+        // accepts the reconnect.
+        drop(listener.accept().unwrap());
+        drop(listener);
+        let _server = DummyGraphiteServer::start(port);
+
+        let msgs = [GraphiteMessage::new("test.a", "1")];
+        let result = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_batch_atomic(&msgs).unwrap()
+            })
+            .find(|result| result.possibly_duplicated);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn test_lazy_reconnect_policy_defers_reconnect_to_next_send() {
+        let port = 20059;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .reconnect_policy(ReconnectPolicy::Lazy)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+
+        // With nothing listening, a failed write must return without reconnecting.
+        let msg = GraphiteMessage::new("test.a", "1");
+        let failed = (0..100).find_map(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            client.send_message(&msg).err()
+        });
+        assert!(failed.is_some());
+        assert_eq!(client.connections_closed(), 0);
+
+        let server = RecordingGraphiteServer::start(port);
+        client.send_message(&msg).unwrap();
+        assert_eq!(client.connections_closed(), 1);
+        drop(client);
+        assert!(server.received().starts_with("test.a 1 "));
+    }
+
+    #[test]
+    fn test_verify_connection_before_send_delivers_after_server_close() {
+        let port = 20060;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .verify_connection_before_send(true)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+        let server = RecordingGraphiteServer::start(port);
+
+        client
+            .send_message(&GraphiteMessage::new("test.after_close", "1"))
+            .unwrap();
+        drop(client);
+
+        assert!(server.received().starts_with("test.after_close 1 "));
+    }
+
+    #[test]
+    fn test_epoch_timestamp_rejected_unless_floored() {
+        let port = 20061;
+        let server = RecordingGraphiteServer::start(port);
+
+        let epoch: GraphiteMessage = "test.epoch 1 0".parse().unwrap();
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let err = client.send_message(&epoch).unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::ClockSkew);
+        drop(client);
+        assert_eq!(server.received(), "");
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .min_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        client.send_message(&epoch).unwrap();
+        drop(client);
+        assert_eq!(server.received(), "test.epoch 1 1600000000\n");
+    }
+
+    #[test]
+    fn test_client_as_io_write_sink() {
+        let port = 20062;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        writeln!(client, "test.raw.a 1 1600000000").unwrap();
+        client.write_all(b"test.raw.b 2 1600000000\n").unwrap();
+        client.flush().unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.raw.a 1 1600000000\n\
+             test.raw.b 2 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_is_connected_and_check_connection() {
+        let port = 20063;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        assert!(client.is_connected());
+
+        drop(stream);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!client.is_connected());
+
+        client.check_connection().unwrap();
+        let _accepted = listener.accept().unwrap();
+        assert!(client.is_connected());
+        assert_eq!(client.connections_closed(), 1);
+    }
+
+    #[test]
+    fn test_send_message_by_past_deadline_times_out() {
+        let port = 20064;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let msg = GraphiteMessage::new("test.deadline", "1");
+        let err = client
+            .send_message_by(&msg, std::time::Instant::now())
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::Timeout);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        client.send_message_by(&msg, deadline).unwrap();
+        drop(client);
+        assert!(server.received().starts_with("test.deadline 1 "));
+    }
+
+    #[test]
+    fn test_send_batch_resumes_after_mid_batch_reset() {
+        let port = 20065;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            // Reset the first connection with unread data so the batch write fails
+            // partway, then record everything written after the reconnect.
+            let (stream, _) = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            drop(stream);
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = String::new();
+            let _ = stream.read_to_string(&mut data);
+            tx.send(data).unwrap();
+        });
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        // Large enough to overflow the socket buffers, so the write is still in
+        // progress when the connection is reset.
+        let count = 500_000;
+        let msgs: Vec<_> = (0..count)
+            .map(|i| GraphiteMessage::new(&format!("test.resume.{i}"), "1"))
+            .collect();
+        let result = client.send_batch(&msgs).unwrap();
+        drop(client);
+
+        assert_eq!(result.messages, count);
+        assert_eq!(result.reconnects, 1);
+        assert!(result.possibly_duplicated);
+        assert!(result.resent <= 1);
+        assert_eq!(result.sent_once + result.resent, count);
+
+        let received = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let resumed_at: usize = received
+            .strip_prefix("test.resume.")
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(resumed_at > 0);
+        let expected: String = (resumed_at..count)
+            .map(|i| format!("test.resume.{i} 1 1600000000\n"))
+            .collect();
+        assert!(received == expected);
+    }
+
+    #[test]
+    fn test_batch_drops_resent_lines_after_failed_chunk() {
+        let port = 20129;
+        // Every line is 28 bytes, so each chunk holds exactly `per_chunk` lines.
+        let per_chunk = 300_000;
+        let line = |i: usize| format!("test.r.{i:07} 1 1600000000\n");
+        let last_of_first_chunk = line(per_chunk - 1);
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        std::thread::spawn(move || {
+            // Once the first chunk is being written, reset the connection with unread data
+            // so the chunk is written again from the broken line on the second connection.
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut [0; 1]).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            drop(stream);
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut tail = Vec::new();
+            let mut buf = [0; 64 * 1024];
+            loop {
+                let len = stream.read(&mut buf).unwrap();
+                assert!(len > 0, "the first chunk was cut short");
+                tail.extend_from_slice(&buf[..len]);
+                if tail.ends_with(last_of_first_chunk.as_bytes()) {
+                    break;
+                }
+                let keep = tail.len().saturating_sub(last_of_first_chunk.len());
+                tail.drain(..keep);
+            }
+            // Refuse the reconnect, so the second chunk fails without being written.
+            drop(listener);
+            drop(stream);
+        });
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .retries(1)
+            .connect_retries(0)
+            .send_buffer_size(4096)
+            .verify_connection_before_send(true)
+            .max_write_size(per_chunk * 28)
+            .build()
+            .unwrap();
+        let mut batch = Batch::new();
+        for i in 0..2 * per_chunk {
+            batch.push_message(line(i).trim_end().parse().unwrap());
+        }
+
+        let err = batch.send(&mut client).unwrap_err();
+        let GraphiteError::BatchIncomplete { sent, messages, .. } = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(messages, 2 * per_chunk);
+        // The broken line of the first chunk was resent, and still counts as written.
+        assert_eq!(sent, per_chunk);
+        assert_eq!(batch.len(), per_chunk);
+        assert_eq!(batch.messages()[0].to_string(), line(per_chunk));
+    }
+
+    #[test]
+    fn test_at_most_once_reports_partial_batch_without_resending() {
+        let port = 20066;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            drop(stream);
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = String::new();
+            let _ = stream.read_to_string(&mut data);
+            tx.send(data).unwrap();
+        });
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .delivery(Delivery::AtMostOnce)
+            .build()
+            .unwrap();
+        let count = 500_000;
+        let msgs: Vec<_> = (0..count)
+            .map(|i| GraphiteMessage::new(&format!("test.once.{i}"), "1"))
+            .collect();
+        let result = client.send_batch(&msgs).unwrap();
+        assert_eq!(result.failure, Some(GraphiteErrorKind::RetriesExhausted));
+        assert!(result.sent_once > 0);
+        assert!(result.unsent > 0);
+        assert_eq!(result.sent_once + result.maybe_sent + result.unsent, count);
+        assert_eq!(result.resent, 0);
+
+        // The client reconnected for later sends without rewriting any of the batch.
+        client
+            .send_message(&GraphiteMessage::new("test.after", "1"))
+            .unwrap();
+        drop(client);
+        let received = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "test.after 1 1600000000\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_user_timeout_applied_to_connections() {
+        let port = 20067;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .user_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        client.reconnect().unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.user_timeout", "1"))
+            .unwrap();
+        drop(client);
+
+        assert_eq!(server.received(), "");
+        assert!(server.received().starts_with("test.user_timeout 1 "));
+    }
+
+    #[test]
+    fn test_custom_separator() {
+        let port = 20068;
+        let server = RecordingGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .separator("\t")
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.single", "1"))
+            .unwrap();
+        client
+            .send_batch_message(&[GraphiteMessage::new("test.batch", "2")])
+            .unwrap();
+        drop(client);
+
+        assert_eq!(
+            server.received(),
+            "test.single\t1\t1600000000\n\
+             test.batch\t2\t1600000000\n"
+        );
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .separator("\n")
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig);
+    }
+
+    #[test]
+    fn test_send_message_report() {
+        let port = 20069;
+        let _server = DummyGraphiteServer::start(port);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .retry_initial_delay(Duration::ZERO)
+            .build()
+            .unwrap();
+        let msg = GraphiteMessage::new("test.report", "1");
+        let report = client.send_message_report(&msg).unwrap();
+        assert_eq!(report.bytes_written, 25);
+        assert_eq!(report.attempts, 1);
+        assert_eq!(report.reconnects, 0);
+
+        // The server drops every connection, so sends eventually fail and reconnect.
+        let report = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_message_report(&msg).unwrap()
+            })
+            .find(|report| report.reconnects > 0)
+            .unwrap();
+        assert_eq!(report.attempts, report.reconnects + 1);
+        assert!(report.bytes_written >= 25);
+    }
+
+    #[test]
+    fn test_failed_reconnect_uses_up_a_send_attempt() {
+        let port = 20070;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_retries(5)
+            .connect_retries(0)
+            .retry_initial_delay(Duration::from_millis(200))
+            .retry_max_delay(Duration::from_millis(200))
+            .verify_connection_before_send(true)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+
+        // The server comes back while the send is still retrying.
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = String::new();
+            let _ = stream.read_to_string(&mut data);
+            tx.send(data).unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(50));
+
+        client
+            .send_message(&GraphiteMessage::new("test.recovered", "1"))
+            .unwrap();
+        drop(client);
+        let received = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(received.starts_with("test.recovered 1 "));
+    }
+
+    #[test]
+    fn test_batch_dedupe_policies() {
+        let port = 20071;
+        let server = RecordingGraphiteServer::start(port);
+        let msgs = [
+            GraphiteMessage::new("test.a", "1"),
+            GraphiteMessage::new("test.b", "5"),
+            GraphiteMessage::new("test.a", "3"),
+            GraphiteMessage::new("test.a", "2"),
+        ];
+
+        let cases = [
+            (DedupePolicy::First, "1"),
+            (DedupePolicy::Last, "2"),
+            (DedupePolicy::Sum, "6"),
+            (DedupePolicy::Max, "3"),
+        ];
+        for (policy, expected) in cases {
+            let mut client = GraphiteClient::builder()
+                .address("127.0.0.1")
+                .port(port)
+                .fixed_timestamp(1_600_000_000)
+                .batch_dedupe(policy)
+                .build()
+                .unwrap();
+            let result = client.send_batch(&msgs).unwrap();
+            assert_eq!(result.messages, 2);
+            drop(client);
+            assert_eq!(
+                server.received(),
+                format!("test.a {expected} 1600000000\ntest.b 5 1600000000\n")
+            );
+        }
+    }
+
+    #[test]
+    fn test_hostname_address() {
+        let port = 20072;
+        let server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("localhost")
+            .port(port)
+            .dns_cache_ttl(Duration::ZERO)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.connected_addr(),
+            format!("127.0.0.1:{port}").parse().unwrap()
+        );
+
+        // A reconnect resolves the hostname again and lands on the same server.
+        client.reconnect().unwrap();
+        assert_eq!(client.connected_addr().port(), port);
+        client
+            .send_message(&GraphiteMessage::new("test.host", "1"))
+            .unwrap();
+        drop(client);
+        assert_eq!(server.received(), "");
+        assert_eq!(server.received(), "test.host 1 1600000000\n");
+    }
+
+    #[test]
+    fn test_from_env() {
+        let port = 20073;
+        let server = RecordingGraphiteServer::start(port);
+
+        // Environment variables are process-wide, so every case lives in this one test.
+        unsafe {
+            std::env::set_var("GRAPHITE_ADDRESS", "127.0.0.1");
+            std::env::set_var("GRAPHITE_PORT", "not-a-port");
+        }
+        let err = GraphiteClient::from_env().unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig);
+        assert!(err.to_string().contains("GRAPHITE_PORT"));
+
+        unsafe {
+            std::env::set_var("GRAPHITE_PORT", port.to_string());
+            std::env::set_var("GRAPHITE_RETRIES", "1");
+            std::env::set_var("GRAPHITE_TIMEOUT_MS", "250");
+        }
+        let mut client = GraphiteClient::from_env().unwrap();
+        unsafe {
+            std::env::remove_var("GRAPHITE_ADDRESS");
+            std::env::remove_var("GRAPHITE_PORT");
+            std::env::remove_var("GRAPHITE_RETRIES");
+            std::env::remove_var("GRAPHITE_TIMEOUT_MS");
+        }
+        let debug = format!("{client:?}");
+        assert!(debug.contains("send_retries: 1,"));
+        assert!(debug.contains("timeout: 250ms,"));
+
+        client
+            .send_message(&GraphiteMessage::new("test.env", "1"))
+            .unwrap();
+        drop(client);
+        assert!(server.received().starts_with("test.env 1 "));
+    }
+
+    #[test]
+    fn test_address_selection() {
+        let ports = [20074, 20075, 20076];
+        let _servers = ports.map(DummyGraphiteServer::start);
+        let pool: Vec<std::net::SocketAddr> = ports
+            .iter()
+            .map(|port| format!("127.0.0.1:{port}").parse().unwrap())
+            .collect();
+
+        // Round robin cycles through the whole pool, one address per reconnect.
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(ports[0])
+            .address_selection(AddressSelection::RoundRobin)
+            .build()
+            .unwrap();
+        client.update_target(&pool[..]).unwrap();
+        assert_eq!(client.connected_addr(), pool[1]);
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            client.reconnect().unwrap();
+            seen.push(client.connected_addr());
+        }
+        assert_eq!(seen, [pool[2], pool[0], pool[1], pool[2], pool[0], pool[1]]);
+
+        // Shuffle spreads reconnects over the pool and never stays put while others are up.
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(ports[0])
+            .rng_seed(7)
+            .build()
+            .unwrap();
+        client.update_target(&pool[..]).unwrap();
+        let mut counts = [0; 3];
+        for _ in 0..30 {
+            let previous = client.connected_addr();
+            client.reconnect().unwrap();
+            assert_ne!(client.connected_addr(), previous);
+            let index = pool
+                .iter()
+                .position(|a| *a == client.connected_addr())
+                .unwrap();
+            counts[index] += 1;
+        }
+        assert!(counts.iter().all(|&count| count > 0), "{counts:?}");
+    }
+
+    #[test]
+    fn test_failover_and_failback() {
+        let primary = 20077;
+        let fallback = 20078;
+        let _fallback_server = DummyGraphiteServer::start(fallback);
+        let switches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&switches);
+
+        // The primary is down, so the client starts on the fallback.
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(primary)
+            .fallbacks([("127.0.0.1", fallback)])
+            .failback_interval(Duration::ZERO)
+            .connect_retries(0)
+            .on_endpoint_change(move |address, port| {
+                recorded.lock().unwrap().push(format!("{address}:{port}"));
+            })
+            .build()
+            .unwrap();
+        assert_eq!(client.active_endpoint(), ("127.0.0.1", fallback));
+
+        // While the primary stays down, sends keep going to the fallback.
+        client
+            .send_message(&GraphiteMessage::new("test.failover", "1"))
+            .unwrap();
+        assert_eq!(client.active_endpoint(), ("127.0.0.1", fallback));
+        assert!(switches.lock().unwrap().is_empty());
+
+        // Once the primary is back, the next send fails back to it.
+        let primary_server = RecordingGraphiteServer::start(primary);
+        client
+            .send_message(&GraphiteMessage::new("test.failback", "1"))
+            .unwrap();
+        assert_eq!(client.active_endpoint(), ("127.0.0.1", primary));
+        assert_eq!(*switches.lock().unwrap(), [format!("127.0.0.1:{primary}")]);
+        drop(client);
+        assert!(primary_server.received().starts_with("test.failback 1 "));
+    }
+
+    #[test]
+    fn test_self_report_after_retries_exhausted() {
+        let port = 20079;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_retries(0)
+            .connect_retries(0)
+            .verify_connection_before_send(true)
+            .fixed_timestamp(1_600_000_000)
+            .self_report(true)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let err = client
+            .send_message(&GraphiteMessage::new("test.lost", "1"))
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::RetriesExhausted);
+
+        let server = RecordingGraphiteServer::start(port);
+        client
+            .send_message(&GraphiteMessage::new("test.after", "1"))
+            .unwrap();
+        // The meta-metric's write timeout doesn't outlast its own write.
+        let stream = client.connection_ref().unwrap();
+        assert_eq!(stream.write_timeout().unwrap(), None);
+        drop(client);
+        let received = server.received();
+        let (report, rest) = received.split_once('\n').unwrap();
+        assert!(report.starts_with("graphyne.send_failures 1 "), "{report}");
+        assert_eq!(rest, "test.after 1 1600000000\n");
+    }
+
+    #[test]
+    fn test_fanout_mirrors_to_every_destination() {
+        let (primary_port, mirror_port, dead_port) = (20080, 20081, 20082);
+        let primary_server = RecordingGraphiteServer::start(primary_port);
+        let mirror_server = RecordingGraphiteServer::start(mirror_port);
+        let dead = TcpListener::bind(format!("127.0.0.1:{}", dead_port)).unwrap();
+        let client = |port| {
+            GraphiteClient::builder()
+                .address("127.0.0.1")
+                .port(port)
+                .send_retries(0)
+                .connect_retries(0)
+                .verify_connection_before_send(true)
+                .fixed_timestamp(1_600_000_000)
+                .build()
+                .unwrap()
+        };
+
+        let mut fanout = FanoutGraphiteClient::builder()
+            .primary(client(primary_port))
+            .mirrors(vec![client(mirror_port), client(dead_port)])
+            .policy(FanoutPolicy::PrimaryOnlyErrors)
+            .build();
+        drop(dead.accept().unwrap());
+        drop(dead);
+        std::thread::sleep(Duration::from_millis(50));
+
+        // The dead mirror fails without affecting the result or the other destinations.
+        let msg = GraphiteMessage::new("test.mirrored", "1");
+        assert_eq!(fanout.send_message(&msg).unwrap(), 27);
+        let stats = fanout.destination_stats();
+        assert_eq!((stats[0].sent, stats[0].failed), (1, 0));
+        assert_eq!((stats[1].sent, stats[1].failed), (1, 0));
+        assert_eq!((stats[2].sent, stats[2].failed), (0, 1));
+        assert_eq!(
+            stats[2].last_error,
+            Some(GraphiteErrorKind::RetriesExhausted)
+        );
+
+        drop(fanout);
+        assert_eq!(primary_server.received(), "test.mirrored 1 1600000000\n");
+        assert_eq!(mirror_server.received(), "test.mirrored 1 1600000000\n");
+    }
+
+    #[test]
+    fn test_sharded_client_matches_carbon_ch() {
+        let ports = [20083, 20084, 20085];
+        let servers = ports.map(RecordingGraphiteServer::start);
+        let destinations: Vec<String> = ports.iter().map(|p| format!("127.0.0.1:{p}")).collect();
+        let mut client = ShardedGraphiteClient::builder()
+            .destinations(&[
+                (&destinations[0], Some("a")),
+                (&destinations[1], Some("b")),
+                (&destinations[2], Some("c")),
+            ])
+            .hash_ring(HashRing::CarbonCh)
+            .build()
+            .unwrap();
+
+        // Generated with carbon's ConsistentHashRing for nodes
+        // ('127.0.0.1', 'a'), ('127.0.0.1', 'b') and ('127.0.0.1', 'c').
+        let fixtures = [
+            ("carbon.agents.host1.cpuUsage", 0),
+            ("servers.web01.cpu.usage", 2),
+            ("servers.web02.cpu.usage", 2),
+            ("servers.db01.disk.sda.iops", 0),
+            ("app.requests.count", 0),
+            ("app.requests.latency.p99", 1),
+            ("a", 0),
+            ("stats.gauges.queue.depth", 1),
+            ("collectd.host.memory.used", 1),
+            ("foo.bar.baz", 0),
+        ];
+        for (path, expected) in fixtures {
+            assert_eq!(client.destination_index(path), expected, "{path}");
+        }
+
+        let msgs: Vec<_> = fixtures
+            .iter()
+            .map(|(path, _)| GraphiteMessage::new(path, "1"))
+            .collect();
+        client.send_batch_message(&msgs).unwrap();
+        drop(client);
+        for (index, server) in servers.iter().enumerate() {
+            let expected: String = fixtures
+                .iter()
+                .filter(|(_, node)| *node == index)
+                .map(|(path, _)| format!("{path} 1 "))
+                .collect();
+            let received: String = server
+                .received()
+                .lines()
+                .map(|line| format!("{} ", line.rsplit_once(' ').unwrap().0))
+                .collect();
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[test]
+    fn test_sharded_client_replication() {
+        let ports = [20086, 20087, 20088];
+        let _live = [ports[0], ports[1]].map(RecordingGraphiteServer::start);
+        let dead = TcpListener::bind(format!("127.0.0.1:{}", ports[2])).unwrap();
+        let destinations: Vec<String> = ports.iter().map(|p| format!("127.0.0.1:{p}")).collect();
+        let mut client = ShardedGraphiteClient::builder()
+            .destinations(&[
+                (&destinations[0], Some("a")),
+                (&destinations[1], Some("b")),
+                (&destinations[2], Some("c")),
+            ])
+            .replication_factor(2)
+            .send_retries(0)
+            .connect_retries(0)
+            .build()
+            .unwrap();
+        drop(dead.accept().unwrap());
+        drop(dead);
+
+        // Generated with carbon's ConsistentHashRing.get_nodes for the same nodes.
+        assert_eq!(
+            client.destination_indexes("servers.web01.cpu.usage"),
+            [2, 0]
+        );
+        assert_eq!(
+            client.destination_indexes("app.requests.latency.p99"),
+            [1, 0]
+        );
+        assert_eq!(
+            client.destination_indexes("stats.gauges.queue.depth"),
+            [1, 2]
+        );
+        assert_eq!(client.destination_indexes("foo.bar.baz"), [0, 1]);
+
+        // Writes to the closed destination fail once the reset has come back.
+        let msgs = [
+            GraphiteMessage::new("servers.web01.cpu.usage", "1"),
+            GraphiteMessage::new("foo.bar.baz", "1"),
+        ];
+        let result = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_batch(&msgs).unwrap()
+            })
+            .find(|result| result.degraded > 0)
+            .unwrap();
+        assert_eq!((result.degraded, result.failed), (1, 0));
+        let dead_replica = result.replicas.iter().find(|r| r.destination == 2).unwrap();
+        assert_eq!(dead_replica.messages, 1);
+        assert!(dead_replica.error.is_some());
+        client.send_batch_message(&msgs).unwrap();
+
+        let err = ShardedGraphiteClient::builder()
+            .destinations(&[(&destinations[0], None)])
+            .replication_factor(2)
+            .min_successful_replicas(3)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_transport() {
+        use graphyne::Transport;
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("graphyne-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok((mut stream, _)) = listener.accept() {
+                let mut data = String::new();
+                let _ = stream.read_to_string(&mut data);
+                if tx.send(data).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = GraphiteClient::builder()
+            .transport(Transport::UnixSocket(path.clone()))
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.unix", "1"))
+            .unwrap();
+        client.reconnect().unwrap();
+        assert_eq!(client.connections_closed(), 1);
+        client
+            .send_message(&GraphiteMessage::new("test.unix", "2"))
+            .unwrap();
+        drop(client);
+        let received =
+            |rx: &mpsc::Receiver<String>| rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received(&rx), "test.unix 1 1600000000\n");
+        assert_eq!(received(&rx), "test.unix 2 1600000000\n");
+        let _ = std::fs::remove_file(&path);
+
+        let err = GraphiteClient::builder().build().unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig);
+    }
+
+    #[test]
+    fn test_initial_connect_retries() {
+        let port = 20089;
+
+        // Without initial retries, build fails while the relay is still starting.
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::Connect);
+
+        let starting = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            RecordingGraphiteServer::start(port)
+        });
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .connect_retries(0)
+            .initial_connect_retries(30)
+            .initial_connect_backoff(Duration::from_millis(20))
+            .connect_retry_max_delay(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let server = starting.join().unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.started", "1"))
+            .unwrap();
+        drop(client);
+        assert!(server.received().starts_with("test.started 1 "));
+    }
+
+    #[test]
+    fn test_fanout_skips_unhealthy_destination() {
+        let (primary_port, dead_port) = (20090, 20091);
+        let primary_server = RecordingGraphiteServer::start(primary_port);
+        let dead = TcpListener::bind(format!("127.0.0.1:{}", dead_port)).unwrap();
+        let client = |port| {
+            GraphiteClient::builder()
+                .address("127.0.0.1")
+                .port(port)
+                .send_retries(0)
+                .connect_retries(0)
+                .verify_connection_before_send(true)
+                .fixed_timestamp(1_600_000_000)
+                .build()
+                .unwrap()
+        };
+
+        let mut fanout = FanoutGraphiteClient::builder()
+            .primary(client(primary_port))
+            .mirrors(vec![client(dead_port)])
+            .policy(FanoutPolicy::PrimaryOnlyErrors)
+            .unhealthy_threshold(1)
+            .reprobe_interval(Duration::from_millis(200))
+            .build();
+        drop(dead.accept().unwrap());
+        drop(dead);
+        std::thread::sleep(Duration::from_millis(50));
+
+        // The first failure marks the mirror unhealthy, so the next send skips it.
+        let msg = GraphiteMessage::new("test.health", "1");
+        fanout.send_message(&msg).unwrap();
+        fanout.send_message(&msg).unwrap();
+        let stats = fanout.destination_stats();
+        assert!(stats[0].healthy);
+        assert_eq!((stats[0].sent, stats[0].consecutive_failures), (2, 0));
+        assert!(stats[0].last_success.is_some());
+        assert!(!stats[1].healthy);
+        assert_eq!((stats[1].failed, stats[1].skipped), (1, 1));
+        assert_eq!(stats[1].last_success, None);
+
+        // Once the reprobe interval passes, the mirror is attempted again.
+        std::thread::sleep(Duration::from_millis(250));
+        fanout.send_message(&msg).unwrap();
+        let stats = fanout.destination_stats();
+        assert_eq!((stats[1].failed, stats[1].skipped), (2, 1));
+        assert_eq!(stats[1].consecutive_failures, 2);
+
+        drop(fanout);
+        assert_eq!(primary_server.received().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_batch_drops_expired_messages() {
+        let port = 20092;
+        let server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&dropped);
+
+        let mut batch = Batch::new()
+            .max_metric_age(Duration::from_secs(60))
+            .on_expired(move |msgs| {
+                recorded.lock().unwrap().extend(msgs.iter().cloned());
+            });
+        let stale: GraphiteMessage = "test.stale 1 1600000000".parse().unwrap();
+        batch.push_message(stale.clone()).push("test.fresh", "2");
+        batch.send(&mut client).unwrap();
+
+        assert_eq!(batch.expired(), 1);
+        assert_eq!(*dropped.lock().unwrap(), [stale]);
+        drop(client);
+        let received = server.received();
+        assert!(received.starts_with("test.fresh 2 "), "{received}");
+        assert_eq!(received.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_connection_ref() {
+        let port = 20093;
+        let _server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+
+        let stream = client.connection_ref().unwrap();
+        stream.set_nodelay(true).unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.peer_addr().unwrap(), client.connected_addr());
+    }
+
+    #[test]
+    fn test_dedupe_window_skips_resends() {
+        let port = 20094;
+        let server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .dedupe_window(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let msg = GraphiteMessage::new("test.dedupe", "1");
+        assert_eq!(client.send_message(&msg).unwrap(), 25);
+        assert_eq!(client.send_message(&msg).unwrap(), 0);
+        // A different value is a different point.
+        assert_eq!(
+            client
+                .send_message(&GraphiteMessage::new("test.dedupe", "2"))
+                .unwrap(),
+            25
+        );
+        // Once the window passes, the same point is sent again.
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(client.send_message(&msg).unwrap(), 25);
+
+        drop(client);
+        assert_eq!(
+            server.received(),
+            "test.dedupe 1 1600000000\ntest.dedupe 2 1600000000\ntest.dedupe 1 1600000000\n"
+        );
+    }
+
+    #[test]
+    fn test_send_error_context() {
+        let port = 20095;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_retries(1)
+            .connect_retries(0)
+            .retry_initial_delay(Duration::from_millis(1))
+            .verify_connection_before_send(true)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let err = client
+            .send_message(&GraphiteMessage::new("test.context", "1"))
+            .unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.subject, "metric 'test.context'");
+        assert_eq!((context.attempts, context.reconnects), (2, 0));
+        assert!(
+            err.to_string()
+                .contains("sending metric 'test.context' after 2 attempts and 0 reconnects in "),
+            "{err}"
+        );
+
+        let batch = [
+            GraphiteMessage::new("test.a", "1"),
+            GraphiteMessage::new("test.b", "2"),
+        ];
+        let err = client.send_batch_message(&batch).unwrap_err();
+        assert_eq!(err.context().unwrap().subject, "batch of 2 messages");
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20096)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, GraphiteError::ConnectRefused { .. }),
+            "{err:?}"
+        );
+
+        let err = GraphiteClient::builder().build().unwrap_err();
+        assert!(
+            matches!(err, GraphiteError::InvalidConfig { .. }),
+            "{err:?}"
+        );
+        assert!(err.msg().starts_with("Graphite Error: "));
+
+        let _server = RecordingGraphiteServer::start(20096);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20096)
+            .max_line_bytes(32)
+            .build()
+            .unwrap();
+        let err = client
+            .send_message(&GraphiteMessage::new(&"a".repeat(40), "1"))
+            .unwrap_err();
+        match err {
+            GraphiteError::InvalidMessage {
+                path,
+                rule: ValidationRule::LineTooLong { len, limit },
+                ..
+            } => {
+                assert_eq!(path, "a".repeat(40));
+                assert!(len > limit);
+                assert_eq!(limit, 32);
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error as _;
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20097)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::ConnectionRefused));
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::ConnectionRefused
+        );
+
+        let err = GraphiteClient::builder().build().unwrap_err();
+        assert_eq!(err.io_kind(), None);
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_error_predicates() {
+        let port = 20098;
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap_err();
+        assert!(err.is_connection_refused());
+        assert!(!err.is_timeout() && !err.is_broken_pipe() && !err.is_parse_error());
+
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        let deadline = Duration::from_millis(200);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retries(100)
+            .retry_initial_delay(Duration::from_millis(20))
+            .retry_max_delay(Duration::from_millis(20))
+            .send_deadline(deadline)
+            .build()
+            .unwrap();
+        drop(listener);
+
+        let err = client.reconnect().unwrap_err();
+        assert!(err.is_connection_refused());
+
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let err = (0..100)
+            .find_map(|_| client.send_message(&msg).err())
+            .unwrap();
+        assert!(err.is_timeout());
+        assert!(!err.is_parse_error());
+
+        let err = client
+            .send_batch_message(&[msg.clone(), msg.clone()])
+            .unwrap_err();
+        assert!(err.is_timeout());
+
+        let err = "test.metric".parse::<GraphiteMessage>().unwrap_err();
+        assert!(err.is_parse_error());
+        assert!(!err.is_timeout() && !err.is_connection_refused());
+
+        let err = GraphiteError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(err.is_broken_pipe());
+        assert!(!err.is_connection_refused());
+    }
+
+    #[test]
+    fn test_null_client_records_messages() {
+        let mut client = GraphiteClient::null();
+        let first = "test.a 1 1600000000".parse::<GraphiteMessage>().unwrap();
+        let batch = [
+            "test.b 2 1600000001".parse::<GraphiteMessage>().unwrap(),
+            "test.c 3.5 1600000002".parse::<GraphiteMessage>().unwrap(),
+        ];
+        client.send_message(&first).unwrap();
+        client.send_batch_message(&batch).unwrap();
+        client.reconnect().unwrap();
+
+        assert_eq!(
+            client.sent_messages(),
+            vec![first, batch[0].clone(), batch[1].clone()]
+        );
+        assert!(client.connection_ref().is_none());
+    }
+
+    #[test]
+    fn test_error_is_retryable() {
+        let port = 20099;
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap_err();
+        assert!(err.is_retryable());
+
+        let err = GraphiteClient::builder().build().unwrap_err();
+        assert!(!err.is_retryable());
+
+        let err = "test.metric".parse::<GraphiteMessage>().unwrap_err();
+        assert!(!err.is_retryable());
+
+        let _server = DummyGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .max_line_bytes(32)
+            .retryable_kinds(vec![])
+            .build()
+            .unwrap();
+        let err = client
+            .send_message(&GraphiteMessage::new(&"a".repeat(40), "1"))
+            .unwrap_err();
+        assert!(!err.is_retryable());
+
+        // The server drops the connection, so writes start failing once the reset arrives,
+        // and with no retryable kinds the client gives up on the first failure.
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let err = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_message(&msg).err()
+            })
+            .unwrap();
+        assert_eq!(err.kind(), GraphiteErrorKind::NonRetryable);
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_metric_sink() {
+        fn emit(sink: &mut impl MetricSink) -> Result<usize, GraphiteError> {
+            sink.send_message(&"test.a 1 1600000000".parse().unwrap())?;
+            sink.send_batch_message(&["test.b 2 1600000000".parse().unwrap()])
+        }
+
+        let mut client = GraphiteClient::null();
+        emit(&mut client).unwrap();
+        {
+            let mut sink: Box<dyn MetricSink + '_> = Box::new(&mut client);
+            emit(&mut sink).unwrap();
+            let mut batch = Batch::new();
+            batch.push_message("test.c 3 1600000000".parse().unwrap());
+            batch.send(&mut sink).unwrap();
+        }
+
+        let lines: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "test.a 1 1600000000\n",
+                "test.b 2 1600000000\n",
+                "test.a 1 1600000000\n",
+                "test.b 2 1600000000\n",
+                "test.c 3 1600000000\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        let msgs: Vec<GraphiteMessage> = ["test.a NaN 1600000000", "test.b 2 1600000000"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        let inf = "test.c -inf 1600000000".parse::<GraphiteMessage>().unwrap();
+
+        let mut client = GraphiteClient::null();
+        let err = client.send_message(&inf).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                GraphiteError::InvalidMessage { path, rule: ValidationRule::NonFiniteValue, .. }
+                    if path == "test.c"
+            ),
+            "{err:?}"
+        );
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidMessage);
+        assert!(!err.is_retryable());
+        client.send_batch_message(&msgs).unwrap_err();
+        assert!(client.sent_messages().is_empty());
+
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .on_non_finite(NonFinitePolicy::Skip)
+            .build()
+            .unwrap();
+        assert_eq!(client.send_message(&inf).unwrap(), 0);
+        let result = client.send_batch(&msgs).unwrap();
+        assert_eq!(result.messages, 1);
+        assert_eq!(client.sent_messages(), vec![msgs[1].clone()]);
+
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .on_non_finite(NonFinitePolicy::Zero)
+            .build()
+            .unwrap();
+        client.send_message(&inf).unwrap();
+        client.send_batch_message(&msgs).unwrap();
+        let lines: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "test.c 0 1600000000\n",
+                "test.a 0 1600000000\n",
+                "test.b 2 1600000000\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_batch_detailed() {
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .max_line_bytes(32)
+            .batch_dedupe(DedupePolicy::Last)
+            .build()
+            .unwrap();
+        let msgs: Vec<GraphiteMessage> = [
+            "test.a 1 1600000000",
+            "test.too.long.to.fit.in.a.line 2 1600000000",
+            "test.a 3 1600000000",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+        let outcomes = client.send_batch_detailed(&msgs).unwrap();
+        assert!(matches!(outcomes[0], MessageOutcome::Delivered));
+        assert!(matches!(
+            &outcomes[1],
+            MessageOutcome::Failed(err) if err.kind() == GraphiteErrorKind::MessageTooLong
+        ));
+        assert!(matches!(outcomes[2], MessageOutcome::Delivered));
+        assert_eq!(client.sent_messages(), vec![msgs[2].clone()]);
+
+        // A mid-batch reset leaves at most the line it cut into possibly duplicated.
+        let port = 20100;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            drop(stream);
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read_to_end(&mut Vec::new());
+        });
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        let count = 500_000;
+        let msgs: Vec<_> = (0..count)
+            .map(|i| GraphiteMessage::new(&format!("test.detailed.{i}"), "1"))
+            .collect();
+        let outcomes = client.send_batch_detailed(&msgs).unwrap();
+        assert_eq!(outcomes.len(), count);
+        let duplicated = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, MessageOutcome::PossiblyDuplicated))
+            .count();
+        let delivered = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, MessageOutcome::Delivered))
+            .count();
+        assert!(duplicated <= 1);
+        assert_eq!(delivered + duplicated, count);
+
+        // When retries run out, the messages not written are reported as failed.
+        let port = 20101;
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_retries(1)
+            .connect_retries(0)
+            .retry_initial_delay(Duration::from_millis(1))
+            .verify_connection_before_send(true)
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+        std::thread::sleep(Duration::from_millis(50));
+        let outcomes = client.send_batch_detailed(&msgs[..2]).unwrap();
+        for outcome in &outcomes {
+            assert!(
+                matches!(outcome, MessageOutcome::Failed(err) if err.is_retryable()),
+                "{outcome:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_validation_reports_every_failure() {
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .max_line_bytes(32)
+            .build()
+            .unwrap();
+        let msgs: Vec<GraphiteMessage> = [
+            "test.ok 1 1600000000",
+            "test.too.long.to.fit.in.a.line 2 1600000000",
+            "test.nan NaN 1600000000",
+            "test.also.much.too.long.for.a.line 3 1600000000",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+
+        let err = client.send_batch_message(&msgs).unwrap_err();
+        let GraphiteError::InvalidBatch { failures, messages } = &err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(*messages, 4);
+        let rules: Vec<_> = failures
+            .iter()
+            .map(|(index, err)| match err {
+                GraphiteError::InvalidMessage { path, rule, .. } => (*index, path.as_str(), *rule),
+                other => panic!("unexpected error {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (
+                    1,
+                    "test.too.long.to.fit.in.a.line",
+                    ValidationRule::LineTooLong { len: 44, limit: 32 }
+                ),
+                (2, "test.nan", ValidationRule::NonFiniteValue),
+                (
+                    3,
+                    "test.also.much.too.long.for.a.line",
+                    ValidationRule::LineTooLong { len: 48, limit: 32 }
+                ),
+            ]
+        );
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+        assert!(!err.is_retryable());
+        assert!(client.sent_messages().is_empty());
+
+        let err = "test.metric".parse::<GraphiteMessage>().unwrap_err();
+        assert!(matches!(
+            err,
+            GraphiteError::InvalidMessage {
+                rule: ValidationRule::Syntax,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_send_message_into_reuses_buffer() {
+        let mut client = GraphiteClient::null();
+        let mut buf = String::from("stale contents");
+        let first = "test.a 1 1600000000".parse::<GraphiteMessage>().unwrap();
+        let second = "test.b 2 1600000000".parse::<GraphiteMessage>().unwrap();
+
+        assert_eq!(client.send_message_into(&first, &mut buf).unwrap(), 20);
+        assert_eq!(buf, "test.a 1 1600000000\n");
+        client.send_message_into(&second, &mut buf).unwrap();
+        assert_eq!(buf, "test.b 2 1600000000\n");
+        assert_eq!(client.sent_messages(), vec![first, second]);
+    }
+
+    #[test]
+    fn test_reconnect_jitter_bounds() {
+        let port = 20102;
+        let _server = RecordingGraphiteServer::start(port);
+        let jitter = Duration::from_millis(100);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .reconnect_jitter(jitter)
+            .rng_seed(7)
+            .build()
+            .unwrap();
+
+        let mut total = Duration::ZERO;
+        for _ in 0..5 {
+            let start = std::time::Instant::now();
+            client.reconnect().unwrap();
+            let elapsed = start.elapsed();
+            assert!(elapsed < jitter + Duration::from_millis(50), "{elapsed:?}");
+            total += elapsed;
+        }
+        assert!(total > Duration::from_millis(20), "{total:?}");
+
+        // The delay never runs past the connect deadline.
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .reconnect_jitter(Duration::from_secs(10))
+            .connect_deadline(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let start = std::time::Instant::now();
+        let _ = client.reconnect();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_send_batch_in_chunks() {
+        let server = RecordingGraphiteServer::start(20103);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20103)
+            .fixed_timestamp(1_600_000_000)
+            .max_write_size(60)
+            .build()
+            .unwrap();
+        let msgs: Vec<_> = (0..10)
+            .map(|i| GraphiteMessage::new(&format!("test.chunk.{i}"), "1"))
+            .collect();
+
+        let mut invalid = msgs.clone();
+        invalid.push(GraphiteMessage::new(&"x".repeat(20_000), "1"));
+        let err = client.send_batch(&invalid).unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+
+        // Each line is 26 bytes, so a 60 byte chunk holds two.
+        let result = client.send_batch(&msgs).unwrap();
+        assert_eq!(result.chunks, 5);
+        assert_eq!(result.messages, 10);
+        assert_eq!(result.bytes, 260);
+        assert_eq!(result.sent_once, 10);
+        assert_eq!(result.failure, None);
+        drop(client);
+
+        let expected: String = (0..10)
+            .map(|i| format!("test.chunk.{i} 1 1600000000\n"))
+            .collect();
+        assert_eq!(server.received(), expected);
+    }
+
+    #[test]
+    fn test_batch_keeps_only_unsent_chunks_after_failure() {
+        // Writes batches in chunks of two messages into a null client, failing the second
+        // chunk of the first batch.
+        struct FailSecondChunk {
+            client: GraphiteClient,
+            failed: bool,
+        }
+
+        impl MetricSink for FailSecondChunk {
+            fn send_message(&mut self, msg: &GraphiteMessage) -> Result<usize, GraphiteError> {
+                self.client.send_message(msg)
+            }
+
+            fn send_batch_message(
+                &mut self,
+                msgs: &[GraphiteMessage],
+            ) -> Result<usize, GraphiteError> {
+                let mut written = 0;
+                for (index, chunk) in msgs.chunks(2).enumerate() {
+                    if index == 1 && !self.failed {
+                        self.failed = true;
+                        return Err(GraphiteError::BatchIncomplete {
+                            kind: GraphiteErrorKind::RetriesExhausted,
+                            sent: 2,
+                            messages: msgs.len(),
+                        });
+                    }
+                    written += self.client.send_batch_message(chunk)?;
+                }
+                Ok(written)
+            }
+        }
+
+        let mut sink = FailSecondChunk {
+            client: GraphiteClient::null(),
+            failed: false,
+        };
+        let mut batch = Batch::new();
+        for i in 0..5 {
+            batch.push_message(format!("test.chunk.{i} 1 1600000000").parse().unwrap());
+        }
+        let line_len = "test.chunk.0 1 1600000000\n".len();
+
+        let err = batch.send(&mut sink).unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::RetriesExhausted);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.size_bytes(), 3 * line_len);
+
+        batch.send(&mut sink).unwrap();
+        assert!(batch.is_empty());
+        let lines: Vec<_> = sink
+            .client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.to_string())
+            .collect();
+        let expected: Vec<_> = (0..5)
+            .map(|i| format!("test.chunk.{i} 1 1600000000\n"))
+            .collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_construction_error_kinds() {
+        let port = 20105;
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.l")
+            .port(port)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidAddress);
+        assert!(!err.is_retryable());
+
+        let err = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::Connect);
+        assert!(err.is_retryable());
+
+        let _server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let err = client.update_target("127.0.0.1").unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidAddress);
+    }
+
+    #[test]
+    fn test_send_fmt() {
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .max_line_bytes(40)
+            .on_non_finite(NonFinitePolicy::Skip)
+            .build()
+            .unwrap();
+
+        let p99 = 0.123456;
+        let sent = client
+            .send_fmt_at("api.latency.p99", format_args!("{p99:.3}"), 1_600_000_000)
+            .unwrap();
+        assert_eq!(sent, "api.latency.p99 0.123 1600000000\n".len());
+        let sent = client
+            .send_fmt("api.latency.p50", format_args!("{}", f64::NAN))
+            .unwrap();
+        assert_eq!(sent, 0);
+        let err = client
+            .send_fmt("api.latency.p99", format_args!("{:.30}", p99))
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+
+        let sent = client
+            .send_fmt("api.requests", format_args!("{}", 42))
+            .unwrap();
+        assert!(sent > 0);
+        let messages = client.sent_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0].to_string(),
+            "api.latency.p99 0.123 1600000000\n"
+        );
+        assert!(messages[1].to_string().starts_with("api.requests 42 "));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let inspected = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&inspected);
+        // Nothing listens on this address, but a dry run never connects.
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(20106)
+            .fixed_timestamp(1_600_000_000)
+            .max_line_bytes(32)
+            .dry_run(true)
+            .on_dry_run(move |data| sink.lock().unwrap().extend_from_slice(data))
+            .build()
+            .unwrap();
+
+        let sent = client
+            .send_message(&GraphiteMessage::new("app.requests", "42"))
+            .unwrap();
+        assert_eq!(sent, "app.requests 42 1600000000\n".len());
+        let sent = client
+            .send_batch_message(&[
+                GraphiteMessage::new("app.a", "1"),
+                GraphiteMessage::new("app.b", "2"),
+            ])
+            .unwrap();
+        assert_eq!(sent, 2 * "app.a 1 1600000000\n".len());
+        let err = client
+            .send_message(&GraphiteMessage::new("app.too.long.for.the.limit", "1"))
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+
+        assert_eq!(
+            String::from_utf8(inspected.lock().unwrap().clone()).unwrap(),
+            "app.requests 42 1600000000\n\
+             app.a 1 1600000000\n\
+             app.b 2 1600000000\n"
+        );
+        assert!(client.sent_messages().is_empty());
+    }
+
+    #[test]
+    fn test_metric_handle() {
+        fn assert_send<T: Send + Clone>(_: &T) {}
+
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .fixed_timestamp(1_600_000_000)
+            .build()
+            .unwrap();
+        let requests = client.register_path("prod.api.requests").unwrap();
+        assert_send(&requests);
+        assert_eq!(requests.path(), "prod.api.requests");
+
+        let sent = client.send_value(&requests, 42.0).unwrap();
+        assert_eq!(sent, "prod.api.requests 42 1600000000\n".len());
+        client.send_value(&requests.clone(), 0.5).unwrap();
+        client
+            .send_batch_message(&[requests.message(1.0), requests.message(2.0)])
+            .unwrap();
+        let sent: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                "prod.api.requests 42 1600000000\n",
+                "prod.api.requests 0.5 1600000000\n",
+                "prod.api.requests 1 1600000000\n",
+                "prod.api.requests 2 1600000000\n",
+            ]
+        );
+
+        for path in ["", "prod.api requests"] {
+            let err = client.register_path(path).unwrap_err();
+            assert_eq!(err.kind(), GraphiteErrorKind::InvalidMessage, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn test_send_buffer_size() {
+        let port = 20107;
+        let _server = RecordingGraphiteServer::start(port);
+        let send_buffer = |client: &GraphiteClient| {
+            socket2::SockRef::from(client.connection_ref().unwrap())
+                .send_buffer_size()
+                .unwrap()
+        };
+
+        let default = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let mut small = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .send_buffer_size(4096)
+            .build()
+            .unwrap();
+        assert!(send_buffer(&small) < send_buffer(&default));
+
+        small.reconnect().unwrap();
+        assert!(send_buffer(&small) < send_buffer(&default));
+    }
+
+    #[test]
+    fn test_batch_coalesces_same_second_points() {
+        let mut batch = Batch::new().coalesce_by(|path| {
+            if path.ends_with(".count") {
+                Some(DedupePolicy::Sum)
+            } else if path.starts_with("raw.") {
+                None
+            } else {
+                Some(DedupePolicy::Last)
+            }
+        });
+        for line in [
+            "queue.depth 3 1600000000",
+            "requests.count 2 1600000000",
+            "queue.depth 5 1600000000",
+            "raw.sample 1 1600000000",
+            "requests.count 4 1600000000",
+            "raw.sample 2 1600000000",
+            "queue.depth 7 1600000001",
+        ] {
+            batch.push_message(line.parse().unwrap());
+        }
+        assert_eq!(batch.coalesced(), 2);
+        assert_eq!(
+            batch.size_bytes(),
+            batch
+                .messages()
+                .iter()
+                .map(|msg| msg.to_string().len())
+                .sum::<usize>()
+        );
+
+        let mut client = GraphiteClient::null();
+        batch.send(&mut client).unwrap();
+        let sent: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                "queue.depth 5 1600000000\n",
+                "requests.count 6 1600000000\n",
+                "raw.sample 1 1600000000\n",
+                "raw.sample 2 1600000000\n",
+                "queue.depth 7 1600000001\n",
+            ]
+        );
+
+        // The index is reset once the batch is sent.
+        batch.push_message("queue.depth 9 1600000000".parse().unwrap());
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.coalesced(), 2);
+    }
+
+    #[test]
+    fn test_sender_flushes_on_shutdown() {
+        let port = 20108;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let sender = GraphiteSender::builder()
+            .client(client)
+            .flush_interval(Duration::from_secs(60))
+            .max_batch_messages(10)
+            .build();
+
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                let producer = sender.producer();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let line = format!("test.sender.w{worker} {i} 1600000000");
+                        producer.send(line.parse().unwrap()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let late = sender.producer();
+        sender.shutdown().unwrap();
+        assert!(
+            late.send(GraphiteMessage::new("test.sender.late", "1"))
+                .is_err()
+        );
+
+        let received = server.received();
+        assert_eq!(received.lines().count(), 100);
+        for worker in 0..4 {
+            for i in 0..25 {
+                let line = format!("test.sender.w{worker} {i} 1600000000\n");
+                assert!(received.contains(&line), "missing {line:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sender_expires_and_coalesces_buffered_points() {
+        let port = 20133;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let sender = GraphiteSender::builder()
+            .client(client)
+            .flush_interval(Duration::from_secs(60))
+            .max_batch_messages(3)
+            .max_metric_age(Duration::from_secs(3600))
+            .on_expired({
+                let expired = Arc::clone(&expired);
+                move |msgs| expired.lock().unwrap().extend_from_slice(msgs)
+            })
+            .coalesce(DedupePolicy::Last)
+            .build();
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let producer = sender.producer();
+        for line in [
+            "test.sender.old 1 1000000000".to_string(),
+            format!("test.sender.gauge 1 {now}"),
+            format!("test.sender.gauge 2 {now}"),
+            format!("test.sender.other 1 {now}"),
+        ] {
+            producer.send(line.parse().unwrap()).unwrap();
+        }
+
+        // The coalesced gauge leaves three points, which flush without the expired one.
+        let start = std::time::Instant::now();
+        while sender.expired() == 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sender.expired(), 1);
+        assert_eq!(sender.coalesced(), 1);
+        assert_eq!(
+            *expired.lock().unwrap(),
+            ["test.sender.old 1 1000000000".parse().unwrap()]
+        );
+        sender.shutdown().unwrap();
+        assert_eq!(
+            server.received(),
+            format!("test.sender.gauge 2 {now}\ntest.sender.other 1 {now}\n")
+        );
+
+        // A sharded sender applies the same options to every worker.
+        let sender = ShardedSender::builder()
+            .clients(vec![GraphiteClient::null(), GraphiteClient::null()])
+            .coalesce(DedupePolicy::Sum)
+            .build()
+            .unwrap();
+        let producer = sender.producer();
+        for value in ["1", "2"] {
+            let line = format!("test.sharded.count {value} {now}");
+            producer.send(line.parse().unwrap()).unwrap();
+        }
+        let worker = &sender.workers()[sender.worker_index("test.sharded.count")];
+        let start = std::time::Instant::now();
+        while worker.coalesced() == 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(worker.coalesced(), 1);
+    }
+
+    #[test]
+    fn test_try_send_message_queues_when_socket_is_full() {
+        let port = 20109;
+        let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
+        let (start_reading, reading) = mpsc::channel::<()>();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            reading.recv().unwrap();
+            let mut data = String::new();
+            let _ = stream.read_to_string(&mut data);
+            tx.send(data).unwrap();
+        });
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let series = Series("test.nonblocking");
+
+        // Nobody reads yet, so the socket buffers fill up and the sends stop blocking.
+        let mut sent = 0;
+        while client.try_send_message(&series.msg(sent)).unwrap() == SendStatus::Sent {
+            sent += 1;
+            assert!(sent < 10_000_000, "socket never filled up");
+        }
+        assert!(client.pending_bytes() > 0);
+        for i in sent + 1..sent + 11 {
+            let status = client.try_send_message(&series.msg(i)).unwrap();
+            assert_eq!(status, SendStatus::WouldBlock);
+        }
+        assert_eq!(client.poll_flush().unwrap(), SendStatus::WouldBlock);
+
+        // A blocking send writes everything queued ahead of its own line.
+        start_reading.send(()).unwrap();
+        client.send_message(&series.msg(sent + 11)).unwrap();
+        assert_eq!(client.pending_bytes(), 0);
+        assert_eq!(client.poll_flush().unwrap(), SendStatus::Sent);
+        drop(client);
+
+        let received = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let expected: String = (0..sent + 12).map(|i| series.line(i) + "\n").collect();
+        assert!(received == expected, "lines were lost, split or reordered");
+    }
+
+    #[test]
+    fn test_send_delta() {
+        let mut client = GraphiteClient::null();
+        assert_eq!(client.send_delta("test.bytes", 1000.0).unwrap(), 0);
+        client.send_delta("test.bytes", 1250.0).unwrap();
+        client.send_delta("test.other", 7.0).unwrap();
+        client.send_delta("test.bytes", 1250.0).unwrap();
+        // A lower value means the counter was reset.
+        client.send_delta("test.bytes", 40.0).unwrap();
+        client.send_delta("test.bytes", 45.5).unwrap();
+
+        let values: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.to_string().rsplit_once(' ').unwrap().0.to_owned())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "test.bytes 250",
+                "test.bytes 0",
+                "test.bytes 40",
+                "test.bytes 5.5"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_connect_fires_once() {
+        let port = 20110;
+        let _server = DummyGraphiteServer::start(port);
+        let connects = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&connects);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .on_connect(move |addr| seen.lock().unwrap().push(*addr))
+            .build()
+            .unwrap();
+        assert_eq!(
+            *connects.lock().unwrap(),
+            [format!("127.0.0.1:{port}").parse().unwrap()]
+        );
+
+        client.reconnect().unwrap();
+        assert_eq!(connects.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_write_buffer() {
+        let series = Series("test.buffered");
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .write_buffer(64)
+            .build()
+            .unwrap();
+
+        // Each line is 29 bytes, so two fit and the third writes them out.
+        client.send_message(&series.msg(1)).unwrap();
+        client.send_message(&series.msg(2)).unwrap();
+        assert!(client.sent_messages().is_empty());
+        assert_eq!(client.pending_bytes(), 58);
+        client.send_message(&series.msg(3)).unwrap();
+        assert_eq!(client.sent_messages(), [series.msg(1), series.msg(2)]);
+
+        // Other writes go out after what is buffered.
+        client.send_batch_message(&[series.msg(4)]).unwrap();
+        assert_eq!(
+            client.sent_messages(),
+            [series.msg(1), series.msg(2), series.msg(3), series.msg(4)]
+        );
+        let report = client.send_message_report(&series.msg(5)).unwrap();
+        assert_eq!((report.bytes_written, report.bytes_buffered), (0, 29));
+        assert_eq!(client.flush().unwrap(), 29);
+        assert_eq!(client.flush().unwrap(), 0);
+        assert_eq!(client.pending_bytes(), 0);
+        assert_eq!(client.sent_messages().len(), 5);
+
+        // Dropping the client writes out what is left.
+        let port = 20111;
+        let server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .write_buffer(1024)
+            .build()
+            .unwrap();
+        client.send_message(&series.msg(6)).unwrap();
+        client.send_message(&series.msg(7)).unwrap();
+        drop(client);
+        assert_eq!(
+            server.received(),
+            format!("{}\n{}\n", series.line(6), series.line(7))
+        );
+
+        // With the server gone, dropping makes one attempt instead of reconnecting.
+        let port = 20134;
+        let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .write_buffer(1024)
+            .verify_connection_before_send(true)
+            .connect_retries(3)
+            .connect_retry_initial_delay(Duration::from_secs(1))
+            .build()
+            .unwrap();
+        drop(listener.accept().unwrap());
+        drop(listener);
+        client.send_message(&series.msg(8)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        drop(client);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_sharded_sender_routes_by_path() {
+        let ports = [20112, 20113, 20114];
+        let servers: Vec<_> = ports
+            .iter()
+            .map(|&port| RecordingGraphiteServer::start(port))
+            .collect();
+        let clients = ports
+            .iter()
+            .map(|&port| {
+                GraphiteClient::builder()
+                    .address("127.0.0.1")
+                    .port(port)
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let sender = ShardedSender::builder()
+            .clients(clients)
+            .max_batch_messages(7)
+            .build()
+            .unwrap();
+        assert_eq!(sender.workers().len(), 3);
+
+        let paths: Vec<_> = (0..30).map(|i| format!("test.sharded.{i}")).collect();
+        let producer = sender.producer();
+        for point in 0..5 {
+            for path in &paths {
+                let msg = format!("{path} {point} 1600000000").parse().unwrap();
+                producer.clone().send(msg).unwrap();
+            }
+        }
+        let routes: Vec<_> = paths.iter().map(|path| sender.worker_index(path)).collect();
+        assert!(sender.workers().iter().all(|worker| worker.dropped() == 0));
+        sender.shutdown_timeout(Duration::from_secs(5)).unwrap();
+
+        let received: Vec<_> = servers.iter().map(|server| server.received()).collect();
+        assert_eq!(
+            received
+                .iter()
+                .map(|data| data.lines().count())
+                .sum::<usize>(),
+            150
+        );
+        for (path, &route) in paths.iter().zip(&routes) {
+            let points: Vec<_> = received[route]
+                .lines()
+                .filter_map(|line| line.strip_prefix(&format!("{path} ")))
+                .collect();
+            assert_eq!(
+                points,
+                (0..5)
+                    .map(|p| format!("{p} 1600000000"))
+                    .collect::<Vec<_>>(),
+                "points for {path} on worker {route}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_send_statsd_over_udp() {
+        let port = 20115;
+        let server = std::net::UdpSocket::bind(format!("127.0.0.1:{port}")).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Udp)
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+
+        let msg = GraphiteMessage::new("test.statsd.requests", "3");
+        client.send_statsd(&msg, MetricType::Counter).unwrap();
+        client
+            .send_statsd(
+                &GraphiteMessage::new("test.statsd.queue", "12"),
+                MetricType::Gauge,
+            )
+            .unwrap();
+        client
+            .write_all(msg.to_statsd_sampled(MetricType::Counter, 0.25).as_bytes())
+            .unwrap();
+
+        let mut buf = [0; 1024];
+        let mut datagrams = Vec::new();
+        for _ in 0..3 {
+            let len = server.recv(&mut buf).unwrap();
+            datagrams.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert_eq!(
+            datagrams,
+            [
+                "test.statsd.requests:3|c\n",
+                "test.statsd.queue:12|g\n",
+                "test.statsd.requests:3|c|@0.25",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_statsd_validates_lines() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&written);
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Udp)
+            .address("127.0.0.1")
+            .port(20127)
+            .max_line_bytes(24)
+            .on_non_finite(NonFinitePolicy::Skip)
+            .dry_run(true)
+            .on_dry_run(move |data| sink.lock().unwrap().extend_from_slice(data))
+            .build()
+            .unwrap();
+
+        for path in [
+            "test.bad path",
+            "test.bad:path",
+            "test.bad|path",
+            "test.bad\npath",
+        ] {
+            let err = client
+                .send_statsd(&GraphiteMessage::new(path, "1"), MetricType::Counter)
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    GraphiteError::InvalidMessage {
+                        rule: ValidationRule::Syntax,
+                        ..
+                    }
+                ),
+                "{path:?}: {err:?}"
+            );
+        }
+        let err = client
+            .send_statsd(
+                &GraphiteMessage::new("test.value", "1|c"),
+                MetricType::Gauge,
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidMessage);
+        let err = client
+            .send_statsd(
+                &GraphiteMessage::new("test.statsd.too.long", "1"),
+                MetricType::Counter,
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::MessageTooLong);
+        let skipped = client
+            .send_statsd(&GraphiteMessage::new("test.nan", "NaN"), MetricType::Gauge)
+            .unwrap();
+        assert_eq!(skipped, 0);
+
+        client
+            .send_statsd(&GraphiteMessage::new("test.ok", "2"), MetricType::Gauge)
+            .unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"test.ok:2|g\n");
+    }
+
+    #[test]
+    fn test_retry_if_overrides_retryable_kinds() {
+        let port = 20116;
+        let _server = DummyGraphiteServer::start(port);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&seen);
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .retry_if(move |err| {
+                recorded.lock().unwrap().push(err.kind());
+                false
+            })
+            .build()
+            .unwrap();
+
+        // The server drops the connection, so writes start failing once the reset arrives.
+        let msg = GraphiteMessage::new("test.metric", "1");
+        let err = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                client.send_message(&msg).err()
+            })
+            .unwrap();
+        assert_eq!(err.kind(), GraphiteErrorKind::NonRetryable);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_send_encoded_uses_line_only_when_verbatim() {
+        let msg: GraphiteMessage = "test.cached 1 1600000000".parse().unwrap();
+        let encoded = EncodedMessage::new(msg.clone());
+
+        let mut client = GraphiteClient::null();
+        client.send_encoded(&encoded).unwrap();
+        let mut fixed = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .fixed_timestamp(1_700_000_000)
+            .build()
+            .unwrap();
+        fixed.send_encoded(&encoded).unwrap();
+        assert_eq!(client.sent_messages(), [msg]);
+        assert_eq!(
+            fixed.sent_messages(),
+            ["test.cached 1 1700000000"
+                .parse::<GraphiteMessage>()
+                .unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_send_messages_grouped() {
+        let msgs: Vec<GraphiteMessage> = [
+            "test.cpu 10 1600000000",
+            "test.mem 512 1600000000",
+            "test.cpu 12 1600000060",
+            "test.disk 7 1600000000",
+            "test.mem 500 1600000060",
+            "test.cpu 11 1600000120",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+        let mut client = GraphiteClient::null();
+
+        let sent = client.send_messages_grouped(&msgs).unwrap();
+        assert_eq!(sent.groups, 3);
+        assert_eq!(
+            sent.bytes,
+            msgs.iter().map(|msg| msg.to_string().len()).sum()
+        );
+        let lines: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.format_line())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "test.cpu 10 1600000000",
+                "test.cpu 12 1600000060",
+                "test.cpu 11 1600000120",
+                "test.mem 512 1600000000",
+                "test.mem 500 1600000060",
+                "test.disk 7 1600000000",
+            ]
+        );
+
+        // A grouped batch over `max_batch_messages` is written in chunks, still in order.
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .max_batch_messages(4)
+            .build()
+            .unwrap();
+        client.send_messages_grouped(&msgs).unwrap();
+        let chunked: Vec<_> = client
+            .sent_messages()
+            .iter()
+            .map(|msg| msg.format_line())
+            .collect();
+        assert_eq!(chunked, lines);
+    }
+
+    #[test]
+    fn test_sync_client_shared_between_threads() {
+        let port = 20117;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let shared = SyncGraphiteClient::from(client);
+
+        let handles: Vec<_> = (0..16)
+            .map(|thread| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        let msg =
+                            GraphiteMessage::new(&format!("stress.t{thread}"), &i.to_string());
+                        if i % 10 == 0 {
+                            shared.send_batch_message(&[msg]).unwrap();
+                        } else {
+                            shared.send_message(&msg).unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        drop(shared);
+
+        let received = server.received();
+        let lines: Vec<_> = received.lines().collect();
+        assert_eq!(lines.len(), 1600);
+        for thread in 0..16 {
+            let prefix = format!("stress.t{thread} ");
+            let values: Vec<_> = lines
+                .iter()
+                .filter_map(|line| line.strip_prefix(&prefix))
+                .map(|rest| rest.split(' ').next().unwrap().parse::<u32>().unwrap())
+                .collect();
+            assert_eq!(values, (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_sync_client_lock_timeout() {
+        let shared = SyncGraphiteClient::builder()
+            .client(GraphiteClient::null())
+            .lock_timeout(Duration::from_millis(20))
+            .build();
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let holder = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared
+                    .with_client(|_| {
+                        locked_tx.send(()).unwrap();
+                        std::thread::sleep(Duration::from_millis(200));
+                    })
+                    .unwrap();
+            })
+        };
+        locked_rx.recv().unwrap();
+
+        let err = shared
+            .send_message(&GraphiteMessage::new("test.locked", "1"))
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::Timeout);
+
+        holder.join().unwrap();
+        shared
+            .send_message(&GraphiteMessage::new("test.unlocked", "1"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sync_client_recovers_from_poisoning() {
+        let shared = SyncGraphiteClient::from(GraphiteClient::null());
+        let panicked = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared
+                    .with_client(|_| panic!("panic while sending"))
+                    .unwrap();
+            })
+            .join()
+        };
+        assert!(panicked.is_err());
+
+        shared
+            .send_message(&GraphiteMessage::new("test.recovered", "1"))
+            .unwrap();
+        let sent = shared
+            .with_client(|client| client.sent_messages().len())
+            .unwrap();
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    fn test_sync_client_sends_through_shared_reference() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SyncGraphiteClient>();
+
+        fn report(mut sink: impl MetricSink) {
+            sink.send_message(&GraphiteMessage::new("test.shared", "1"))
+                .unwrap();
+        }
+        let shared = SyncGraphiteClient::from(GraphiteClient::null());
+        let borrowed = &shared;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| report(borrowed));
+            }
+        });
+        let sent = shared
+            .with_client(|client| client.sent_messages().len())
+            .unwrap();
+        assert_eq!(sent, 4);
+    }
+
+    #[test]
+    fn test_try_clone_shares_connection() {
+        let port = 20119;
+        let server = RecordingGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let mut clone = client.try_clone().unwrap();
+        assert_eq!(clone.connected_addr(), client.connected_addr());
+
+        let series = Series("test.cloned");
+        let second = series.msg(2);
+        let worker = std::thread::spawn(move || clone.send_message(&second).unwrap());
+        worker.join().unwrap();
+        client.send_message(&series.msg(1)).unwrap();
+        drop(client);
+
+        // Both handles wrote to the one connection, which closed with the last of them.
+        assert_eq!(
+            server.received(),
+            format!("{}\n{}\n", series.line(2), series.line(1))
+        );
+
+        let mut null = GraphiteClient::null();
+        null.send_message(&series.msg(1)).unwrap();
+        let mut null_clone = null.try_clone().unwrap();
+        assert!(null_clone.sent_messages().is_empty());
+        null_clone.send_message(&series.msg(2)).unwrap();
+        assert_eq!(null.sent_messages(), [series.msg(1)]);
+        assert_eq!(null_clone.sent_messages(), [series.msg(2)]);
+    }
+
+    #[test]
+    fn test_try_clone_reconnects_once_for_every_clone() {
+        use std::io::BufRead;
+
+        let port = 20132;
+        let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            // The first connection closes after one line; the second is kept.
+            let (stream, _) = listener.accept().unwrap();
+            let mut first = String::new();
+            std::io::BufReader::new(stream)
+                .read_line(&mut first)
+                .unwrap();
+            tx.send(first).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut rest = String::new();
+            stream.read_to_string(&mut rest).unwrap();
+            tx.send(rest).unwrap();
+            listener.set_nonblocking(true).unwrap();
+            let extra = listener.accept().is_ok();
+            tx.send(format!("extra connection: {extra}")).unwrap();
+        });
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .verify_connection_before_send(true)
+            .build()
+            .unwrap();
+        let mut clone = client.try_clone().unwrap();
+
+        let series = Series("test.cloned");
+        client.send_message(&series.msg(1)).unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            series.line(1) + "\n"
+        );
+        std::thread::sleep(Duration::from_millis(100));
+
+        // The clone finds the connection closed and reconnects; the original moves to
+        // its connection rather than dialing one of its own.
+        clone.send_message(&series.msg(2)).unwrap();
+        client.send_message(&series.msg(3)).unwrap();
+        assert_eq!(client.connected_addr(), clone.connected_addr());
+        drop(client);
+        clone.send_message(&series.msg(4)).unwrap();
+        drop(clone);
+        let rest = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let expected: String = (2..=4).map(|i| series.line(i) + "\n").collect();
+        assert_eq!(rest, expected);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "extra connection: false"
+        );
+    }
+
+    #[test]
+    fn test_sync_client_clones_share_connection() {
+        let port = 20120;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let shared = SyncGraphiteClient::from(client);
+
+        let series = Series("test.handle");
+        let handler = {
+            let metrics = shared.clone();
+            move |i| metrics.send_message(&series.msg(i)).unwrap()
+        };
+        handler(1);
+        drop(handler);
+        shared.send_message(&series.msg(2)).unwrap();
+
+        // Dropping a clone left the connection open; dropping the last one closes it.
+        drop(shared);
+        assert_eq!(
+            server.received(),
+            format!("{}\n{}\n", series.line(1), series.line(2))
+        );
+    }
+
+    #[test]
+    fn test_max_batch_messages() {
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .fixed_timestamp(1_600_000_000)
+            .max_batch_messages(2)
+            .build()
+            .unwrap();
+        let msgs: Vec<_> = (0..5)
+            .map(|i| GraphiteMessage::new(&format!("test.counted.{i}"), "1"))
+            .collect();
+
+        let result = client.send_batch(&msgs).unwrap();
+        assert_eq!(result.chunks, 3);
+        assert_eq!(result.sent_once, 5);
+        assert_eq!(result.bytes, 5 * 28);
+        assert_eq!(client.send_batch_message(&msgs[..2]).unwrap(), 2 * 28);
+        assert_eq!(client.send_batch_atomic(&msgs).unwrap().chunks, 1);
+        assert_eq!(client.sent_messages().len(), 12);
+
+        let err = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .max_batch_messages(0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), GraphiteErrorKind::InvalidConfig);
+    }
+
+    #[test]
+    fn test_send_batch_detailed_in_chunks() {
+        let port = 20131;
+        let server = std::net::UdpSocket::bind(format!("127.0.0.1:{port}")).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Udp)
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .max_batch_messages(2)
+            .max_write_size(80)
+            .build()
+            .unwrap();
+        let msgs: Vec<_> = (0..7)
+            .map(|i| GraphiteMessage::new(&format!("test.counted.{i}"), "1"))
+            .collect();
+        let outcomes = client.send_batch_detailed(&msgs).unwrap();
+        assert!(
+            outcomes
+                .iter()
+                .all(|outcome| matches!(outcome, MessageOutcome::Delivered))
+        );
+
+        // Every datagram is one write, of at most two 28 byte lines.
+        let mut buf = [0; 1024];
+        let datagrams: Vec<_> = (0..4)
+            .map(|_| {
+                let len = server.recv(&mut buf).unwrap();
+                String::from_utf8_lossy(&buf[..len]).lines().count()
+            })
+            .collect();
+        assert_eq!(datagrams, [2, 2, 2, 1]);
+
+        // With a smaller `max_write_size`, that limit ends each chunk first.
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Udp)
+            .address("127.0.0.1")
+            .port(port)
+            .fixed_timestamp(1_600_000_000)
+            .max_batch_messages(2)
+            .max_write_size(40)
+            .build()
+            .unwrap();
+        client.send_batch_detailed(&msgs[..3]).unwrap();
+        for _ in 0..3 {
+            let len = server.recv(&mut buf).unwrap();
+            assert_eq!(len, 28);
+        }
+    }
+
+    #[test]
+    fn test_detached_sender_flushes_after_last_producer() {
+        let port = 20121;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let (producer, thread) = GraphiteSender::builder()
+            .client(client)
+            .flush_interval(Duration::from_secs(60))
+            .build()
+            .detach();
+
+        let series = Series("test.detached");
+        let first = series.msg(1);
+        let worker = {
+            let producer = producer.clone();
+            std::thread::spawn(move || producer.send(first).unwrap())
+        };
+        worker.join().unwrap();
+        producer.send_batch([series.msg(2), series.msg(3)]).unwrap();
+
+        // The flush interval is far off, so the lines go out once the last producer is gone.
+        drop(producer);
+        thread.join().unwrap();
+        let expected: String = (1..=3).map(|i| format!("{}\n", series.line(i))).collect();
+        assert_eq!(server.received(), expected);
+    }
+
+    #[test]
+    fn test_detached_sender_shuts_down_with_producers_alive() {
+        let port = 20128;
+        let server = RecordingGraphiteServer::start(port);
+        let client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        let (producer, thread) = GraphiteSender::builder()
+            .client(client)
+            .flush_interval(Duration::from_secs(60))
+            .build()
+            .detach();
+
+        let series = Series("test.detached");
+        producer.send(series.msg(1)).unwrap();
+        assert!(!thread.is_finished());
+
+        // The producer is still held, yet the thread flushes and stops within the timeout.
+        thread.shutdown_timeout(Duration::from_secs(5)).unwrap();
+        assert!(producer.send(series.msg(2)).is_err());
+        assert_eq!(server.received(), "test.detached.1 1 1600000000\n");
+    }
+
+    #[test]
+    fn test_send_message_by_restores_write_timeout() {
+        let port = 20122;
+        let _server = RecordingGraphiteServer::start(port);
+        for policy in [ReconnectPolicy::Eager, ReconnectPolicy::Lazy] {
+            let mut client = GraphiteClient::builder()
+                .address("127.0.0.1")
+                .port(port)
+                .reconnect_policy(policy)
+                .build()
+                .unwrap();
+            let deadline = std::time::Instant::now() + Duration::from_millis(20);
+            client
+                .send_message_by(&GraphiteMessage::new("test.deadline", "1"), deadline)
+                .unwrap();
+            let stream = client.connection_ref().unwrap();
+            assert_eq!(stream.write_timeout().unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_update_target_keeps_hostname() {
+        let port = 20123;
+        let _server = DummyGraphiteServer::start(port);
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Null)
+            .build()
+            .unwrap();
+
+        client.update_target(format!("localhost:{port}")).unwrap();
+        assert_eq!(client.active_endpoint(), ("localhost", port));
+        client.update_target(("127.0.0.1", port)).unwrap();
+        assert_eq!(client.active_endpoint(), ("127.0.0.1", port));
+    }
+
+    #[test]
+    fn test_update_target_keeps_transport() {
+        // Nothing listens here; a null or dry-run client must not try to connect.
+        let port = 20124;
+        let mut client = GraphiteClient::null();
+        client.update_target(("127.0.0.1", port)).unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.retarget", "1"))
+            .unwrap();
+        assert_eq!(client.sent_messages().len(), 1);
+        assert!(client.connection_ref().is_none());
+
+        let mut client = GraphiteClient::builder()
+            .address("127.0.0.1")
+            .port(port)
+            .dry_run(true)
+            .build()
+            .unwrap();
+        client.update_target(("127.0.0.1", port + 1)).unwrap();
+        client
+            .send_message(&GraphiteMessage::new("test.retarget", "1"))
+            .unwrap();
+        assert!(client.connection_ref().is_none());
+
+        let server = std::net::UdpSocket::bind(format!("127.0.0.1:{port}")).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut client = GraphiteClient::builder()
+            .transport(graphyne::Transport::Udp)
+            .address("127.0.0.1")
+            .port(port + 1)
+            .build()
+            .unwrap();
+        client.update_target(("127.0.0.1", port)).unwrap();
+        client
+            .send_statsd(
+                &GraphiteMessage::new("test.retarget", "1"),
+                MetricType::Gauge,
+            )
+            .unwrap();
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"test.retarget:1|g\n");
+    }
 }
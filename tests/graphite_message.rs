@@ -0,0 +1,179 @@
+#[cfg(test)]
+mod tests {
+    use graphyne::{
+        DurationUnit, EncodedMessage, GraphiteErrorKind, GraphiteMessage, PathInterner, Summary,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_round_trips_with_display() {
+        let line = "servers.web01.cpu.usage 45.2 1609459200\n";
+        let msg: GraphiteMessage = line.parse().unwrap();
+        assert_eq!(msg.to_string(), line);
+    }
+
+    #[test]
+    fn test_parse_defaults_timestamp_to_now() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let msg: GraphiteMessage = "app.requests 42".parse().unwrap();
+        let line = msg.to_string();
+
+        let timestamp: u64 = line
+            .strip_prefix("app.requests 42 ")
+            .unwrap()
+            .trim_end()
+            .parse()
+            .unwrap();
+        assert!(timestamp.abs_diff(now) <= 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lines() {
+        for line in [
+            "",
+            "missing.value",
+            "bad.timestamp 1 yesterday",
+            "too.many 1 2 3",
+        ] {
+            let err = line.parse::<GraphiteMessage>().unwrap_err();
+            assert_eq!(err.kind(), GraphiteErrorKind::InvalidMessage, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_duration_units() {
+        let cases = [
+            (Duration::from_millis(1500), DurationUnit::Seconds, "1.5"),
+            (Duration::from_secs(2), DurationUnit::Seconds, "2"),
+            (
+                Duration::from_millis(100),
+                DurationUnit::Milliseconds,
+                "100",
+            ),
+            (
+                Duration::from_micros(500),
+                DurationUnit::Milliseconds,
+                "0.5",
+            ),
+        ];
+        for (duration, unit, expected) in cases {
+            let msg = GraphiteMessage::from_duration("op.time", duration, unit);
+            assert!(
+                msg.to_string().starts_with(&format!("op.time {expected} ")),
+                "{duration:?} in {unit:?}: {msg}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_line_and_write_to_omit_newline() {
+        let msg: GraphiteMessage = "app.requests 42 1609459200".parse().unwrap();
+        assert_eq!(msg.format_line(), "app.requests 42 1609459200");
+        assert_eq!(format!("{}\n", msg.format_line()), msg.to_string());
+
+        let mut framed = Vec::new();
+        for _ in 0..2 {
+            framed.extend_from_slice(&26u32.to_be_bytes());
+            msg.write_to(&mut framed).unwrap();
+        }
+        let line: &[u8] = b"app.requests 42 1609459200";
+        assert_eq!(framed.len(), 2 * (4 + line.len()));
+        assert_eq!(&framed[4..30], line);
+        assert_eq!(&framed[34..], line);
+    }
+
+    #[test]
+    fn test_interned_paths_are_shared() {
+        let mut interner = PathInterner::new();
+        let first = interner.intern("app.requests");
+        let second = interner.intern("app.requests");
+        assert!(Arc::ptr_eq(&first, &second));
+        interner.intern("app.errors");
+        assert_eq!(interner.len(), 2);
+
+        let msg = GraphiteMessage::interned(first, "42");
+        assert!(msg.to_string().starts_with("app.requests 42 "));
+
+        interner.clear();
+        assert!(interner.is_empty());
+        assert!(!Arc::ptr_eq(&second, &interner.intern("app.requests")));
+    }
+
+    #[test]
+    fn test_summary_messages_share_timestamp() {
+        assert_eq!(Summary::from_values(&[]), None);
+
+        let values: Vec<f64> = (1..=20).map(f64::from).collect();
+        let summary = Summary::from_values(&values).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                count: 20,
+                sum: 210.0,
+                min: 1.0,
+                max: 20.0,
+                p95: 19.0,
+            }
+        );
+
+        let lines: Vec<_> = summary
+            .messages_at("api.latency", 1_600_000_000)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "api.latency.count 20 1600000000\n",
+                "api.latency.sum 210 1600000000\n",
+                "api.latency.min 1 1600000000\n",
+                "api.latency.max 20 1600000000\n",
+                "api.latency.p95 19 1600000000\n",
+            ]
+        );
+
+        let msgs = summary.messages("api.latency");
+        let timestamp =
+            |msg: &GraphiteMessage| msg.to_string().rsplit(' ').next().unwrap().to_string();
+        assert!(msgs.iter().all(|msg| timestamp(msg) == timestamp(&msgs[0])));
+    }
+
+    #[test]
+    fn test_encoded_message_touch_updates_line() {
+        let msg: GraphiteMessage = "app.heartbeat 1 999999999".parse().unwrap();
+        let mut encoded = EncodedMessage::new(msg.clone());
+        assert_eq!(encoded.as_bytes(), b"app.heartbeat 1 999999999\n");
+        assert_eq!(encoded.message(), &msg);
+        assert_eq!(msg.encoded(), encoded);
+
+        // A timestamp with more digits replaces the old one whole.
+        encoded.set_timestamp(1_600_000_000);
+        assert_eq!(encoded.as_bytes(), b"app.heartbeat 1 1600000000\n");
+        assert_eq!(encoded.message().to_string(), encoded.line());
+        assert_ne!(encoded.message(), &msg);
+
+        encoded.touch();
+        assert_eq!(encoded.message().to_string(), encoded.line());
+    }
+
+    #[test]
+    fn test_messages_dedupe_in_hash_set() {
+        let msg = |line: &str| line.parse::<GraphiteMessage>().unwrap();
+
+        let set: std::collections::HashSet<_> = [
+            msg("app.requests 5 1600000000"),
+            msg("app.requests 5 1600000000"),
+            msg("app.requests 5 1600000060"),
+            msg("app.requests 5.0 1600000000"),
+            msg("app.errors 5 1600000000"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 4);
+        assert!(set.contains(&msg("app.requests 5 1600000060")));
+    }
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "stub")]
+
+use graphyne::{GraphiteClient, GraphiteMessage};
+
+#[test]
+fn test_stub_sends_without_a_server() {
+    // Nothing listens here; a real client would fail to connect.
+    let mut client = GraphiteClient::builder()
+        .address("127.0.0.1")
+        .port(20118)
+        .build()
+        .unwrap();
+    let msg = GraphiteMessage::new("test.stub", "1");
+    assert_eq!(client.send_message(&msg).unwrap(), msg.to_string().len());
+    // Retargeting doesn't connect either.
+    client.update_target("127.0.0.1:20126").unwrap();
+    assert_eq!(client.send_message(&msg).unwrap(), msg.to_string().len());
+}
+
+#[test]
+fn test_stub_keeps_null_clients_recording() {
+    let mut client = GraphiteClient::null();
+    client
+        .send_message(&GraphiteMessage::new("test.stub", "1"))
+        .unwrap();
+    assert_eq!(client.sent_messages().len(), 1);
+}